@@ -0,0 +1,134 @@
+//! Fingerprinting of the static assets, which publishes them with content hashes in their names
+//! like `css/style.3f2a1c9e.css`, so that they can be cached forever.
+
+use crate::config::{get_allay_config, get_asset_config, static_dirs};
+use crate::file::{self, FileError, FileResult};
+use crate::{read, write};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use tracing::warn;
+
+/// The manifest of the fingerprinted assets in the publish directory,
+/// mapping the original paths to the fingerprinted ones
+pub const ASSET_MANIFEST_FILE: &str = "assets.json";
+
+/// The number of hex digits of the content hash in the file names
+const HASH_LEN: usize = 8;
+
+/// A fingerprinted static asset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// the source file in the workspace
+    pub source: PathBuf,
+    /// the fingerprinted path relative to the publish directory
+    pub path: PathBuf,
+    lastmod: u64,
+}
+
+/// All the fingerprinted static assets, keyed by their paths relative to the publish directory
+#[derive(Debug, Default)]
+pub struct AssetManifest {
+    assets: RwLock<HashMap<PathBuf, Fingerprint>>,
+}
+
+impl AssetManifest {
+    pub fn get_instance() -> &'static AssetManifest {
+        static INSTANCE: OnceLock<AssetManifest> = OnceLock::new();
+        INSTANCE.get_or_init(AssetManifest::default)
+    }
+
+    /// Check if a static asset should be fingerprinted by the `[assets]` config
+    pub fn should_fingerprint<P: AsRef<Path>>(path: P) -> bool {
+        let config = get_asset_config();
+        config.fingerprint
+            && path
+                .as_ref()
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| config.extensions.iter().any(|e| e == ext))
+    }
+
+    /// Find the source file of a static asset. The site static files override the theme ones,
+    /// which override the parent theme ones, and the assets generated into the publish directory
+    /// come last, like compiled stylesheets.
+    fn source_of(path: &Path) -> Option<PathBuf> {
+        static_dirs()
+            .into_iter()
+            .chain([PathBuf::from(&get_allay_config().publish_dir)])
+            .map(|dir| file::workspace(dir).join(path))
+            .find(|source| source.is_file())
+    }
+
+    /// Fingerprint a static asset like `css/style.css`, using the cache if its source is unchanged
+    pub fn fingerprint<P: AsRef<Path>>(&self, path: P) -> FileResult<Fingerprint> {
+        let path = path.as_ref();
+        let source = Self::source_of(path).ok_or_else(|| FileError::FileNotFound(path.into()))?;
+        let lastmod = file::last_modified(&source)?;
+
+        if let Some(asset) = read!(self.assets).get(path)
+            && asset.source == source
+            && asset.lastmod == lastmod
+        {
+            return Ok(asset.clone());
+        }
+
+        let content = std::fs::read(&source)?;
+        let asset = Fingerprint {
+            path: fingerprinted_path(path, &content_hash(&content)),
+            source,
+            lastmod,
+        };
+        write!(self.assets).insert(path.into(), asset.clone());
+        Ok(asset)
+    }
+
+    /// Get the last fingerprint of a static asset without checking its source
+    pub fn lookup<P: AsRef<Path>>(&self, path: P) -> Option<Fingerprint> {
+        read!(self.assets).get(path.as_ref()).cloned()
+    }
+
+    /// Check if a path relative to the publish directory is a fingerprinted asset,
+    /// like `css/style.3f2a1c9e.css`, which never changes
+    pub fn is_fingerprinted<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+        read!(self.assets).values().any(|asset| asset.path == path)
+    }
+
+    pub fn remove<P: AsRef<Path>>(&self, path: P) {
+        write!(self.assets).remove(path.as_ref());
+    }
+
+    /// Write the manifest into the publish directory
+    pub fn dump(&self) {
+        let manifest: BTreeMap<_, _> = read!(self.assets)
+            .iter()
+            .map(|(path, asset)| (path.to_string_lossy().to_string(), asset.path.clone()))
+            .collect();
+        let path = file::workspace(&get_allay_config().publish_dir).join(ASSET_MANIFEST_FILE);
+        if let Ok(content) = serde_json::to_string_pretty(&manifest)
+            && file::write_file(&path, &content).is_ok()
+        {
+        } else {
+            warn!("Failed to dump asset manifest to file.");
+        }
+    }
+}
+
+/// The short hex SHA-256 hash of a content
+pub fn content_hash(content: &[u8]) -> String {
+    let hash = Sha256::digest(content);
+    hash.iter().map(|b| format!("{:02x}", b)).collect::<String>()[..HASH_LEN].to_string()
+}
+
+/// Insert a content hash before the extension of a path, like `css/style.css` -> `css/style.3f2a1c9e.css`
+pub fn fingerprinted_path<P: AsRef<Path>>(path: P, hash: &str) -> PathBuf {
+    let path = path.as_ref();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, hash, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, hash),
+    };
+    path.with_file_name(name)
+}
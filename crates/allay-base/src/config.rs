@@ -2,6 +2,7 @@ pub mod allay;
 pub mod cli;
 pub mod site;
 pub mod theme;
+pub mod validate;
 
 pub use allay::*;
 pub use cli::*;
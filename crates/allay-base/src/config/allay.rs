@@ -1,7 +1,14 @@
+use crate::config::{get_site_config, try_get_cli_config};
 use crate::file;
+use crate::log::NoPanicUnwrap;
 use serde::{Deserialize, Serialize};
 use std::sync::OnceLock;
 
+/// The section of the site config with the settings of [`AllayConfig`]
+const BUILD: &str = "build";
+/// The prefix of the environment variables overriding the settings, like `ALLAY_PUBLISH_DIR`
+const ENV_PREFIX: &str = "ALLAY_";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AllayConfig {
     #[serde(default = "AllayConfig::default_content_dir")]
@@ -18,6 +25,14 @@ pub struct AllayConfig {
     pub theme_dir: String,
     #[serde(default = "AllayConfig::default_log_dir")]
     pub log_dir: String,
+    #[serde(default = "AllayConfig::default_i18n_dir")]
+    pub i18n_dir: String,
+    #[serde(default = "AllayConfig::default_cache_dir")]
+    pub cache_dir: String,
+    #[serde(default = "AllayConfig::default_archetype_dir")]
+    pub archetype_dir: String,
+    #[serde(default = "AllayConfig::default_template_dir")]
+    pub template_dir: String,
 }
 
 impl Default for AllayConfig {
@@ -30,6 +45,10 @@ impl Default for AllayConfig {
             shortcode_dir: Self::default_shortcode_dir(),
             theme_dir: Self::default_theme_dir(),
             log_dir: Self::default_log_dir(),
+            i18n_dir: Self::default_i18n_dir(),
+            cache_dir: Self::default_cache_dir(),
+            archetype_dir: Self::default_archetype_dir(),
+            template_dir: Self::default_template_dir(),
         }
     }
 }
@@ -62,6 +81,48 @@ impl AllayConfig {
     fn default_log_dir() -> String {
         "logs".into()
     }
+
+    fn default_i18n_dir() -> String {
+        "i18n".into()
+    }
+
+    fn default_cache_dir() -> String {
+        ".allay".into()
+    }
+
+    fn default_archetype_dir() -> String {
+        "archetypes".into()
+    }
+
+    fn default_template_dir() -> String {
+        "templates".into()
+    }
+
+    /// The settings by their keys, which are also the keys in the `[build]` section of the site config
+    fn entries_mut(&mut self) -> [(&'static str, &mut String); 11] {
+        [
+            ("content_dir", &mut self.content_dir),
+            ("publish_dir", &mut self.publish_dir),
+            ("statics_dir", &mut self.statics_dir),
+            ("plugin_dir", &mut self.plugin_dir),
+            ("shortcode_dir", &mut self.shortcode_dir),
+            ("theme_dir", &mut self.theme_dir),
+            ("log_dir", &mut self.log_dir),
+            ("i18n_dir", &mut self.i18n_dir),
+            ("cache_dir", &mut self.cache_dir),
+            ("archetype_dir", &mut self.archetype_dir),
+            ("template_dir", &mut self.template_dir),
+        ]
+    }
+
+    /// Override the settings found by their keys
+    pub fn override_with(&mut self, lookup: impl Fn(&str) -> Option<String>) {
+        for (key, value) in self.entries_mut() {
+            if let Some(new) = lookup(key) {
+                *value = new;
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -72,6 +133,14 @@ pub enum Environment {
 }
 
 impl Environment {
+    /// The name of the environment, like `production`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Environment::Development => "development",
+            Environment::Production => "production",
+        }
+    }
+
     /// Check if the environment is development
     pub fn is_dev(&self) -> bool {
         matches!(self, Environment::Development)
@@ -95,17 +164,35 @@ pub fn get_env() -> &'static Environment {
     })
 }
 
+/// Get the settings of the directories, from the user config in `~/.config/allay/config.toml`,
+/// overridden by the `[build]` section of the site config, the `ALLAY_*` environment variables
+/// and the CLI flags in turn
 pub fn get_allay_config() -> &'static AllayConfig {
     static INSTANCE: OnceLock<AllayConfig> = OnceLock::new();
 
     INSTANCE.get_or_init(|| {
         let config_file = std::env::home_dir().map(|p| p.join(".config/allay/config.toml"));
-        if let Some(config_file) = config_file
+        let mut config = if let Some(config_file) = config_file
             && let Ok(config) = file::read_file_string(config_file)
         {
             toml::from_str(&config).unwrap_or_default()
         } else {
             AllayConfig::default()
+        };
+
+        if let Some(build) = get_site_config().get(BUILD) {
+            let build = build.as_obj().expect_("The build config must be a table");
+            config.override_with(|key| {
+                let value = build.get(key)?;
+                let value = value.as_str().expect_on(|_| format!("`{key}` must be a string"));
+                Some(value.into())
+            });
+        }
+        config
+            .override_with(|key| std::env::var(format!("{ENV_PREFIX}{}", key.to_uppercase())).ok());
+        if let Some(cli) = try_get_cli_config() {
+            config.override_with(|key| cli.dirs.get(key));
         }
+        config
     })
 }
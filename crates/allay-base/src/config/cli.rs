@@ -22,19 +22,66 @@ pub struct AllayCLI {
     #[arg(long, global = true, default_value_t = false)]
     pub online: bool,
 
+    #[command(flatten)]
+    pub dirs: DirArgs,
+
     /// Subcommand to execute
     #[command(subcommand)]
     pub command: CLICommand,
 }
 
+static CLI: OnceLock<AllayCLI> = OnceLock::new();
+
 pub fn get_cli_config() -> &'static AllayCLI {
-    static INSTANCE: OnceLock<AllayCLI> = OnceLock::new();
-    INSTANCE.get_or_init(AllayCLI::parse)
+    CLI.get_or_init(AllayCLI::parse)
+}
+
+/// Get the CLI arguments only if already parsed, which they are not in the tests
+pub fn try_get_cli_config() -> Option<&'static AllayCLI> {
+    CLI.get()
+}
+
+/// The directories of the site, overriding the config and the `ALLAY_*` environment variables
+#[derive(Args, Debug, Clone, Default)]
+pub struct DirArgs {
+    /// Directory of the contents (default: contents)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub content_dir: Option<String>,
+
+    /// Directory to publish the site to (default: public)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub publish_dir: Option<String>,
+
+    /// Directory of the themes (default: themes)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub theme_dir: Option<String>,
+
+    /// Directory of the shortcodes (default: shortcodes)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub shortcode_dir: Option<String>,
+
+    /// Directory of the plugins (default: plugins)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub plugin_dir: Option<String>,
+}
+
+impl DirArgs {
+    /// Get a directory by its key in the config, like `publish_dir`
+    pub fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "content_dir" => self.content_dir.clone(),
+            "publish_dir" => self.publish_dir.clone(),
+            "theme_dir" => self.theme_dir.clone(),
+            "shortcode_dir" => self.shortcode_dir.clone(),
+            "plugin_dir" => self.plugin_dir.clone(),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
 pub enum CLICommand {
-    /// Create a new Allay site in the specified directory
+    /// Create a new Allay site in the specified directory, or a new content with `new content`
     New(NewArgs),
     /// Initialize a new Allay site in the current directory
     Init(InitArgs),
@@ -45,15 +92,41 @@ pub enum CLICommand {
     /// Manage Allay plugins
     #[command(subcommand)]
     Plugin(PluginCommand),
+    /// Manage the themes of the site
+    #[command(subcommand)]
+    Theme(ThemeCommand),
 }
 
 #[derive(Args, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct NewArgs {
     /// Directory to create the new site in
-    pub dir: String,
+    #[arg(required = true)]
+    pub dir: Option<String>,
     /// Skip cloning the default theme
     #[arg(long, default_value_t = false)]
     pub skip_theme: bool,
+
+    #[command(subcommand)]
+    pub command: Option<NewCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum NewCommand {
+    /// Create a new content from its archetype, like `allay new content posts/my-post.md`
+    Content(NewContentArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct NewContentArgs {
+    /// Path of the content relative to the content directory
+    pub path: String,
+    /// Create a page bundle, i.e. `posts/my-post/index.md` for `posts/my-post.md`
+    #[arg(long, default_value_t = false)]
+    pub bundle: bool,
+    /// The archetype to use (default: the section of the content, then `default`)
+    #[arg(short, long)]
+    pub kind: Option<String>,
 }
 
 #[derive(Args, Debug)]
@@ -64,7 +137,52 @@ pub struct InitArgs {
 }
 
 #[derive(Args, Debug)]
-pub struct BuildArgs {}
+pub struct BuildArgs {
+    #[command(flatten)]
+    pub preview: PreviewArgs,
+
+    /// Remove everything in the output directory before building, except the kept files
+    #[arg(long, default_value_t = false)]
+    pub clean: bool,
+
+    /// Allow --clean to empty a publish directory outside of the site root
+    #[arg(long, default_value_t = false, requires = "clean")]
+    pub clean_outside: bool,
+
+    /// Print a report of the build with timings and statistics
+    #[arg(long, default_value_t = false)]
+    pub report: bool,
+
+    /// Write the report of the build as JSON to the file
+    #[arg(long, value_name = "FILE")]
+    pub report_json: Option<String>,
+
+    /// The number of the slowest pages in the report
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    pub slowest: usize,
+}
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct PreviewArgs {
+    /// Include the contents marked as draft
+    #[arg(long, default_value_t = false)]
+    pub drafts: bool,
+
+    /// Include the contents whose publish date is in the future
+    #[arg(long, default_value_t = false)]
+    pub future: bool,
+}
+
+impl CLICommand {
+    /// Get the preview options of the command, which are all off for commands not publishing contents
+    pub fn preview(&self) -> PreviewArgs {
+        match self {
+            CLICommand::Build(args) => args.preview.clone(),
+            CLICommand::Serve(args) => args.preview.clone(),
+            _ => PreviewArgs::default(),
+        }
+    }
+}
 
 #[derive(Args, Debug)]
 pub struct ServeArgs {
@@ -79,17 +197,99 @@ pub struct ServeArgs {
     /// Open the site in the browser
     #[arg(long)]
     pub open: bool,
+
+    #[command(flatten)]
+    pub preview: PreviewArgs,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum PluginCommand {
+    /// List the installed plugins with their locked versions and hashes
+    List,
+    /// Install a plugin from a local file, a URL or a local registry
+    Add(PluginAddArgs),
+    /// Remove an installed plugin
+    Remove(PluginNameArgs),
+    /// Show the details of an installed plugin
+    Info(PluginNameArgs),
     /// Update installed plugins
     Update(PluginUpdateArgs),
 }
 
+#[derive(Args, Debug)]
+pub struct PluginAddArgs {
+    /// Path or URL of the `.wasm` file, or `name[@version]` in the registry
+    pub source: String,
+    /// Name of the plugin (default: the name of the file or in the registry)
+    #[arg(short, long)]
+    pub name: Option<String>,
+    /// Local directory of a plugin registry with an `index.toml`
+    #[arg(long)]
+    pub registry: Option<String>,
+    /// Expected SHA-256 hash of the `.wasm` file, checked before installing it
+    #[arg(long, value_name = "HASH")]
+    pub sha256: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct PluginNameArgs {
+    /// Name of the plugin
+    pub name: String,
+}
+
 #[derive(Args, Debug)]
 pub struct PluginUpdateArgs {
     /// Name of the plugin to update (if empty, update all plugins)
     #[arg(short, long)]
     pub name: Option<String>,
+    /// Accept a release whose hash differs from the locked one under the same tag
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ThemeCommand {
+    /// List the installed themes
+    List,
+    /// Install a theme from a local directory or a git repository
+    Add(ThemeAddArgs),
+    /// Remove an installed theme
+    Remove(ThemeNameArgs),
+    /// Use an installed theme for the site
+    Use(ThemeNameArgs),
+    /// Update the themes installed from git repositories
+    Update(ThemeUpdateArgs),
+    /// Show the params of a theme with their defaults
+    Params(ThemeParamsArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ThemeAddArgs {
+    /// Local directory or git URL of the theme
+    pub source: String,
+    /// Name of the theme directory (default: the last part of the source)
+    #[arg(short, long)]
+    pub name: Option<String>,
+    /// Use the theme for the site after installing it
+    #[arg(long = "use", default_value_t = false)]
+    pub use_theme: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ThemeNameArgs {
+    /// Name of the theme
+    pub name: String,
+}
+
+#[derive(Args, Debug)]
+pub struct ThemeParamsArgs {
+    /// Name of the theme (default: the theme used by the site)
+    pub name: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ThemeUpdateArgs {
+    /// Name of the theme to update (if empty, update all themes)
+    #[arg(short, long)]
+    pub name: Option<String>,
 }
@@ -1,11 +1,23 @@
-use crate::config::get_allay_config;
+use crate::config::{CLICommand, Environment, get_allay_config, get_cli_config, get_env};
 use crate::data::{AllayData, AllayObject};
 use crate::file::{read_file_string, workspace};
 use crate::log::NoPanicUnwrap;
+use crate::url::slugify;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 use std::sync::Arc;
 use std::{path::PathBuf, sync::OnceLock};
+use toml_edit::{DocumentMut, Item};
 
 pub const SITE_CONFIG_FILE: &str = "allay.toml";
+/// The config of each plugin, keyed by the plugin names
+pub const PLUGIN_CONFIG_FILE: &str = "plugin.toml";
+
+/// Get the config file overlaying the site config in an environment, like `allay.production.toml`
+pub fn env_config_file(env: &Environment) -> String {
+    format!("allay.{}.toml", env.name())
+}
 
 pub const DEFAULT_SITE_CONFIG: &str = r#"# Default Allay site configuration
 base_url = "http://your-site.com/"
@@ -20,25 +32,49 @@ pub fn get_site_config() -> Arc<AllayObject> {
 
     INSTANCE
         .get_or_init(|| {
-            if let Ok(config) = read_file_string(workspace(SITE_CONFIG_FILE))
-                && let Ok(config) = AllayData::from_toml(&config)
-            {
-                return Arc::new(config);
-            }
+            // the default config is only used without `allay.toml`, never for an invalid one
+            let mut config = match read_file_string(workspace(SITE_CONFIG_FILE)) {
+                Ok(content) => AllayData::from_toml(&content)
+                    .expect_on(|e| format!("Invalid {SITE_CONFIG_FILE}: {e}")),
+                Err(_) => AllayData::from_toml(DEFAULT_SITE_CONFIG)
+                    .expect("Failed to parse default config"),
+            };
 
-            Arc::new(
-                AllayData::from_toml(DEFAULT_SITE_CONFIG).expect("Failed to parse default config"),
-            )
+            // the config of the environment overrides the tables key by key
+            let overlay = env_config_file(get_env());
+            if let Ok(content) = read_file_string(workspace(&overlay)) {
+                let overlay =
+                    AllayData::from_toml(&content).expect_on(|e| format!("Invalid {overlay}: {e}"));
+                AllayData::merge_object(&mut config, overlay);
+            }
+            Arc::new(config)
         })
         .clone()
 }
 
+/// Set a top-level string in the content of a config file, keeping the rest of it as it is,
+/// with the comments of the old value. A new key is added before the first table.
+pub fn set_config_string(
+    content: &str,
+    key: &str,
+    value: &str,
+) -> Result<String, toml_edit::TomlError> {
+    let mut document = content.parse::<DocumentMut>()?;
+    let mut value = toml_edit::Value::from(value);
+    if let Some(old) = document.get(key).and_then(Item::as_value) {
+        *value.decor_mut() = old.decor().clone();
+    }
+    document.insert(key, Item::Value(value));
+    Ok(document.to_string())
+}
+
+/// The theme used if the site config does not choose one
+pub const DEFAULT_THEME_NAME: &str = "Axolotl";
+
 pub fn get_theme_path() -> &'static PathBuf {
     static INSTANCE: OnceLock<PathBuf> = OnceLock::new();
 
     INSTANCE.get_or_init(|| {
-        const DEFAULT_THEME_NAME: &str = "Axolotl";
-
         let dir = &get_allay_config().theme_dir;
         let chosen = get_site_config()
             .get("theme")
@@ -49,3 +85,531 @@ pub fn get_theme_path() -> &'static PathBuf {
         PathBuf::from(dir).join(chosen)
     })
 }
+
+/// The `[related]` section of the site config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelatedConfig {
+    /// the maximum number of related pages for each page
+    #[serde(default = "RelatedConfig::default_limit")]
+    pub limit: usize,
+    /// the front-matter keys to compare and their weights
+    #[serde(default = "RelatedConfig::default_weights")]
+    pub weights: HashMap<String, i64>,
+}
+
+impl Default for RelatedConfig {
+    fn default() -> Self {
+        Self {
+            limit: Self::default_limit(),
+            weights: Self::default_weights(),
+        }
+    }
+}
+
+impl RelatedConfig {
+    fn default_limit() -> usize {
+        5
+    }
+
+    fn default_weights() -> HashMap<String, i64> {
+        HashMap::from([("tags".into(), 1), ("categories".into(), 1)])
+    }
+}
+
+pub fn get_related_config() -> &'static RelatedConfig {
+    const RELATED: &str = "related";
+    static INSTANCE: OnceLock<RelatedConfig> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| match get_site_config().get(RELATED) {
+        Some(data) => data.parse_as().expect_on(|e| format!("Invalid related config: {e}")),
+        None => RelatedConfig::default(),
+    })
+}
+
+/// A format of the feeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+}
+
+impl FeedFormat {
+    /// The file name of the feed in its directory
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "rss.xml",
+            FeedFormat::Atom => "atom.xml",
+        }
+    }
+}
+
+/// The `[feed]` section of the site config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedConfig {
+    /// whether to generate feeds
+    #[serde(default = "FeedConfig::default_enabled")]
+    pub enabled: bool,
+    /// the formats of the feeds
+    #[serde(default = "FeedConfig::default_formats")]
+    pub formats: Vec<FeedFormat>,
+    /// the maximum number of items in each feed
+    #[serde(default = "FeedConfig::default_limit")]
+    pub limit: usize,
+    /// whether to include the full content of pages rather than their summaries
+    #[serde(default)]
+    pub full_content: bool,
+    /// whether to generate a feed for each section, i.e. top-level directory of contents
+    #[serde(default = "FeedConfig::default_sections")]
+    pub sections: bool,
+    /// the front-matter keys whose terms have their own feeds
+    #[serde(default = "FeedConfig::default_taxonomies")]
+    pub taxonomies: Vec<String>,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            enabled: Self::default_enabled(),
+            formats: Self::default_formats(),
+            limit: Self::default_limit(),
+            full_content: false,
+            sections: Self::default_sections(),
+            taxonomies: Self::default_taxonomies(),
+        }
+    }
+}
+
+impl FeedConfig {
+    fn default_enabled() -> bool {
+        true
+    }
+
+    fn default_formats() -> Vec<FeedFormat> {
+        vec![FeedFormat::Rss, FeedFormat::Atom]
+    }
+
+    fn default_limit() -> usize {
+        20
+    }
+
+    fn default_sections() -> bool {
+        true
+    }
+
+    fn default_taxonomies() -> Vec<String> {
+        vec!["tags".into(), "categories".into()]
+    }
+}
+
+pub fn get_feed_config() -> &'static FeedConfig {
+    const FEED: &str = "feed";
+    static INSTANCE: OnceLock<FeedConfig> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| match get_site_config().get(FEED) {
+        Some(data) => data.parse_as().expect_on(|e| format!("Invalid feed config: {e}")),
+        None => FeedConfig::default(),
+    })
+}
+
+/// The permalink patterns of the sections, like `posts = "/:year/:month/:slug/"` in `[permalinks]`.
+/// A section is a top-level directory of the contents.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PermalinkConfig {
+    pub patterns: BTreeMap<String, String>,
+}
+
+impl PermalinkConfig {
+    /// Resolve the permalink of a content path without language suffix, like `posts/foo.md`,
+    /// by the pattern of its section. The placeholders are:
+    ///
+    /// - `:year`, `:month` and `:day`: the `date` of the page
+    /// - `:slug`: the `slug` of the page, or the file name (the directory name for `index.md`)
+    /// - `:title`: the slugified `title` of the page
+    /// - `:section`: the section of the page
+    ///
+    /// Returns `None` if the section has no pattern or a placeholder is missing in the front matter.
+    pub fn resolve<P: AsRef<Path>>(&self, path: P, meta: &AllayObject) -> Option<String> {
+        let path = path.as_ref();
+        let section = path.components().next()?.as_os_str().to_str()?;
+        // the pages directly under the content directory and the index pages of sections are kept
+        let parent = path.parent()?;
+        if parent.as_os_str().is_empty()
+            || parent == Path::new(section) && path.file_stem().is_some_and(|s| s == "index")
+        {
+            return None;
+        }
+        let pattern = self.patterns.get(section)?;
+
+        let str_field =
+            |key: &str| meta.get(key).and_then(|data| data.as_str().ok().map(String::from));
+        let date = meta.get("date").and_then(|data| match data.as_ref() {
+            AllayData::String(text) => Some(text.to_string()),
+            // a TOML datetime
+            AllayData::Object(obj) => obj.values().next().map(|v| v.to_string()),
+            _ => None,
+        });
+        // dates in all the supported formats start with `YYYY-MM-DD`
+        let date_part = |range: std::ops::Range<usize>| {
+            let part = date.as_ref()?.get(range)?;
+            part.chars().all(|c| c.is_ascii_digit()).then(|| part.to_string())
+        };
+        let slug = || {
+            let name = match path.file_stem()?.to_str()? {
+                "index" => path.parent()?.file_name()?.to_str()?,
+                name => name,
+            };
+            Some(str_field("slug").unwrap_or_else(|| slugify(name)))
+        };
+
+        let mut link = pattern.trim_start_matches('/').to_string();
+        for (placeholder, value) in [
+            (":year", date_part(0..4)),
+            (":month", date_part(5..7)),
+            (":day", date_part(8..10)),
+            (":section", Some(section.to_string())),
+            (":title", str_field("title").map(|title| slugify(&title))),
+            (":slug", slug()),
+        ] {
+            if link.contains(placeholder) {
+                link = link.replace(placeholder, &value.filter(|v| !v.is_empty())?);
+            }
+        }
+        Some(link)
+    }
+}
+
+pub fn get_permalink_config() -> &'static PermalinkConfig {
+    const PERMALINKS: &str = "permalinks";
+    static INSTANCE: OnceLock<PermalinkConfig> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| match get_site_config().get(PERMALINKS) {
+        Some(data) => data.parse_as().expect_on(|e| format!("Invalid permalinks config: {e}")),
+        None => PermalinkConfig::default(),
+    })
+}
+
+/// The output formats of the sections, like `posts = ["html", "json"]` in `[outputs]`.
+/// Every format other than HTML is rendered by the theme template of its extension, like `page.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct OutputConfig {
+    pub sections: BTreeMap<String, Vec<String>>,
+}
+
+impl OutputConfig {
+    /// The format always published for every page
+    pub const HTML: &str = "html";
+
+    /// Get the output formats of a content path like `posts/foo.md`, starting with HTML.
+    /// The `outputs` in the front matter, as a string or a list, overrides the formats of the section.
+    pub fn formats_of<P: AsRef<Path>>(&self, path: P, meta: &AllayObject) -> Vec<String> {
+        let path = path.as_ref();
+        let formats = match meta.get("outputs").map(AsRef::as_ref) {
+            Some(AllayData::List(list)) => list.iter().map(|format| format.to_string()).collect(),
+            Some(AllayData::String(format)) => vec![format.to_string()],
+            _ => path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .and_then(|_| path.components().next()?.as_os_str().to_str())
+                .and_then(|section| self.sections.get(section).cloned())
+                .unwrap_or_default(),
+        };
+
+        let mut result = vec![Self::HTML.to_string()];
+        for format in formats {
+            let format = format.trim().to_lowercase();
+            if !format.is_empty() && !result.contains(&format) {
+                result.push(format);
+            }
+        }
+        result
+    }
+}
+
+pub fn get_output_config() -> &'static OutputConfig {
+    const OUTPUTS: &str = "outputs";
+    static INSTANCE: OnceLock<OutputConfig> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| match get_site_config().get(OUTPUTS) {
+        Some(data) => data.parse_as().expect_on(|e| format!("Invalid outputs config: {e}")),
+        None => OutputConfig::default(),
+    })
+}
+
+/// The settings of the static assets, from `[assets]` in the site config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetConfig {
+    /// if the static assets are also published with content hashes in their names
+    #[serde(default)]
+    pub fingerprint: bool,
+    /// the extensions of the static assets to fingerprint
+    #[serde(default = "AssetConfig::default_extensions")]
+    pub extensions: Vec<String>,
+}
+
+impl Default for AssetConfig {
+    fn default() -> Self {
+        Self {
+            fingerprint: false,
+            extensions: Self::default_extensions(),
+        }
+    }
+}
+
+impl AssetConfig {
+    fn default_extensions() -> Vec<String> {
+        vec!["css".into(), "js".into()]
+    }
+}
+
+pub fn get_asset_config() -> &'static AssetConfig {
+    const ASSETS: &str = "assets";
+    static INSTANCE: OnceLock<AssetConfig> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| match get_site_config().get(ASSETS) {
+        Some(data) => data.parse_as().expect_on(|e| format!("Invalid assets config: {e}")),
+        None => AssetConfig::default(),
+    })
+}
+
+/// The settings of the image processing, from `[images]` in the site config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageConfig {
+    /// the widths of the resized variants of the images in Markdown, no processing if empty
+    #[serde(default)]
+    pub widths: Vec<u32>,
+    /// if the WebP variants are also generated
+    #[serde(default = "ImageConfig::default_webp")]
+    pub webp: bool,
+    /// the quality of the resized JPEG images and the WebP variants, from 1 to 100
+    #[serde(default = "ImageConfig::default_quality")]
+    pub quality: u8,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            widths: vec![],
+            webp: Self::default_webp(),
+            quality: Self::default_quality(),
+        }
+    }
+}
+
+impl ImageConfig {
+    fn default_webp() -> bool {
+        true
+    }
+
+    fn default_quality() -> u8 {
+        80
+    }
+}
+
+pub fn get_image_config() -> &'static ImageConfig {
+    const IMAGES: &str = "images";
+    static INSTANCE: OnceLock<ImageConfig> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| match get_site_config().get(IMAGES) {
+        Some(data) => data.parse_as().expect_on(|e| format!("Invalid images config: {e}")),
+        None => ImageConfig::default(),
+    })
+}
+
+/// The settings of the minification of the published files, from `[minify]` in the site config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MinifyConfig {
+    /// if the published files are minified by `allay build` in production
+    #[serde(default = "MinifyConfig::default_true")]
+    pub enabled: bool,
+    /// if the HTML pages are minified
+    #[serde(default = "MinifyConfig::default_true")]
+    pub html: bool,
+    /// if the stylesheets and the inline styles are minified
+    #[serde(default = "MinifyConfig::default_true")]
+    pub css: bool,
+    /// if the scripts and the inline scripts are minified
+    #[serde(default = "MinifyConfig::default_true")]
+    pub js: bool,
+}
+
+impl Default for MinifyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            html: true,
+            css: true,
+            js: true,
+        }
+    }
+}
+
+impl MinifyConfig {
+    fn default_true() -> bool {
+        true
+    }
+
+    /// Check if the minification is active, which is never for `allay serve`,
+    /// so that the pages are readable during the development
+    pub fn is_active(&self) -> bool {
+        self.enabled
+            && matches!(get_cli_config().command, CLICommand::Build(_))
+            && get_env().is_prod()
+    }
+}
+
+pub fn get_minify_config() -> &'static MinifyConfig {
+    const MINIFY: &str = "minify";
+    static INSTANCE: OnceLock<MinifyConfig> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| match get_site_config().get(MINIFY) {
+        Some(data) => data.parse_as().expect_on(|e| format!("Invalid minify config: {e}")),
+        None => MinifyConfig::default(),
+    })
+}
+
+/// The settings of `allay build`, from `[build]` in the site config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildConfig {
+    /// if the outputs of the last build no longer produced are removed
+    #[serde(default = "BuildConfig::default_prune")]
+    pub prune: bool,
+    /// the glob patterns of the files in the publish directory never removed, like `CNAME`
+    #[serde(default)]
+    pub keep: Vec<String>,
+}
+
+impl Default for BuildConfig {
+    fn default() -> Self {
+        Self {
+            prune: Self::default_prune(),
+            keep: vec![],
+        }
+    }
+}
+
+impl BuildConfig {
+    fn default_prune() -> bool {
+        true
+    }
+}
+
+pub fn get_build_config() -> &'static BuildConfig {
+    const BUILD: &str = "build";
+    static INSTANCE: OnceLock<BuildConfig> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| match get_site_config().get(BUILD) {
+        Some(data) => data.parse_as().expect_on(|e| format!("Invalid build config: {e}")),
+        None => BuildConfig::default(),
+    })
+}
+
+/// A redirect rule of the site, like `[[redirects]]` in the site config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedirectRule {
+    /// the old URL to redirect from, like `/old/path`
+    pub from: String,
+    /// the URL to redirect to, either a site URL or an external one
+    pub to: String,
+    /// the HTTP status code of the redirect, 301 (permanent) by default
+    #[serde(default = "RedirectRule::default_status")]
+    pub status: u16,
+}
+
+impl RedirectRule {
+    fn default_status() -> u16 {
+        301
+    }
+}
+
+pub fn get_redirects() -> &'static [RedirectRule] {
+    const REDIRECTS: &str = "redirects";
+    static INSTANCE: OnceLock<Vec<RedirectRule>> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| match get_site_config().get(REDIRECTS) {
+        Some(data) => data.parse_as().expect_on(|e| format!("Invalid redirects config: {e}")),
+        None => vec![],
+    })
+}
+
+/// A language of a multilingual site, like `[languages.zh]` in the site config
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Language {
+    /// the display name of the language, like `中文` (default to the language code)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// The language settings of the site, from `default_language` and `[languages]` in the site config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageConfig {
+    /// the language of the files without a language suffix, whose URLs are not prefixed
+    #[serde(default = "LanguageConfig::default_language")]
+    pub default_language: String,
+    /// all the languages of the site, keyed by their codes like `en` and `zh`
+    #[serde(default)]
+    pub languages: BTreeMap<String, Language>,
+}
+
+impl Default for LanguageConfig {
+    fn default() -> Self {
+        Self {
+            default_language: Self::default_language(),
+            languages: BTreeMap::new(),
+        }
+    }
+}
+
+impl LanguageConfig {
+    fn default_language() -> String {
+        "en".into()
+    }
+
+    /// Split the language suffix from a content path,
+    /// like `posts/foo.zh.md` -> (`zh`, `posts/foo.md`).
+    /// Files without a suffix of a configured language are in the default language.
+    pub fn split_language<P: AsRef<Path>>(&self, path: P) -> (String, PathBuf) {
+        let path = path.as_ref();
+        let stem = path.file_stem().map(Path::new);
+        if let Some(lang) = stem.and_then(Path::extension).and_then(|lang| lang.to_str())
+            && self.languages.contains_key(lang)
+        {
+            let mut name = stem.and_then(Path::file_stem).unwrap_or_default().to_os_string();
+            if let Some(ext) = path.extension() {
+                name.push(".");
+                name.push(ext);
+            }
+            return (lang.into(), path.with_file_name(name));
+        }
+        (self.default_language.clone(), path.into())
+    }
+
+    /// Get the published path of a content path, prefixed by its language if not the default one,
+    /// like `posts/foo.zh.md` -> `zh/posts/foo.md`
+    pub fn localize<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let (lang, path) = self.split_language(path);
+        if lang == self.default_language {
+            path
+        } else {
+            Path::new(&lang).join(path)
+        }
+    }
+}
+
+pub fn get_language_config() -> &'static LanguageConfig {
+    static INSTANCE: OnceLock<LanguageConfig> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| {
+        let site = get_site_config();
+        let config = AllayObject::from_iter(
+            ["default_language", "languages"]
+                .into_iter()
+                .filter_map(|key| site.get(key).map(|data| (key.to_string(), data.clone()))),
+        );
+        AllayData::from(config)
+            .parse_as()
+            .expect_on(|e| format!("Invalid language config: {e}"))
+    })
+}
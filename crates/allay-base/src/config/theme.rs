@@ -1,8 +1,10 @@
-use crate::config::get_theme_path;
+use crate::config::{get_allay_config, get_theme_path};
+use crate::data::{AllayData, AllayObject};
 use crate::file;
-use crate::log::NoPanicUnwrap;
+use crate::log::{NoPanicUnwrap, show_error};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -14,6 +16,9 @@ pub struct ThemeMeta {
     pub description: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub license: Option<String>,
+    /// the name of the parent theme, whose files are used unless the theme has its own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -101,17 +106,152 @@ pub struct ThemeConfig {
     pub config: FileConfig,
     #[serde(default)]
     pub dependencies: Dependencies,
+    /// the default params of the theme, overridden by the `[params]` of the site
+    #[serde(default, skip_serializing_if = "toml::Table::is_empty")]
+    pub params: toml::Table,
+    /// the documentation of the params accepted by the theme
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params_schema: BTreeMap<String, ParamSchema>,
+}
+
+/// The documentation of a theme param, like `[params_schema.accent]` in `theme.toml`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParamSchema {
+    /// the type of the param, one of [`ParamSchema::TYPES`]
+    #[serde(rename = "type", default = "ParamSchema::default_type")]
+    pub kind: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl ParamSchema {
+    pub const TYPES: &[&str] = &["any", "string", "integer", "bool", "list", "table"];
+
+    fn default_type() -> String {
+        "any".to_string()
+    }
+}
+
+pub const THEME_CONFIG_FILE: &str = "theme.toml";
+
+/// A theme in the inheritance chain of the site
+#[derive(Debug, Clone)]
+pub struct ThemeLayer {
+    /// the directory of the theme, relative to the site root
+    pub path: PathBuf,
+    pub config: ThemeConfig,
+}
+
+impl ThemeLayer {
+    /// The directory of the custom pages of the theme, relative to the site root
+    pub fn custom_dir(&self) -> PathBuf {
+        self.path.join(&self.config.config.custom_dir)
+    }
+
+    /// The directory of the static files of the theme, relative to the site root
+    pub fn static_dir(&self) -> PathBuf {
+        self.path.join(&self.config.config.static_dir)
+    }
+
+    /// The directory of the templates of the theme, relative to the site root
+    pub fn template_dir(&self) -> PathBuf {
+        self.path.join(&self.config.config.templates.dir)
+    }
+}
+
+fn read_theme_config(theme_path: &Path) -> ThemeConfig {
+    let config_file = file::workspace(theme_path).join(THEME_CONFIG_FILE);
+    let config_str = file::read_file_string(&config_file)
+        .expect_on(|e| format!("Failed to read theme config {}: {e}", config_file.display()));
+    toml::from_str(&config_str).expect_on(|e| format!("Invalid {}: {e}", config_file.display()))
+}
+
+/// The directory of the parent theme named by `extends`, relative to the site root
+pub fn parent_theme_path(config: &ThemeConfig) -> Option<PathBuf> {
+    let parent = config.meta.extends.as_ref()?;
+    Some(PathBuf::from(&get_allay_config().theme_dir).join(parent))
 }
 
+/// Read a theme followed by its parent themes, from the nearest to the farthest
+pub fn read_theme_chain(theme_path: &Path) -> Vec<ThemeLayer> {
+    let mut chain: Vec<ThemeLayer> = vec![];
+    let mut next = Some(theme_path.to_path_buf());
+    while let Some(path) = next {
+        if chain.iter().any(|layer| layer.path == path) {
+            show_error(&format!(
+                "Circular `extends` of the themes at {}",
+                path.display()
+            ));
+        }
+        let config = read_theme_config(&path);
+        next = parent_theme_path(&config);
+        chain.push(ThemeLayer { path, config });
+    }
+    chain
+}
+
+/// Get the theme of the site followed by its parent themes, from the nearest to the farthest
+pub fn get_theme_chain() -> &'static [ThemeLayer] {
+    static INSTANCE: OnceLock<Vec<ThemeLayer>> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| read_theme_chain(get_theme_path()))
+}
+
+/// Get the config of the theme used by the site
 pub fn get_theme_config() -> &'static ThemeConfig {
-    const THEME_CONFIG_FILE: &str = "theme.toml";
-    static THEME_CONFIG: OnceLock<ThemeConfig> = OnceLock::new();
-
-    THEME_CONFIG.get_or_init(|| {
-        let theme_path = file::workspace(get_theme_path());
-        let config_file = theme_path.join(THEME_CONFIG_FILE);
-        let config_str = file::read_file_string(&config_file)
-            .expect_on(|e| format!("Failed to read theme config: {e}"));
-        toml::from_str(&config_str).expect_("Failed to parse theme config")
-    })
+    &get_theme_chain()[0].config
+}
+
+/// Merge the default params of a chain of themes, where the ones of a theme override
+/// its parents' key by key
+pub fn merge_theme_params(chain: &[ThemeLayer]) -> AllayObject {
+    let mut params = AllayObject::default();
+    for layer in chain.iter().rev() {
+        let defaults = AllayData::from_toml_table(layer.config.params.clone())
+            .expect_on(|e| format!("Invalid params of theme {}: {e}", layer.path.display()));
+        AllayData::merge_object(&mut params, defaults);
+    }
+    params
+}
+
+/// Get the default params of the themes of the site, see [`merge_theme_params`]
+pub fn get_theme_params() -> &'static AllayObject {
+    static INSTANCE: OnceLock<AllayObject> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| merge_theme_params(get_theme_chain()))
+}
+
+/// The directories to look up a template in, from the site to the farthest parent theme
+pub fn template_dirs() -> Vec<PathBuf> {
+    let site = PathBuf::from(&get_allay_config().template_dir);
+    let themes = get_theme_chain().iter().map(ThemeLayer::template_dir);
+    [site].into_iter().chain(themes).collect()
+}
+
+/// The directories of the static files, from the site to the farthest parent theme
+pub fn static_dirs() -> Vec<PathBuf> {
+    let site = PathBuf::from(&get_allay_config().statics_dir);
+    let themes = get_theme_chain().iter().map(ThemeLayer::static_dir);
+    [site].into_iter().chain(themes).collect()
+}
+
+/// The directories of the custom pages, from the theme to the farthest parent theme
+pub fn custom_dirs() -> Vec<PathBuf> {
+    get_theme_chain().iter().map(ThemeLayer::custom_dir).collect()
+}
+
+/// The directories with the same name in the site and the themes, like the shortcodes,
+/// from the site to the farthest parent theme
+pub fn layered_dirs<P: AsRef<Path>>(dir: P) -> Vec<PathBuf> {
+    let dir = dir.as_ref();
+    let themes = get_theme_chain().iter().map(|layer| layer.path.join(dir));
+    [dir.to_path_buf()].into_iter().chain(themes).collect()
+}
+
+/// Find a template in the [`template_dirs`], returning its path in the workspace
+pub fn find_template<P: AsRef<Path>>(name: P) -> Option<PathBuf> {
+    template_dirs()
+        .into_iter()
+        .map(|dir| file::workspace(dir).join(name.as_ref()))
+        .find(|path| path.is_file())
 }
@@ -0,0 +1,500 @@
+//! Validation of the config files against their schemas before anything reads them.
+//! Every problem is reported with its file, key path and line, so that a typo is neither
+//! silently ignored nor falls back to the defaults. Unknown keys are only warnings,
+//! while the other problems are errors failing the command.
+
+use crate::config::{
+    PLUGIN_CONFIG_FILE, ParamSchema, SITE_CONFIG_FILE, THEME_CONFIG_FILE, ThemeConfig,
+    env_config_file, get_env, get_theme_path, parent_theme_path,
+};
+use crate::file;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use toml::Spanned;
+use toml::de::{DeTable, DeValue};
+
+/// The expected shape of a config value
+#[derive(Debug, Clone, Copy)]
+pub enum Schema {
+    /// any value, like the custom `params`
+    Any,
+    String,
+    /// an integer from the minimum to the maximum, like `0..=u8::MAX` for a `u8` value
+    Integer(i64, i64),
+    Bool,
+    /// one of the strings, like the feed formats
+    OneOf(&'static [&'static str]),
+    /// a list of the values of the schema
+    List(&'static Schema),
+    /// a table with the known fields, whose other keys are warned
+    Table(&'static [Field]),
+    /// a table with the known fields, whose other keys are allowed unless they look like
+    /// a typo of the known ones, like the site config whose keys are all in the templates
+    Open(&'static [Field]),
+    /// a table with any keys, whose values are all of the schema
+    Map(&'static Schema),
+}
+
+/// A known key of a table
+#[derive(Debug, Clone, Copy)]
+pub struct Field {
+    pub key: &'static str,
+    pub schema: Schema,
+    pub required: bool,
+}
+
+impl Field {
+    pub const fn optional(key: &'static str, schema: Schema) -> Self {
+        Self {
+            key,
+            schema,
+            required: false,
+        }
+    }
+
+    pub const fn required(key: &'static str, schema: Schema) -> Self {
+        Self {
+            key,
+            schema,
+            required: true,
+        }
+    }
+}
+
+impl Schema {
+    /// Describe the expected value in the error messages
+    fn describe(&self) -> String {
+        match self {
+            Schema::Any => "any value".into(),
+            Schema::String => "a string".into(),
+            Schema::Integer(..) => "an integer".into(),
+            Schema::Bool => "a boolean".into(),
+            Schema::OneOf(values) => {
+                let values: Vec<_> = values.iter().map(|value| format!("\"{value}\"")).collect();
+                format!("one of {}", values.join(", "))
+            }
+            Schema::List(_) => "a list".into(),
+            Schema::Table(_) | Schema::Open(_) | Schema::Map(_) => "a table".into(),
+        }
+    }
+
+    /// Check if a value has the type of the schema, without its inner values
+    fn matches(&self, value: &DeValue) -> bool {
+        match (self, value) {
+            (Schema::Any, _) => true,
+            (Schema::String, DeValue::String(_)) => true,
+            (Schema::Integer(..), DeValue::Integer(_)) => true,
+            (Schema::Bool, DeValue::Boolean(_)) => true,
+            (Schema::OneOf(values), DeValue::String(s)) => values.contains(&s.as_ref()),
+            (Schema::List(_), DeValue::Array(_)) => true,
+            (Schema::Table(_) | Schema::Open(_) | Schema::Map(_), DeValue::Table(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The name of the type of a value in the error messages
+fn type_name(value: &DeValue) -> &'static str {
+    match value {
+        DeValue::String(_) => "a string",
+        DeValue::Integer(_) => "an integer",
+        DeValue::Float(_) => "a float",
+        DeValue::Boolean(_) => "a boolean",
+        DeValue::Datetime(_) => "a datetime",
+        DeValue::Array(_) => "a list",
+        DeValue::Table(_) => "a table",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem in a config file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProblem {
+    pub severity: Severity,
+    pub file: PathBuf,
+    /// the line of the problem, starting from 1
+    pub line: usize,
+    /// the key path of the problem, like `feed.limit` or `redirects[0].from`, empty for the file
+    pub key: String,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}:{}: ", severity, self.file.display(), self.line)?;
+        if !self.key.is_empty() {
+            write!(f, "`{}`: ", self.key)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The validation state of a config file
+struct Validator<'a> {
+    file: &'a Path,
+    content: &'a str,
+    problems: Vec<ConfigProblem>,
+}
+
+impl Validator<'_> {
+    /// Get the line of a byte offset in the file
+    fn line_of(&self, offset: usize) -> usize {
+        let offset = offset.min(self.content.len());
+        self.content.as_bytes()[..offset].iter().filter(|&&b| b == b'\n').count() + 1
+    }
+
+    fn report(&mut self, severity: Severity, offset: usize, key: &str, message: String) {
+        self.problems.push(ConfigProblem {
+            severity,
+            file: self.file.into(),
+            line: self.line_of(offset),
+            key: key.into(),
+            message,
+        });
+    }
+
+    fn check(&mut self, value: &Spanned<DeValue>, schema: &Schema, path: &str) {
+        let offset = value.span().start;
+        let value = value.get_ref();
+        if !schema.matches(value) {
+            let message = match (schema, value) {
+                (Schema::OneOf(_), DeValue::String(s)) => {
+                    format!("expected {}, found \"{}\"", schema.describe(), s)
+                }
+                _ => format!("expected {}, found {}", schema.describe(), type_name(value)),
+            };
+            return self.report(Severity::Error, offset, path, message);
+        }
+
+        match (schema, value) {
+            (Schema::Integer(min, max), DeValue::Integer(integer)) => {
+                let parsed = i128::from_str_radix(integer.as_str(), integer.radix()).ok();
+                if !parsed.is_some_and(|n| (*min as i128..=*max as i128).contains(&n)) {
+                    let message = format!(
+                        "expected an integer from {min} to {max}, found {}",
+                        integer.as_str()
+                    );
+                    self.report(Severity::Error, offset, path, message);
+                }
+            }
+            (Schema::List(item), DeValue::Array(items)) => {
+                for (i, value) in items.iter().enumerate() {
+                    self.check(value, item, &format!("{path}[{i}]"));
+                }
+            }
+            (Schema::Map(item), DeValue::Table(table)) => {
+                for (key, value) in table.iter() {
+                    self.check(value, item, &join_key(path, key.get_ref()));
+                }
+            }
+            (Schema::Table(fields), DeValue::Table(table)) => {
+                self.check_table(offset, table, fields, path, true)
+            }
+            (Schema::Open(fields), DeValue::Table(table)) => {
+                self.check_table(offset, table, fields, path, false)
+            }
+            _ => {}
+        }
+    }
+
+    /// Check the fields of a table, warning all the unknown keys if it is `strict`,
+    /// or only the ones similar to a known key otherwise
+    fn check_table(
+        &mut self,
+        offset: usize,
+        table: &DeTable,
+        fields: &[Field],
+        path: &str,
+        strict: bool,
+    ) {
+        for (key, value) in table.iter() {
+            let key_path = join_key(path, key.get_ref());
+            if let Some(field) = fields.iter().find(|field| field.key == key.get_ref().as_ref()) {
+                self.check(value, &field.schema, &key_path);
+                continue;
+            }
+            let message = match suggest(key.get_ref(), fields) {
+                Some(similar) => format!("unknown key, did you mean `{similar}`?"),
+                None if strict => "unknown key".into(),
+                None => continue,
+            };
+            self.report(Severity::Warning, key.span().start, &key_path, message);
+        }
+        for field in fields.iter().filter(|field| field.required) {
+            if !table.keys().any(|key| key.get_ref().as_ref() == field.key) {
+                let message = format!("missing required key `{}`", field.key);
+                self.report(Severity::Error, offset, path, message);
+            }
+        }
+    }
+}
+
+fn join_key(path: &str, key: &str) -> String {
+    match path.is_empty() {
+        true => key.into(),
+        false => format!("{path}.{key}"),
+    }
+}
+
+/// Suggest the known key most similar to an unknown one, like `titel` -> `title`
+fn suggest(key: &str, fields: &[Field]) -> Option<&'static str> {
+    fields
+        .iter()
+        .map(|field| (field.key, edit_distance(key, field.key)))
+        .filter(|(known, distance)| *distance <= known.len().min(key.len()) / 3 + 1)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(known, _)| known)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<_> = b.chars().collect();
+    let mut row: Vec<_> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut last = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = (last + usize::from(ca != *cb)).min(row[j] + 1).min(current + 1);
+            last = current;
+        }
+    }
+    row[b.len()]
+}
+
+/// Validate the content of a config file, returning all the problems found
+pub fn validate<P: AsRef<Path>>(file: P, content: &str, schema: &Schema) -> Vec<ConfigProblem> {
+    let mut validator = Validator {
+        file: file.as_ref(),
+        content,
+        problems: vec![],
+    };
+
+    let (table, errors) = DeTable::parse_recoverable(content);
+    for error in errors.iter() {
+        let offset = error.span().map_or(0, |span| span.start);
+        let message = error.message().trim().to_string();
+        validator.report(Severity::Error, offset, "", message);
+    }
+    // the document is only validated if it is parsed, to avoid the errors caused by the syntax ones
+    if errors.is_empty() {
+        match schema {
+            Schema::Table(fields) => validator.check_table(0, table.get_ref(), fields, "", true),
+            Schema::Open(fields) => validator.check_table(0, table.get_ref(), fields, "", false),
+            _ => {
+                let value = Spanned::new(table.span(), DeValue::Table(table.into_inner()));
+                validator.check(&value, schema, "");
+            }
+        }
+    }
+    validator.problems.sort_by_key(|problem| problem.line);
+    validator.problems
+}
+
+/// Validate a config file in the workspace if it exists
+fn validate_file(path: PathBuf, schema: &Schema) -> Vec<ConfigProblem> {
+    match file::read_file_string(&path) {
+        Ok(content) => {
+            let name = path.strip_prefix(file::root()).unwrap_or(&path).to_path_buf();
+            validate(name, &content, schema)
+        }
+        Err(_) => vec![],
+    }
+}
+
+/// Validate the site config with the config of the environment.
+/// They must be valid before the theme is located by the site config.
+pub fn validate_site_configs() -> Vec<ConfigProblem> {
+    let mut problems = validate_file(file::workspace(SITE_CONFIG_FILE), &SITE_SCHEMA);
+    problems.extend(validate_file(
+        file::workspace(env_config_file(get_env())),
+        &SITE_SCHEMA,
+    ));
+    problems
+}
+
+/// Validate the configs of the theme and its parent themes, and the plugin config
+pub fn validate_theme_configs() -> Vec<ConfigProblem> {
+    let mut problems = vec![];
+    let mut visited = vec![];
+    let mut next = Some(get_theme_path().clone());
+    // a circular `extends` is left to be reported when the themes are loaded
+    while let Some(path) = next.take_if(|path| !visited.contains(path)) {
+        let theme_config = file::workspace(&path).join(THEME_CONFIG_FILE);
+        let theme_problems = validate_file(theme_config.clone(), &THEME_SCHEMA);
+        if !theme_problems.iter().any(ConfigProblem::is_error) {
+            next = file::read_file_string(&theme_config)
+                .ok()
+                .and_then(|content| toml::from_str::<ThemeConfig>(&content).ok())
+                .and_then(|config| parent_theme_path(&config));
+        }
+        problems.extend(theme_problems);
+        visited.push(path);
+    }
+    problems.extend(validate_file(
+        file::workspace(PLUGIN_CONFIG_FILE),
+        &PLUGIN_SCHEMA,
+    ));
+    problems
+}
+
+const STRINGS: Schema = Schema::List(&Schema::String);
+const INTEGER: Schema = Schema::Integer(i64::MIN, i64::MAX);
+const USIZE: Schema = Schema::Integer(0, i64::MAX);
+
+/// The schema of `allay.toml`, whose custom top-level keys are available in the templates
+pub const SITE_SCHEMA: Schema = Schema::Open(&[
+    Field::optional("base_url", Schema::String),
+    Field::optional("title", Schema::String),
+    Field::optional("theme", Schema::String),
+    Field::optional("description", Schema::String),
+    Field::optional("author", Schema::String),
+    Field::optional("params", Schema::Map(&Schema::Any)),
+    Field::optional("default_language", Schema::String),
+    Field::optional(
+        "languages",
+        Schema::Map(&Schema::Table(&[Field::optional("name", Schema::String)])),
+    ),
+    Field::optional(
+        "plugins",
+        Schema::Map(&Schema::Table(&[
+            Field::optional("path", Schema::String),
+            Field::optional("git", Schema::String),
+            Field::optional("tag", Schema::String),
+            Field::optional("file", Schema::String),
+            Field::optional("registry", Schema::String),
+            Field::optional("version", Schema::String),
+            Field::optional("sha256", Schema::String),
+        ])),
+    ),
+    Field::optional(
+        "related",
+        Schema::Table(&[
+            Field::optional("limit", USIZE),
+            Field::optional("weights", Schema::Map(&INTEGER)),
+        ]),
+    ),
+    Field::optional(
+        "feed",
+        Schema::Table(&[
+            Field::optional("enabled", Schema::Bool),
+            Field::optional("formats", Schema::List(&Schema::OneOf(&["rss", "atom"]))),
+            Field::optional("limit", USIZE),
+            Field::optional("full_content", Schema::Bool),
+            Field::optional("sections", Schema::Bool),
+            Field::optional("taxonomies", STRINGS),
+        ]),
+    ),
+    Field::optional("permalinks", Schema::Map(&Schema::String)),
+    Field::optional("outputs", Schema::Map(&STRINGS)),
+    Field::optional(
+        "assets",
+        Schema::Table(&[
+            Field::optional("fingerprint", Schema::Bool),
+            Field::optional("extensions", STRINGS),
+        ]),
+    ),
+    Field::optional(
+        "images",
+        Schema::Table(&[
+            Field::optional("widths", Schema::List(&Schema::Integer(0, u32::MAX as i64))),
+            Field::optional("webp", Schema::Bool),
+            Field::optional("quality", Schema::Integer(0, u8::MAX as i64)),
+        ]),
+    ),
+    Field::optional(
+        "minify",
+        Schema::Table(&[
+            Field::optional("enabled", Schema::Bool),
+            Field::optional("html", Schema::Bool),
+            Field::optional("css", Schema::Bool),
+            Field::optional("js", Schema::Bool),
+        ]),
+    ),
+    Field::optional(
+        "build",
+        Schema::Table(&[
+            Field::optional("prune", Schema::Bool),
+            Field::optional("keep", STRINGS),
+            Field::optional("content_dir", Schema::String),
+            Field::optional("publish_dir", Schema::String),
+            Field::optional("statics_dir", Schema::String),
+            Field::optional("plugin_dir", Schema::String),
+            Field::optional("shortcode_dir", Schema::String),
+            Field::optional("theme_dir", Schema::String),
+            Field::optional("log_dir", Schema::String),
+            Field::optional("i18n_dir", Schema::String),
+            Field::optional("cache_dir", Schema::String),
+            Field::optional("archetype_dir", Schema::String),
+            Field::optional("template_dir", Schema::String),
+        ]),
+    ),
+    Field::optional(
+        "redirects",
+        Schema::List(&Schema::Table(&[
+            Field::required("from", Schema::String),
+            Field::required("to", Schema::String),
+            Field::optional("status", Schema::Integer(0, u16::MAX as i64)),
+        ])),
+    ),
+]);
+
+/// The schema of `theme.toml`
+pub const THEME_SCHEMA: Schema = Schema::Table(&[
+    Field::required(
+        "theme",
+        Schema::Table(&[
+            Field::required("name", Schema::String),
+            Field::required("version", Schema::String),
+            Field::required("author", Schema::String),
+            Field::optional("description", Schema::String),
+            Field::optional("license", Schema::String),
+            Field::optional("extends", Schema::String),
+        ]),
+    ),
+    Field::optional(
+        "config",
+        Schema::Table(&[
+            Field::optional("custom", Schema::String),
+            Field::optional("static", Schema::String),
+            Field::optional(
+                "templates",
+                Schema::Table(&[
+                    Field::optional("dir", Schema::String),
+                    Field::optional("index", Schema::String),
+                    Field::optional("content", Schema::String),
+                    Field::optional("not_found", Schema::String),
+                ]),
+            ),
+        ]),
+    ),
+    Field::optional(
+        "dependencies",
+        Schema::Table(&[Field::optional("plugins", Schema::Map(&Schema::String))]),
+    ),
+    Field::optional("params", Schema::Map(&Schema::Any)),
+    Field::optional(
+        "params_schema",
+        Schema::Map(&Schema::Table(&[
+            Field::optional("type", Schema::OneOf(ParamSchema::TYPES)),
+            Field::optional("description", Schema::String),
+        ])),
+    ),
+]);
+
+/// The schema of `plugin.toml`, a table of the config of each plugin
+pub const PLUGIN_SCHEMA: Schema = Schema::Map(&Schema::Map(&Schema::Any));
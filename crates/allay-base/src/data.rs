@@ -1,3 +1,4 @@
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::HashMap;
@@ -114,6 +115,19 @@ impl AllayData {
         }
     }
 
+    /// Convert a table already parsed from TOML, like a table of a config struct
+    pub fn from_toml_table(table: toml::Table) -> DataResult<AllayObject> {
+        let data: RawAllayData = toml::Value::Table(table).try_into()?;
+        match data {
+            RawAllayData::Object(obj) => {
+                Ok(obj.into_iter().map(|(k, v)| (k, Arc::new(AllayData::from(v)))).collect())
+            }
+            _ => Err(AllayDataError::TypeConversion(
+                "TOML value is not a table".to_string(),
+            )),
+        }
+    }
+
     pub fn from_yaml(content: &str) -> DataResult<AllayObject> {
         let data: RawAllayData = serde_yaml::from_str(content)?;
         match data {
@@ -201,6 +215,32 @@ impl AllayData {
             Err(AllayDataError::TypeConversion("not an object".to_string()))
         }
     }
+
+    /// Convert the data into a typed structure by serde
+    pub fn parse_as<T: DeserializeOwned>(&self) -> DataResult<T> {
+        let value = serde_json::to_value(self)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Merge an object into another, the values of `overlay` taking precedence.
+    /// The nested objects are merged recursively instead of replaced.
+    pub fn merge_object(base: &mut AllayObject, overlay: AllayObject) {
+        for (key, value) in overlay {
+            match (
+                base.get(&key).and_then(|old| old.as_obj().ok()),
+                value.as_obj(),
+            ) {
+                (Some(old), Ok(new)) => {
+                    let mut merged = old.as_ref().clone();
+                    Self::merge_object(&mut merged, new.as_ref().clone());
+                    base.insert(key, Arc::new(merged.into()));
+                }
+                _ => {
+                    base.insert(key, value);
+                }
+            }
+        }
+    }
 }
 
 impl PartialOrd for AllayData {
@@ -1,4 +1,5 @@
 //! File operations and utilities
+use crate::manifest::{BuildManifest, CleanRefused};
 use serde::Serialize;
 use std::env;
 use std::fs;
@@ -19,6 +20,9 @@ pub enum FileError {
 
     #[error("Time error: {0}")]
     TimeError(#[from] SystemTimeError),
+
+    #[error("Refused to clean the publish directory: {0}")]
+    CleanRefused(#[from] CleanRefused),
 }
 
 pub type FileResult<T> = Result<T, FileError>;
@@ -214,9 +218,23 @@ pub fn write_file<P: AsRef<Path>>(file_path: P, content: impl AsRef<[u8]>) -> Fi
         create_dir_recursively(parent)?;
     }
     fs::write(file_path, content)?;
+    record_output(file_path, true);
     Ok(())
 }
 
+/// Record the outputs written or removed in the build manifest.
+/// Nothing is recorded before the root is set, like when creating a new site.
+fn record_output(path: &Path, written: bool) {
+    if ROOT.get().is_none() {
+        return;
+    }
+    let manifest = BuildManifest::get_instance();
+    match written {
+        true => manifest.record(path),
+        false => manifest.forget(path),
+    }
+}
+
 /// Remove a file or directory if it exists
 pub fn remove<P: AsRef<Path>>(path: P) -> FileResult<()> {
     let path = path.as_ref();
@@ -236,6 +254,7 @@ pub fn remove_file<P: AsRef<Path>>(file_path: P) -> FileResult<()> {
     let file_path = file_path.as_ref();
     if file_exists(file_path) {
         fs::remove_file(file_path)?;
+        record_output(file_path, false);
     }
     Ok(())
 }
@@ -254,6 +273,7 @@ pub fn remove_dir_recursively<P: AsRef<Path>>(dir_path: P) -> FileResult<()> {
     let dir_path = dir_path.as_ref();
     if dir_exists(dir_path) {
         fs::remove_dir_all(dir_path)?;
+        record_output(dir_path, false);
     }
     Ok(())
 }
@@ -272,7 +292,8 @@ pub fn copy<P: AsRef<Path>>(src: P, dest: P) -> FileResult<()> {
         if let Some(parent) = dest.as_ref().parent() {
             create_dir_recursively(parent)?;
         }
-        fs::copy(src, dest)?;
+        fs::copy(src, dest.as_ref())?;
+        record_output(dest.as_ref(), true);
         Ok(())
     } else {
         Err(FileError::FileNotFound(src.as_ref().into()))
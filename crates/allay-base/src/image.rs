@@ -0,0 +1,282 @@
+//! Build-time processing of images, which publishes resized and WebP variants of them
+//! next to the originals, like `photos/a.jpg` -> `photos/a.480w.jpg` and `photos/a.480w.webp`.
+//! The variants are cached in the cache directory, so unchanged images are never reprocessed.
+
+use crate::asset::content_hash;
+use crate::config::{get_allay_config, get_image_config, static_dirs};
+use crate::file::{self, FileError};
+use crate::{read, write};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use thiserror::Error;
+
+/// The extensions of the images to process
+const IMAGE_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
+const WEBP: &str = "webp";
+
+#[derive(Error, Debug)]
+pub enum ImageError {
+    #[error("Image not found: {0}")]
+    NotFound(PathBuf),
+
+    #[error("File error: {0}")]
+    File(#[from] FileError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to process image {0}: {1}")]
+    Process(PathBuf, image::ImageError),
+}
+
+pub type ImageResult<T> = Result<T, ImageError>;
+
+/// A source image with its size, cached by the last modified time
+#[derive(Debug, Clone)]
+struct SourceImage {
+    source: PathBuf,
+    lastmod: u64,
+    hash: String,
+    width: u32,
+    height: u32,
+}
+
+/// A processed variant of an image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageVariant {
+    /// the path of the variant relative to the publish directory
+    pub path: PathBuf,
+    pub width: u32,
+    pub webp: bool,
+}
+
+/// The variants of an image for the `srcset` attributes
+#[derive(Debug, Clone, Default)]
+pub struct ImageSrcset {
+    /// the width of the original image
+    pub width: u32,
+    /// the variants in the original format, ascending by width
+    pub original: Vec<ImageVariant>,
+    /// the WebP variants, ascending by width
+    pub webp: Vec<ImageVariant>,
+}
+
+/// The image processor, keyed by the paths of images relative to the publish directory
+#[derive(Debug, Default)]
+pub struct ImageProcessor {
+    sources: RwLock<HashMap<PathBuf, SourceImage>>,
+    /// the published variants of every image, as (width, webp) pairs
+    published: RwLock<HashMap<PathBuf, BTreeSet<(u32, bool)>>>,
+}
+
+impl ImageProcessor {
+    pub fn get_instance() -> &'static ImageProcessor {
+        static INSTANCE: OnceLock<ImageProcessor> = OnceLock::new();
+        INSTANCE.get_or_init(ImageProcessor::default)
+    }
+
+    /// Check if a file is an image that can be processed
+    pub fn is_image<P: AsRef<Path>>(path: P) -> bool {
+        path.as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+    }
+
+    /// Find the source file of an image in the static directories of the site and the themes,
+    /// or in the content directory for the images bundled with pages
+    fn source_of(path: &Path) -> Option<PathBuf> {
+        static_dirs()
+            .into_iter()
+            .chain([PathBuf::from(&get_allay_config().content_dir)])
+            .map(|dir| file::workspace(dir).join(path))
+            .find(|source| source.is_file())
+    }
+
+    fn inspect(&self, path: &Path) -> ImageResult<SourceImage> {
+        let source = Self::source_of(path).ok_or_else(|| ImageError::NotFound(path.into()))?;
+        let lastmod = file::last_modified(&source)?;
+        if let Some(image) = read!(self.sources).get(path)
+            && image.source == source
+            && image.lastmod == lastmod
+        {
+            return Ok(image.clone());
+        }
+
+        let (width, height) =
+            image::image_dimensions(&source).map_err(|e| ImageError::Process(path.into(), e))?;
+        let image = SourceImage {
+            hash: content_hash(&std::fs::read(&source)?),
+            source,
+            lastmod,
+            width,
+            height,
+        };
+        write!(self.sources).insert(path.into(), image.clone());
+        Ok(image)
+    }
+
+    /// Get the variant of an image at a width (the original width if `None` or larger),
+    /// and publish it if needed. The original image itself is returned if nothing is changed.
+    pub fn variant<P: AsRef<Path>>(
+        &self,
+        path: P,
+        width: Option<u32>,
+        webp: bool,
+    ) -> ImageResult<ImageVariant> {
+        let path = path.as_ref();
+        let image = self.inspect(path)?;
+        let width = width.unwrap_or(image.width).clamp(1, image.width);
+        let webp = webp && !is_webp(path);
+
+        if width == image.width && !webp {
+            return Ok(ImageVariant {
+                path: path.into(),
+                width,
+                webp,
+            });
+        }
+
+        let variant = ImageVariant {
+            path: variant_path(path, width, webp),
+            width,
+            webp,
+        };
+        self.publish(path, &image, &variant)?;
+        write!(self.published).entry(path.into()).or_default().insert((width, webp));
+        Ok(variant)
+    }
+
+    /// Get all the variants of an image by the configured widths for the `srcset` attributes
+    pub fn srcset<P: AsRef<Path>>(&self, path: P) -> ImageResult<ImageSrcset> {
+        let path = path.as_ref();
+        let config = get_image_config();
+        let image = self.inspect(path)?;
+
+        let mut widths: BTreeSet<_> =
+            config.widths.iter().copied().filter(|w| *w > 0 && *w < image.width).collect();
+        widths.insert(image.width);
+
+        let mut srcset = ImageSrcset {
+            width: image.width,
+            ..Default::default()
+        };
+        for width in widths {
+            srcset.original.push(self.variant(path, Some(width), false)?);
+            if config.webp && !is_webp(path) {
+                srcset.webp.push(self.variant(path, Some(width), true)?);
+            }
+        }
+        Ok(srcset)
+    }
+
+    /// Publish the variants of an image again after its source changes
+    pub fn refresh<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        let path = path.as_ref();
+        let Some(variants) = read!(self.published).get(path).cloned() else {
+            return Ok(());
+        };
+        for (width, webp) in variants {
+            self.variant(path, Some(width), webp)?;
+        }
+        Ok(())
+    }
+
+    /// Write a variant into the publish directory from the cache, processing it if not cached
+    fn publish(&self, path: &Path, image: &SourceImage, variant: &ImageVariant) -> ImageResult<()> {
+        let ext = variant.path.extension().unwrap_or_default().to_string_lossy();
+        // the variants are encoded again when the quality changes
+        let quality = get_image_config().quality;
+        let cached = file::workspace(&get_allay_config().cache_dir).join("images").join(format!(
+            "{}.{}w.q{}.{}",
+            image.hash, variant.width, quality, ext
+        ));
+        let dest = file::workspace(&get_allay_config().publish_dir).join(&variant.path);
+
+        if !cached.is_file() {
+            file::create_dir_recursively(cached.parent().unwrap_or(Path::new(".")))?;
+            if let Err(e) = resize(image, variant, &cached) {
+                // never leave a broken variant in the cache
+                file::remove(&cached)?;
+                return Err(ImageError::Process(path.into(), e));
+            }
+        }
+        file::copy(&cached, &dest)?;
+        Ok(())
+    }
+}
+
+fn is_webp(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(WEBP))
+}
+
+/// The path of a variant, like `photos/a.jpg` -> `photos/a.480w.jpg` or `photos/a.480w.webp`
+pub fn variant_path<P: AsRef<Path>>(path: P, width: u32, webp: bool) -> PathBuf {
+    let path = path.as_ref();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = match webp {
+        true => WEBP.into(),
+        false => path.extension().unwrap_or_default().to_string_lossy(),
+    };
+    path.with_file_name(format!("{}.{}w.{}", stem, width, ext))
+}
+
+fn resize(image: &SourceImage, variant: &ImageVariant, dest: &Path) -> image::ImageResult<()> {
+    let reader = ImageReader::open(&image.source)?.with_guessed_format()?;
+    let format = reader.format();
+    let mut img = reader.decode()?;
+    if variant.width != image.width {
+        let height = (image.height as u64 * variant.width as u64 / image.width as u64).max(1);
+        img = img.resize_exact(variant.width, height as u32, FilterType::Lanczos3);
+    }
+
+    let mut writer = BufWriter::new(File::create(dest)?);
+    let quality = get_image_config().quality.clamp(1, 100);
+    match format {
+        _ if variant.webp => Ok(writer.write_all(&encode_webp(&img, quality)?)?),
+        Some(ImageFormat::Jpeg) => img
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut writer, quality)),
+        Some(format) => img.write_to(&mut writer, format),
+        None => img.write_to(&mut writer, ImageFormat::Png),
+    }
+}
+
+/// Encode an image into lossy WebP at a quality from 1 to 100, keeping its transparency if any
+#[cfg(not(target_family = "wasm"))]
+fn encode_webp(img: &DynamicImage, quality: u8) -> image::ImageResult<Vec<u8>> {
+    use image::error::{EncodingError, ImageFormatHint};
+
+    let encoded = match img.color().has_alpha() {
+        true => {
+            let rgba = img.to_rgba8();
+            webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height())
+                .encode_simple(false, quality as f32)
+        }
+        false => {
+            let rgb = img.to_rgb8();
+            webp::Encoder::from_rgb(&rgb, rgb.width(), rgb.height())
+                .encode_simple(false, quality as f32)
+        }
+    };
+    encoded.map(|webp| webp.to_vec()).map_err(|e| {
+        image::ImageError::Encoding(EncodingError::new(
+            ImageFormatHint::Exact(ImageFormat::WebP),
+            format!("{:?}", e),
+        ))
+    })
+}
+
+/// Encode an image into lossless WebP, as libwebp is not built for WebAssembly
+#[cfg(target_family = "wasm")]
+fn encode_webp(img: &DynamicImage, _quality: u8) -> image::ImageResult<Vec<u8>> {
+    let mut encoded = std::io::Cursor::new(vec![]);
+    img.write_to(&mut encoded, ImageFormat::WebP)?;
+    Ok(encoded.into_inner())
+}
@@ -1,9 +1,15 @@
+pub mod asset;
 pub mod config;
 pub mod data;
 pub mod dependency;
 pub mod file;
+pub mod image;
 pub mod lock;
 pub mod log;
+pub mod manifest;
+pub mod plugin_health;
+pub mod plugin_lock;
+pub mod report;
 pub mod sitemap;
 pub mod template;
 pub mod url;
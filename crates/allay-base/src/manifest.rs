@@ -0,0 +1,244 @@
+//! The build manifest, recording every file written into the publish directory by a build.
+//! It is kept in the cache directory, so that the next build can remove the outputs
+//! no longer produced, like the pages whose sources are deleted between the builds.
+
+use crate::config::{get_allay_config, get_build_config};
+use crate::file::{self, FileResult};
+use crate::lock;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use thiserror::Error;
+use tracing::warn;
+use walkdir::WalkDir;
+
+/// The build manifest in the cache directory
+pub const BUILD_MANIFEST_FILE: &str = "build.json";
+
+/// The files written into the publish directory, relative to it
+#[derive(Debug, Default)]
+pub struct BuildManifest {
+    written: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl BuildManifest {
+    pub fn get_instance() -> &'static BuildManifest {
+        static INSTANCE: OnceLock<BuildManifest> = OnceLock::new();
+        INSTANCE.get_or_init(BuildManifest::default)
+    }
+
+    fn publish_dir() -> PathBuf {
+        file::workspace(&get_allay_config().publish_dir)
+    }
+
+    fn manifest_path() -> PathBuf {
+        file::workspace(&get_allay_config().cache_dir).join(BUILD_MANIFEST_FILE)
+    }
+
+    /// Record a file written, if it is in the publish directory
+    pub fn record<P: AsRef<Path>>(&self, path: P) {
+        if let Ok(path) = path.as_ref().strip_prefix(Self::publish_dir()) {
+            lock!(self.written).insert(path.into());
+        }
+    }
+
+    /// Forget a file or a directory removed from the publish directory
+    pub fn forget<P: AsRef<Path>>(&self, path: P) {
+        if let Ok(path) = path.as_ref().strip_prefix(Self::publish_dir()) {
+            lock!(self.written).retain(|written| !written.starts_with(path));
+        }
+    }
+
+    /// Get the files written so far
+    pub fn written(&self) -> BTreeSet<PathBuf> {
+        lock!(self.written).clone()
+    }
+
+    /// Load the files written by the last build, empty if there was none
+    pub fn load() -> BTreeSet<PathBuf> {
+        file::read_file_string(Self::manifest_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save the files written, together with the ones of the last build not removed,
+    /// so that they can still be pruned later
+    pub fn save(&self, last: &BTreeSet<PathBuf>) -> FileResult<()> {
+        let publish_dir = Self::publish_dir();
+        let mut written = self.written();
+        written.extend(last.iter().filter(|path| publish_dir.join(path).is_file()).cloned());
+        let content = serde_json::to_string_pretty(&written).unwrap_or_default();
+        file::write_file(Self::manifest_path(), content)
+    }
+
+    /// Remove the outputs of the last build no longer written by this one, except the kept ones,
+    /// returning the removed files
+    pub fn prune(&self, last: &BTreeSet<PathBuf>) -> FileResult<Vec<PathBuf>> {
+        let keep = keep_patterns();
+        let written = self.written();
+        let publish_dir = Self::publish_dir();
+
+        let mut removed = vec![];
+        for path in last.difference(&written) {
+            if is_kept(&keep, path) || !publish_dir.join(path).is_file() {
+                continue;
+            }
+            file::remove_file(publish_dir.join(path))?;
+            remove_empty_parents(&publish_dir, path)?;
+            removed.push(path.clone());
+        }
+        Ok(removed)
+    }
+}
+
+/// Compile the `keep` patterns of the `[build]` config
+fn keep_patterns() -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in get_build_config().keep.iter() {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => warn!("Invalid keep pattern {:?}: {}", pattern, e),
+        }
+    }
+    builder.build().unwrap_or_else(|_| GlobSet::empty())
+}
+
+/// Check if a path in the publish directory matches a keep pattern, or is in a kept directory
+pub fn is_kept<P: AsRef<Path>>(keep: &GlobSet, path: P) -> bool {
+    path.as_ref()
+        .ancestors()
+        .any(|path| !path.as_os_str().is_empty() && keep.is_match(path))
+}
+
+/// Remove the directories left empty by removing a file, up to the publish directory
+fn remove_empty_parents(publish_dir: &Path, path: &Path) -> FileResult<()> {
+    for parent in path.ancestors().skip(1) {
+        let dir = publish_dir.join(parent);
+        if parent.as_os_str().is_empty() || !file::dirty_dir(&dir).is_ok_and(|dirty| !dirty) {
+            break;
+        }
+        file::remove_dir(dir)?;
+    }
+    Ok(())
+}
+
+/// Why a directory is refused to be cleaned, as it holds more than the outputs of the builds
+#[derive(Error, Debug, PartialEq)]
+pub enum CleanRefused {
+    #[error("{0:?} is the site root or contains it")]
+    ContainsRoot(PathBuf),
+
+    #[error("{0:?} contains the {1} directory {2:?}")]
+    ContainsDir(PathBuf, &'static str, PathBuf),
+
+    #[error("{0:?} is outside of the site root, pass --clean-outside to clean it anyway")]
+    Outside(PathBuf),
+}
+
+/// Resolve a path to an absolute one without `.` or `..`.
+/// The links are followed in the part of the path which exists.
+fn resolve(path: &Path) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    for component in file::absolute(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+    for ancestor in resolved.ancestors() {
+        if let Ok(real) = ancestor.canonicalize() {
+            return match resolved.strip_prefix(ancestor) {
+                Ok(rest) if !rest.as_os_str().is_empty() => real.join(rest),
+                _ => real,
+            };
+        }
+    }
+    resolved
+}
+
+/// Check that a directory can be cleaned: it must not be the site root or contain it,
+/// nor equal or contain any of the directories of the site, like the contents or the themes.
+/// A directory outside of the root is only cleaned if allowed.
+pub fn check_clean_dir(
+    root: &Path,
+    dir: &Path,
+    site_dirs: &[(&'static str, PathBuf)],
+    allow_outside: bool,
+) -> Result<(), CleanRefused> {
+    let root = resolve(root);
+    let dir = resolve(dir);
+    if root.starts_with(&dir) {
+        return Err(CleanRefused::ContainsRoot(dir));
+    }
+    for (kind, site_dir) in site_dirs {
+        let site_dir = resolve(site_dir);
+        if site_dir.starts_with(&dir) {
+            return Err(CleanRefused::ContainsDir(dir, kind, site_dir));
+        }
+    }
+    if !allow_outside && !dir.starts_with(&root) {
+        return Err(CleanRefused::Outside(dir));
+    }
+    Ok(())
+}
+
+/// The directories of the site never cleaned
+fn site_dirs() -> Vec<(&'static str, PathBuf)> {
+    let config = get_allay_config();
+    [
+        ("content", &config.content_dir),
+        ("theme", &config.theme_dir),
+        ("static", &config.statics_dir),
+        ("plugin", &config.plugin_dir),
+        ("cache", &config.cache_dir),
+        ("shortcode", &config.shortcode_dir),
+        ("template", &config.template_dir),
+        ("archetype", &config.archetype_dir),
+        ("i18n", &config.i18n_dir),
+    ]
+    .into_iter()
+    .map(|(kind, dir)| (kind, file::workspace(dir)))
+    .collect()
+}
+
+/// Remove everything in the publish directory except the kept files, for a clean build.
+/// The directory is checked first by [`check_clean_dir`].
+pub fn clean_publish_dir(allow_outside: bool) -> FileResult<()> {
+    let keep = keep_patterns();
+    let publish_dir = BuildManifest::publish_dir();
+    if !publish_dir.is_dir() {
+        return Ok(());
+    }
+    check_clean_dir(&file::root(), &publish_dir, &site_dirs(), allow_outside)?;
+
+    // remove the files first, then the directories left empty from the deepest
+    let entries: Vec<_> = WalkDir::new(&publish_dir)
+        .min_depth(1)
+        .contents_first(true)
+        .into_iter()
+        .filter_map(Result::ok)
+        .collect();
+    for entry in entries {
+        let Ok(path) = entry.path().strip_prefix(&publish_dir) else {
+            continue;
+        };
+        if is_kept(&keep, path) {
+            continue;
+        }
+        if entry.file_type().is_dir() {
+            if file::dirty_dir(entry.path()).is_ok_and(|dirty| !dirty) {
+                file::remove_dir(entry.path())?;
+            }
+        } else {
+            file::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! The health of the plugins, recording the failures of their calls.
+//! It is saved in the cache directory by the builds, so that `allay plugin list`
+//! can show the plugins failing or disabled in the last build.
+
+use crate::config::get_allay_config;
+use crate::file::{self, FileResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// The health of the plugins in the cache directory
+pub const PLUGIN_HEALTH_FILE: &str = "plugins.json";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PluginHealth {
+    pub calls: u64,
+    pub failures: u64,
+    /// the failures since the last successful call
+    pub consecutive_failures: u32,
+    /// the times the plugin is reinstantiated after a failure
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    /// a disabled plugin is no longer called
+    pub disabled: bool,
+}
+
+impl PluginHealth {
+    /// Record a successful call
+    pub fn succeed(&mut self) {
+        self.calls += 1;
+        self.consecutive_failures = 0;
+    }
+
+    /// Record a failed call, disabling the plugin after the given failures in a row.
+    /// Returns whether the plugin is disabled by this failure.
+    pub fn fail(&mut self, error: String, max_failures: u32) -> bool {
+        self.calls += 1;
+        self.failures += 1;
+        self.consecutive_failures += 1;
+        self.last_error = Some(error);
+        if !self.disabled && self.consecutive_failures >= max_failures {
+            self.disabled = true;
+            return true;
+        }
+        false
+    }
+
+    /// A short description of the health, like `healthy` or `disabled`
+    pub fn status(&self) -> &'static str {
+        if self.disabled {
+            "disabled"
+        } else if self.failures > 0 {
+            "failing"
+        } else {
+            "healthy"
+        }
+    }
+}
+
+fn health_path() -> PathBuf {
+    file::workspace(&get_allay_config().cache_dir).join(PLUGIN_HEALTH_FILE)
+}
+
+/// Load the health of the plugins in the last build, empty if there was none
+pub fn load_health() -> BTreeMap<String, PluginHealth> {
+    file::read_file_string(health_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_health(health: &BTreeMap<String, PluginHealth>) -> FileResult<()> {
+    let content = serde_json::to_string_pretty(health).unwrap_or_default();
+    file::write_file(health_path(), content)
+}
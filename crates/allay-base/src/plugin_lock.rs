@@ -0,0 +1,107 @@
+//! The lockfile of the plugins, `plugins.lock` in the site root.
+//! It records the version, the SHA-256 hash and the source of every installed plugin,
+//! so that a plugin changed after its installation is refused when the plugins are loaded.
+
+use crate::file::{self, FileError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// The lockfile in the site root
+pub const PLUGIN_LOCK_FILE: &str = "plugins.lock";
+
+#[derive(Error, Debug)]
+pub enum PluginLockError {
+    #[error("File error: {0}")]
+    File(#[from] FileError),
+
+    #[error("Invalid {PLUGIN_LOCK_FILE}: {0}")]
+    Parse(#[from] toml::de::Error),
+
+    #[error("Failed to write {PLUGIN_LOCK_FILE}: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+pub type PluginLockResult<T> = Result<T, PluginLockError>;
+
+/// An installed plugin recorded in the lockfile
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedPlugin {
+    /// the version reported by the plugin
+    pub version: String,
+    /// the hex SHA-256 hash of the `.wasm` file
+    pub sha256: String,
+    /// where the plugin is installed from, a local path, a URL or a registry directory
+    pub source: String,
+    /// the version requirement resolved in the registry, for the plugins installed from one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requirement: Option<String>,
+}
+
+/// The result of checking a plugin against the lockfile
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verification {
+    /// the plugin has the hash recorded
+    Verified,
+    /// the plugin is not recorded
+    Unlocked,
+    /// the plugin has another hash than the recorded one
+    Mismatch { expected: String, actual: String },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginLock {
+    #[serde(default)]
+    pub plugins: BTreeMap<String, LockedPlugin>,
+}
+
+impl PluginLock {
+    pub fn path() -> PathBuf {
+        file::workspace(PLUGIN_LOCK_FILE)
+    }
+
+    /// Read the lockfile of the site, `None` if there is none
+    pub fn read() -> PluginLockResult<Option<PluginLock>> {
+        let path = Self::path();
+        if !file::file_exists(&path) {
+            return Ok(None);
+        }
+        Ok(Some(toml::from_str(&file::read_file_string(path)?)?))
+    }
+
+    /// Read the lockfile of the site, or an empty one if there is none
+    pub fn read_or_default() -> PluginLockResult<PluginLock> {
+        Ok(Self::read()?.unwrap_or_default())
+    }
+
+    pub fn write(&self) -> PluginLockResult<()> {
+        let content = format!(
+            "# This file is generated by `allay plugin`, do not edit it manually\n\n{}",
+            toml::to_string(self)?
+        );
+        Ok(file::write_file(Self::path(), content)?)
+    }
+
+    /// Check the content of a plugin against its recorded hash
+    pub fn verify(&self, name: &str, content: &[u8]) -> Verification {
+        let Some(locked) = self.plugins.get(name) else {
+            return Verification::Unlocked;
+        };
+        let actual = sha256_hex(content);
+        if actual.eq_ignore_ascii_case(&locked.sha256) {
+            Verification::Verified
+        } else {
+            Verification::Mismatch {
+                expected: locked.sha256.clone(),
+                actual,
+            }
+        }
+    }
+}
+
+/// The full hex SHA-256 hash of a content
+pub fn sha256_hex(content: &[u8]) -> String {
+    Sha256::digest(content).iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,314 @@
+//! The statistics of a build for `allay build --report`, like the compile time of each page
+//! and template, the expansions of includes and shortcodes, the hit rates of the compiler caches
+//! and the time spent in the plugin hooks. Nothing is recorded unless enabled.
+
+use crate::file;
+use crate::lock;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// The accumulated time and count of something timed, like the compilations of a page
+#[derive(Debug, Default, Clone, Copy)]
+struct Timing {
+    count: usize,
+    total: Duration,
+}
+
+impl Timing {
+    fn add(&mut self, elapsed: Duration) {
+        self.count += 1;
+        self.total += elapsed;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    pages: BTreeMap<String, BTreeSet<PathBuf>>,
+    page_times: HashMap<PathBuf, Timing>,
+    template_times: HashMap<PathBuf, Timing>,
+    includes: BTreeMap<String, usize>,
+    shortcodes: BTreeMap<String, usize>,
+    caches: BTreeMap<String, (usize, usize)>,
+    hooks: BTreeMap<String, BTreeMap<String, Timing>>,
+}
+
+/// The recorder of the build statistics
+#[derive(Debug, Default)]
+pub struct BuildStats {
+    enabled: AtomicBool,
+    stats: Mutex<Stats>,
+}
+
+/// Display a path in the workspace relative to the root of the site
+fn relative(path: &Path) -> String {
+    let root = file::root();
+    path.strip_prefix(&root).unwrap_or(path).to_string_lossy().to_string()
+}
+
+impl BuildStats {
+    pub fn get_instance() -> &'static BuildStats {
+        static INSTANCE: OnceLock<BuildStats> = OnceLock::new();
+        INSTANCE.get_or_init(BuildStats::default)
+    }
+
+    /// Start recording the statistics
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Start a timer if enabled, to be passed to a `record_*_time` method
+    pub fn start(&self) -> Option<Instant> {
+        self.is_enabled().then(Instant::now)
+    }
+
+    /// Record a file published, by its kind like `article` or `static`
+    pub fn record_page<P: AsRef<Path>>(&self, kind: &str, path: P) {
+        if self.is_enabled() {
+            let mut stats = lock!(self.stats);
+            stats.pages.entry(kind.into()).or_default().insert(path.as_ref().into());
+        }
+    }
+
+    /// Record a compilation of a page with all its output formats
+    pub fn record_page_time<P: AsRef<Path>>(&self, path: P, start: Option<Instant>) {
+        if let Some(start) = start {
+            let mut stats = lock!(self.stats);
+            stats.page_times.entry(path.as_ref().into()).or_default().add(start.elapsed());
+        }
+    }
+
+    /// Record a rendering of a template, including the templates it includes
+    pub fn record_template_time<P: AsRef<Path>>(&self, path: P, start: Option<Instant>) {
+        if let Some(start) = start {
+            let mut stats = lock!(self.stats);
+            stats
+                .template_times
+                .entry(path.as_ref().into())
+                .or_default()
+                .add(start.elapsed());
+        }
+    }
+
+    /// Record an expansion of an include
+    pub fn record_include(&self, name: &str) {
+        if self.is_enabled() {
+            *lock!(self.stats).includes.entry(name.into()).or_default() += 1;
+        }
+    }
+
+    /// Record an expansion of a shortcode
+    pub fn record_shortcode(&self, name: &str) {
+        if self.is_enabled() {
+            *lock!(self.stats).shortcodes.entry(name.into()).or_default() += 1;
+        }
+    }
+
+    /// Record a lookup in a cache of the compiler
+    pub fn record_cache(&self, cache: &str, hit: bool) {
+        if self.is_enabled() {
+            let mut stats = lock!(self.stats);
+            let (hits, misses) = stats.caches.entry(cache.into()).or_default();
+            match hit {
+                true => *hits += 1,
+                false => *misses += 1,
+            }
+        }
+    }
+
+    /// Run a hook of a plugin, recording the time spent in it
+    pub fn time_hook<T>(&self, plugin: &str, hook: &str, f: impl FnOnce() -> T) -> T {
+        let Some(start) = self.start() else {
+            return f();
+        };
+        let res = f();
+        let mut stats = lock!(self.stats);
+        let hooks = stats.hooks.entry(plugin.into()).or_default();
+        hooks.entry(hook.into()).or_default().add(start.elapsed());
+        res
+    }
+
+    /// Summarize the statistics recorded, with the given number of slowest pages
+    pub fn report(&self, total: Duration, slowest: usize) -> BuildReport {
+        let stats = lock!(self.stats);
+
+        let pages = stats.pages.iter().map(|(kind, paths)| (kind.clone(), paths.len())).collect();
+        let timings = |times: &HashMap<PathBuf, Timing>| {
+            let mut timings: Vec<_> = times
+                .iter()
+                .map(|(path, timing)| TimingReport {
+                    path: relative(path),
+                    count: timing.count,
+                    ms: millis(timing.total),
+                })
+                .collect();
+            timings.sort_by(|a, b| b.ms.total_cmp(&a.ms).then_with(|| a.path.cmp(&b.path)));
+            timings
+        };
+        let page_times = timings(&stats.page_times);
+        let caches = stats
+            .caches
+            .iter()
+            .map(|(cache, (hits, misses))| {
+                let rate = match hits + misses {
+                    0 => 0.0,
+                    total => *hits as f64 / total as f64,
+                };
+                (
+                    cache.clone(),
+                    CacheReport {
+                        hits: *hits,
+                        misses: *misses,
+                        hit_rate: rate,
+                    },
+                )
+            })
+            .collect();
+        let plugins = stats
+            .hooks
+            .iter()
+            .map(|(plugin, hooks)| {
+                let hooks = hooks
+                    .iter()
+                    .map(|(hook, timing)| {
+                        let report = HookReport {
+                            calls: timing.count,
+                            ms: millis(timing.total),
+                        };
+                        (hook.clone(), report)
+                    })
+                    .collect();
+                (plugin.clone(), hooks)
+            })
+            .collect();
+
+        BuildReport {
+            total_ms: millis(total),
+            pages,
+            slowest: page_times.iter().take(slowest).cloned().collect(),
+            page_times,
+            template_times: timings(&stats.template_times),
+            includes: stats.includes.clone(),
+            shortcodes: stats.shortcodes.clone(),
+            caches,
+            plugins,
+        }
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// The compile time of a page or template, summed over its compilations
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingReport {
+    pub path: String,
+    pub count: usize,
+    pub ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheReport {
+    pub hits: usize,
+    pub misses: usize,
+    pub hit_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HookReport {
+    pub calls: usize,
+    pub ms: f64,
+}
+
+/// The report of a build, printed by `allay build --report` or written as JSON
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildReport {
+    pub total_ms: f64,
+    /// The number of files published by their kinds
+    pub pages: BTreeMap<String, usize>,
+    /// The slowest pages to compile
+    pub slowest: Vec<TimingReport>,
+    pub page_times: Vec<TimingReport>,
+    pub template_times: Vec<TimingReport>,
+    pub includes: BTreeMap<String, usize>,
+    pub shortcodes: BTreeMap<String, usize>,
+    pub caches: BTreeMap<String, CacheReport>,
+    /// The time spent in the hooks of each plugin
+    pub plugins: BTreeMap<String, BTreeMap<String, HookReport>>,
+}
+
+impl fmt::Display for BuildReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Build finished in {:.1} ms", self.total_ms)?;
+
+        writeln!(f, "\nPages:")?;
+        for (kind, count) in self.pages.iter() {
+            writeln!(f, "  {:<24} {:>8}", kind, count)?;
+        }
+
+        writeln!(f, "\nSlowest pages:")?;
+        for timing in self.slowest.iter() {
+            writeln!(
+                f,
+                "  {:>10.1} ms  {:>4}x  {}",
+                timing.ms, timing.count, timing.path
+            )?;
+        }
+
+        writeln!(f, "\nTemplates:")?;
+        for timing in self.template_times.iter() {
+            writeln!(
+                f,
+                "  {:>10.1} ms  {:>4}x  {}",
+                timing.ms, timing.count, timing.path
+            )?;
+        }
+
+        for (title, expansions) in [
+            ("Includes", &self.includes),
+            ("Shortcodes", &self.shortcodes),
+        ] {
+            if !expansions.is_empty() {
+                writeln!(f, "\n{}:", title)?;
+                for (name, count) in expansions.iter() {
+                    writeln!(f, "  {:<24} {:>8}", name, count)?;
+                }
+            }
+        }
+
+        writeln!(f, "\nCaches:")?;
+        for (cache, report) in self.caches.iter() {
+            writeln!(
+                f,
+                "  {:<24} {:>7.1}%  ({} hits, {} misses)",
+                cache,
+                report.hit_rate * 100.0,
+                report.hits,
+                report.misses
+            )?;
+        }
+
+        if !self.plugins.is_empty() {
+            writeln!(f, "\nPlugin hooks:")?;
+            for (plugin, hooks) in self.plugins.iter() {
+                for (hook, report) in hooks.iter() {
+                    writeln!(
+                        f,
+                        "  {:>10.1} ms  {:>4}x  {}::{}",
+                        report.ms, report.calls, plugin, hook
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -49,9 +49,10 @@ impl SiteMap {
         self.version
     }
 
+    /// The internal dump of the site map, which is kept out of the publish directory
     fn filepath() -> PathBuf {
         const FILE: &str = "sitemap.json";
-        file::workspace(&get_allay_config().publish_dir).join(FILE)
+        file::workspace(&get_allay_config().cache_dir).join(FILE)
     }
 
     pub fn dump(&self) {
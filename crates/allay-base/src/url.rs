@@ -1,7 +1,11 @@
-use crate::config::get_theme_config;
+use crate::config::{RedirectRule, get_theme_config};
 use crate::template::TemplateKind;
 use std::path::{Path, PathBuf};
 
+/// The file listing all the redirects of the site in the publish directory, one per line like
+/// `/old/path /new/path 301`, which is understood by the dev server and many static hosts
+pub const REDIRECTS_FILE: &str = "_redirects";
+
 /// Represents a standardized URL path in Allay.
 /// The path is normalized to handle common cases such as directory paths and HTML files.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -113,6 +117,52 @@ impl AllayUrlPath {
         }
     }
 
+    /// Create a standard [`AllayUrlPath`] from a site URL like `/blog/` or `about?x=1`,
+    /// ignoring the query and the fragment.
+    /// The `.` and `..` segments are resolved like browsers do, never going above the root,
+    /// so that the file of the URL is always inside the publish directory.
+    pub fn from_url(url: &str) -> Self {
+        let url = url.split(['?', '#']).next().unwrap_or_default();
+        let mut segments = vec![];
+        for segment in url.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => {
+                    segments.pop();
+                }
+                segment => segments.push(segment),
+            }
+        }
+        let is_dir = url.ends_with('/') || url.ends_with("/.") || url.ends_with("/..");
+        match (segments.join("/"), is_dir) {
+            (url, _) if url.is_empty() => AllayUrlPath::Index("/".into()),
+            (url, true) => Self::from(Self::to_dir(url)),
+            (url, false) => Self::from(url),
+        }
+    }
+
+    /// Get the file in the publish directory serving this URL path,
+    /// like `blog/` -> `blog/index.html`, `about` -> `about.html` and `main.css` -> `main.css`
+    pub fn file_path(&self) -> PathBuf {
+        match self {
+            AllayUrlPath::Other(p) if p.extension().is_some() => p.clone(),
+            AllayUrlPath::Index(_) => self.possible_paths().swap_remove(0),
+            AllayUrlPath::Html(p) | AllayUrlPath::Other(p) => {
+                p.with_added_extension(TemplateKind::Html.extension())
+            }
+        }
+    }
+
+    /// Normalize a site URL into its standard form, so that different forms of the same URL
+    /// can be compared, like `/blog/index.html` -> `blog/` and `/about.html` -> `about`.
+    /// The root URL is always `/`.
+    pub fn normalize(url: &str) -> String {
+        match Self::from_url(url).as_ref().to_string_lossy().trim_start_matches('/') {
+            "" => "/".into(),
+            path => path.to_string(),
+        }
+    }
+
     pub fn is_dir(path: impl AsRef<Path>) -> bool {
         path.as_ref().to_string_lossy().ends_with("/")
     }
@@ -126,3 +176,43 @@ impl AllayUrlPath {
         }
     }
 }
+
+/// Convert a text into a URL-friendly slug, like `Hello, World!` -> `hello-world`
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() || c == '_' {
+            slug.push(c);
+        } else if (c.is_whitespace() || c == '-') && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
+
+/// Parse the redirect rules in the format of [`REDIRECTS_FILE`].
+/// Empty lines, comments starting with `#` and malformed lines are ignored.
+pub fn parse_redirects(text: &str) -> Vec<RedirectRule> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let from = parts.next()?.to_string();
+            let to = parts.next()?.to_string();
+            let status = match parts.next() {
+                Some(status) => status.trim_end_matches('!').parse().ok()?,
+                None => 301,
+            };
+            Some(RedirectRule { from, to, status })
+        })
+        .collect()
+}
+
+/// Render the redirect rules in the format of [`REDIRECTS_FILE`]
+pub fn render_redirects(rules: &[RedirectRule]) -> String {
+    let mut text = String::from("# Generated by Allay, do not edit\n");
+    for rule in rules {
+        text.push_str(&format!("{} {} {}\n", rule.from, rule.to, rule.status));
+    }
+    text
+}
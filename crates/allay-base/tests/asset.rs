@@ -0,0 +1,21 @@
+use allay_base::asset::{content_hash, fingerprinted_path};
+use std::path::PathBuf;
+
+#[test]
+fn test_content_hash() {
+    // the SHA-256 of "hello" starts with 2cf24dba
+    assert_eq!(content_hash(b"hello"), "2cf24dba");
+    assert_ne!(content_hash(b"hello"), content_hash(b"hello!"));
+}
+
+#[test]
+fn test_fingerprinted_path() {
+    assert_eq!(
+        fingerprinted_path("css/style.css", "3f2a1c9e"),
+        PathBuf::from("css/style.3f2a1c9e.css")
+    );
+    assert_eq!(
+        fingerprinted_path("LICENSE", "3f2a1c9e"),
+        PathBuf::from("LICENSE.3f2a1c9e")
+    );
+}
@@ -0,0 +1,72 @@
+use allay_base::config::{
+    AllayConfig, DirArgs, Environment, ThemeLayer, env_config_file, set_config_string,
+};
+use std::path::PathBuf;
+
+#[test]
+fn test_override_dirs() {
+    let mut config = AllayConfig::default();
+    config.override_with(|key| (key == "publish_dir").then(|| "dist".to_string()));
+    assert_eq!(config.publish_dir, "dist");
+    assert_eq!(config.content_dir, "contents");
+
+    let dirs = DirArgs {
+        content_dir: Some("posts".into()),
+        ..DirArgs::default()
+    };
+    config.override_with(|key| dirs.get(key));
+    assert_eq!(config.publish_dir, "dist");
+    assert_eq!(config.content_dir, "posts");
+}
+
+#[test]
+fn test_env_config_file() {
+    assert_eq!(
+        env_config_file(&Environment::Production),
+        "allay.production.toml"
+    );
+    assert_eq!(
+        env_config_file(&Environment::Development),
+        "allay.development.toml"
+    );
+}
+
+#[test]
+fn test_set_config_string() {
+    let config =
+        "# my site\ntitle = \"Site\"\ntheme = \"old\" # the theme\n\n[params]\ntheme = 1\n";
+    assert_eq!(
+        set_config_string(config, "theme", "new").unwrap(),
+        "# my site\ntitle = \"Site\"\ntheme = \"new\" # the theme\n\n[params]\ntheme = 1\n"
+    );
+
+    let config = "title = \"Site\"\n[params]\ntheme = 1\n";
+    assert_eq!(
+        set_config_string(config, "theme", "new").unwrap(),
+        "title = \"Site\"\ntheme = \"new\"\n[params]\ntheme = 1\n"
+    );
+    assert_eq!(
+        set_config_string("title = 1", "theme", "a").unwrap(),
+        "title = 1\ntheme = \"a\"\n"
+    );
+    // a value of another type is replaced, not added again
+    assert_eq!(
+        set_config_string("theme = 1 # old\n", "theme", "a").unwrap(),
+        "theme = \"a\" # old\n"
+    );
+    assert!(set_config_string("theme = ", "theme", "a").is_err());
+}
+
+#[test]
+fn test_theme_layer_dirs() {
+    let config = "[theme]\nname = \"t\"\nversion = \"0.1.0\"\nauthor = \"a\"\nextends = \"base\"\n\
+        [config]\nstatic = \"assets\"\n";
+    let layer = ThemeLayer {
+        path: PathBuf::from("themes/t"),
+        config: toml::from_str(config).unwrap(),
+    };
+    assert_eq!(layer.config.meta.extends.as_deref(), Some("base"));
+    assert_eq!(layer.static_dir(), PathBuf::from("themes/t/assets"));
+    assert_eq!(layer.custom_dir(), PathBuf::from("themes/t/custom"));
+    assert_eq!(layer.template_dir(), PathBuf::from("themes/t/templates"));
+}
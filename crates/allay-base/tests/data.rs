@@ -90,3 +90,41 @@ subkey2 = 100
     );
     Ok(())
 }
+
+#[test]
+fn test_toml_table() -> DataResult<()> {
+    let table: toml::Table = toml::from_str("accent = \"#f60\"\n[social]\ngithub = \"me\"\n")?;
+    let data = AllayData::from_toml_table(table)?;
+    assert_eq!(data.get("accent").unwrap().as_str()?, "#f60");
+    assert_eq!(
+        data.get("social").unwrap().as_obj()?.get("github").unwrap().as_str()?,
+        "me"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_merge_object() -> DataResult<()> {
+    let mut base = AllayData::from_toml(
+        r#"
+title = "Site"
+[build]
+publish_dir = "public"
+content_dir = "contents"
+"#,
+    )?;
+    let overlay = AllayData::from_toml(
+        r#"
+title = "Preview"
+[build]
+publish_dir = "preview"
+"#,
+    )?;
+    AllayData::merge_object(&mut base, overlay);
+
+    assert_eq!(base["title"].as_str()?, "Preview");
+    let build = base["build"].as_obj()?;
+    assert_eq!(build["publish_dir"].as_str()?, "preview");
+    assert_eq!(build["content_dir"].as_str()?, "contents");
+    Ok(())
+}
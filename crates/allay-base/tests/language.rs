@@ -0,0 +1,46 @@
+use allay_base::config::{Language, LanguageConfig};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+fn config() -> LanguageConfig {
+    LanguageConfig {
+        default_language: "en".into(),
+        languages: BTreeMap::from([
+            ("en".into(), Language::default()),
+            ("zh".into(), Language::default()),
+        ]),
+    }
+}
+
+#[test]
+fn test_split_language() {
+    let config = config();
+    assert_eq!(
+        config.split_language("posts/foo.zh.md"),
+        ("zh".into(), PathBuf::from("posts/foo.md"))
+    );
+    assert_eq!(
+        config.split_language("posts/foo.en.md"),
+        ("en".into(), PathBuf::from("posts/foo.md"))
+    );
+    assert_eq!(
+        config.split_language("posts/foo.md"),
+        ("en".into(), PathBuf::from("posts/foo.md"))
+    );
+    // not a configured language
+    assert_eq!(
+        config.split_language("posts/v1.2.md"),
+        ("en".into(), PathBuf::from("posts/v1.2.md"))
+    );
+}
+
+#[test]
+fn test_localize() {
+    let config = config();
+    assert_eq!(config.localize("index.zh.md"), PathBuf::from("zh/index.md"));
+    assert_eq!(
+        config.localize("posts/foo.en.md"),
+        PathBuf::from("posts/foo.md")
+    );
+    assert_eq!(config.localize("about.md"), PathBuf::from("about.md"));
+}
@@ -0,0 +1,92 @@
+use allay_base::manifest::{CleanRefused, check_clean_dir, is_kept};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::{Path, PathBuf};
+
+fn keep(patterns: &[&str]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).unwrap());
+    }
+    builder.build().unwrap()
+}
+
+#[test]
+fn test_is_kept() {
+    let keep = keep(&["CNAME", ".well-known", "*.pdf"]);
+    assert!(is_kept(&keep, "CNAME"));
+    assert!(is_kept(&keep, ".well-known/security.txt"));
+    assert!(is_kept(&keep, "docs/manual.pdf"));
+    assert!(!is_kept(&keep, "docs/CNAME.html"));
+    assert!(!is_kept(&keep, "index.html"));
+    assert!(!is_kept(&GlobSet::empty(), "CNAME"));
+}
+
+fn site_dirs(root: &Path) -> Vec<(&'static str, PathBuf)> {
+    vec![
+        ("content", root.join("contents")),
+        ("theme", root.join("themes")),
+        ("static", root.join("static")),
+        ("plugin", root.join("plugins")),
+        ("cache", root.join(".allay")),
+    ]
+}
+
+#[test]
+fn test_clean_dir() {
+    let site = tempfile::tempdir().unwrap();
+    let root = site.path().canonicalize().unwrap();
+    std::fs::create_dir_all(root.join("contents")).unwrap();
+    std::fs::create_dir_all(root.join("public")).unwrap();
+    let dirs = site_dirs(&root);
+    let check = |dir: &Path, allow_outside| check_clean_dir(&root, dir, &dirs, allow_outside);
+
+    assert_eq!(check(&root.join("public"), false), Ok(()));
+    assert_eq!(check(&root.join("out/site"), false), Ok(()));
+
+    // the root itself, in any spelling, and its ancestors
+    for dir in [root.clone(), root.join("."), root.join("public/..")] {
+        assert_eq!(
+            check(&dir, true),
+            Err(CleanRefused::ContainsRoot(root.clone()))
+        );
+    }
+    assert!(matches!(
+        check(root.parent().unwrap(), true),
+        Err(CleanRefused::ContainsRoot(_))
+    ));
+    assert!(matches!(
+        check(Path::new("/"), true),
+        Err(CleanRefused::ContainsRoot(_))
+    ));
+
+    // a directory of the site, or one containing it
+    for (kind, dir) in dirs.iter() {
+        assert_eq!(
+            check(dir, false),
+            Err(CleanRefused::ContainsDir(dir.clone(), kind, dir.clone()))
+        );
+    }
+    let parent = root.join("site");
+    let dirs = vec![("theme", parent.join("themes"))];
+    assert_eq!(
+        check_clean_dir(&root, &parent, &dirs, false),
+        Err(CleanRefused::ContainsDir(
+            parent.clone(),
+            "theme",
+            parent.join("themes")
+        ))
+    );
+
+    // outside of the root, unless allowed
+    let outside = tempfile::tempdir().unwrap();
+    let outside = outside.path().canonicalize().unwrap();
+    assert_eq!(
+        check(&outside, false),
+        Err(CleanRefused::Outside(outside.clone()))
+    );
+    assert_eq!(check(&outside, true), Ok(()));
+    assert!(matches!(
+        check(&root.join("../elsewhere"), false),
+        Err(CleanRefused::Outside(_))
+    ));
+}
@@ -0,0 +1,34 @@
+use allay_base::config::OutputConfig;
+use allay_base::data::{AllayData, AllayObject};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+fn config() -> OutputConfig {
+    OutputConfig {
+        sections: BTreeMap::from([("docs".into(), vec!["json".into(), "TXT".into()])]),
+    }
+}
+
+#[test]
+fn test_output_formats() {
+    let config = config();
+    let empty = AllayObject::new();
+    assert_eq!(
+        config.formats_of("docs/a.md", &empty),
+        ["html", "json", "txt"]
+    );
+    assert_eq!(config.formats_of("posts/a.md", &empty), ["html"]);
+    // the pages directly under the content directory have no section
+    assert_eq!(config.formats_of("docs.md", &empty), ["html"]);
+
+    let list = AllayObject::from([(
+        "outputs".to_string(),
+        Arc::new(AllayData::from(vec![
+            Arc::new(AllayData::from("json")),
+            Arc::new(AllayData::from("html")),
+        ])),
+    )]);
+    assert_eq!(config.formats_of("docs/a.md", &list), ["html", "json"]);
+    let single = AllayObject::from([("outputs".to_string(), Arc::new(AllayData::from("txt")))]);
+    assert_eq!(config.formats_of("posts/a.md", &single), ["html", "txt"]);
+}
@@ -0,0 +1,57 @@
+use allay_base::config::PermalinkConfig;
+use allay_base::data::{AllayData, AllayObject};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+fn config() -> PermalinkConfig {
+    PermalinkConfig {
+        patterns: BTreeMap::from([
+            ("posts".into(), "/:year/:month/:slug/".into()),
+            ("notes".into(), "/:section/:day-:title".into()),
+        ]),
+    }
+}
+
+fn meta(pairs: &[(&str, &str)]) -> AllayObject {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), Arc::new(AllayData::from(*v))))
+        .collect()
+}
+
+#[test]
+fn test_resolve_permalink() {
+    let config = config();
+    let dated = meta(&[("date", "2025-03-14 08:00:00"), ("title", "Hello, World!")]);
+    assert_eq!(
+        config.resolve("posts/My Post.md", &dated),
+        Some("2025/03/my-post/".into())
+    );
+    assert_eq!(
+        config.resolve("posts/bundle/index.md", &dated),
+        Some("2025/03/bundle/".into())
+    );
+    assert_eq!(
+        config.resolve("notes/a.md", &dated),
+        Some("notes/14-hello-world".into())
+    );
+
+    let slugged = meta(&[("date", "2025-03-14"), ("slug", "custom")]);
+    assert_eq!(
+        config.resolve("posts/a/b.md", &slugged),
+        Some("2025/03/custom/".into())
+    );
+}
+
+#[test]
+fn test_unresolved_permalink() {
+    let config = config();
+    let dated = meta(&[("date", "2025-03-14")]);
+    // no date
+    assert_eq!(config.resolve("posts/a.md", &meta(&[])), None);
+    // no pattern of the section
+    assert_eq!(config.resolve("pages/a.md", &dated), None);
+    // the index page of the section and the pages out of sections
+    assert_eq!(config.resolve("posts/index.md", &dated), None);
+    assert_eq!(config.resolve("about.md", &dated), None);
+}
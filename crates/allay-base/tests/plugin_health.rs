@@ -0,0 +1,23 @@
+use allay_base::plugin_health::PluginHealth;
+
+#[test]
+fn test_disable_after_failures_in_a_row() {
+    let mut health = PluginHealth::default();
+    assert_eq!(health.status(), "healthy");
+
+    assert!(!health.fail("trap".into(), 2));
+    health.succeed();
+    assert_eq!(health.consecutive_failures, 0);
+    assert_eq!(health.status(), "failing");
+
+    assert!(!health.fail("trap".into(), 2));
+    assert!(health.fail("timeout".into(), 2));
+    assert!(health.disabled);
+    assert_eq!(health.status(), "disabled");
+    assert_eq!(health.calls, 4);
+    assert_eq!(health.failures, 3);
+    assert_eq!(health.last_error.as_deref(), Some("timeout"));
+
+    // a disabled plugin is only disabled once
+    assert!(!health.fail("timeout".into(), 2));
+}
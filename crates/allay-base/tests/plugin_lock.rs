@@ -0,0 +1,48 @@
+use allay_base::plugin_lock::{LockedPlugin, PluginLock, Verification, sha256_hex};
+
+#[test]
+fn test_sha256_hex() {
+    assert_eq!(
+        sha256_hex(b"abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
+
+#[test]
+fn test_verify() {
+    let mut lock = PluginLock::default();
+    lock.plugins.insert(
+        "toc".into(),
+        LockedPlugin {
+            version: "0.1.0".into(),
+            sha256: sha256_hex(b"plugin").to_uppercase(),
+            source: "plugins/toc.wasm".into(),
+            requirement: None,
+        },
+    );
+
+    assert_eq!(lock.verify("toc", b"plugin"), Verification::Verified);
+    assert_eq!(lock.verify("other", b"plugin"), Verification::Unlocked);
+    assert!(matches!(
+        lock.verify("toc", b"modified"),
+        Verification::Mismatch { .. }
+    ));
+}
+
+#[test]
+fn test_lock_round_trip() {
+    let content = r#"
+[plugins.toc]
+version = "0.2.1"
+sha256 = "00ff"
+source = "registry"
+requirement = "^0.2"
+"#;
+    let lock: PluginLock = toml::from_str(content).unwrap();
+    let toc = &lock.plugins["toc"];
+    assert_eq!(toc.version, "0.2.1");
+    assert_eq!(toc.requirement.as_deref(), Some("^0.2"));
+
+    let written: PluginLock = toml::from_str(&toml::to_string(&lock).unwrap()).unwrap();
+    assert_eq!(written.plugins, lock.plugins);
+}
@@ -0,0 +1,36 @@
+use allay_base::report::BuildStats;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_build_report() {
+    let stats = BuildStats::get_instance();
+    // nothing is recorded before enabled
+    stats.record_page("article", "a.md");
+    assert!(stats.start().is_none());
+
+    stats.enable();
+    for path in ["a.md", "b.md", "a.md"] {
+        stats.record_page("article", path);
+        stats.record_page_time(path, stats.start());
+    }
+    stats.record_page_time("b.md", Some(Instant::now() - Duration::from_millis(50)));
+    stats.record_shortcode("note");
+    stats.record_shortcode("note");
+    stats.record_cache("pages", true);
+    stats.record_cache("pages", false);
+    stats.record_cache("pages", true);
+    stats.record_cache("pages", true);
+    assert_eq!(stats.time_hook("sort", "get_sort_order", || 42), 42);
+
+    let report = stats.report(Duration::from_secs(1), 1);
+    assert_eq!(report.total_ms, 1000.0);
+    assert_eq!(report.pages["article"], 2);
+    assert_eq!(report.slowest.len(), 1);
+    assert_eq!(report.slowest[0].path, "b.md");
+    assert_eq!(report.slowest[0].count, 2);
+    assert_eq!(report.page_times.len(), 2);
+    assert_eq!(report.shortcodes["note"], 2);
+    assert!(report.includes.is_empty());
+    assert_eq!(report.caches["pages"].hit_rate, 0.75);
+    assert_eq!(report.plugins["sort"]["get_sort_order"].calls, 1);
+}
@@ -0,0 +1,57 @@
+use allay_base::config::RedirectRule;
+use allay_base::url::{parse_redirects, render_redirects, slugify};
+
+fn rule(from: &str, to: &str, status: u16) -> RedirectRule {
+    RedirectRule {
+        from: from.into(),
+        to: to.into(),
+        status,
+    }
+}
+
+#[test]
+fn test_parse_redirects() {
+    let text = "# comment\n\n/old /new\n/tmp   /posts/tmp 302 # temporary\n/forced /x 301!\nbroken\n/bad /x abc\n";
+    assert_eq!(
+        parse_redirects(text),
+        vec![
+            rule("/old", "/new", 301),
+            rule("/tmp", "/posts/tmp", 302),
+            rule("/forced", "/x", 301),
+        ]
+    );
+}
+
+#[test]
+fn test_render_redirects() {
+    let rules = vec![
+        rule("/old", "/new", 301),
+        rule("/a/", "https://example.com/", 302),
+    ];
+    assert_eq!(parse_redirects(&render_redirects(&rules)), rules);
+}
+
+#[test]
+fn test_slugify() {
+    assert_eq!(slugify("Hello, World!"), "hello-world");
+    assert_eq!(slugify("  Getting -- Started  "), "getting-started");
+    assert_eq!(slugify("配置 Allay"), "配置-allay");
+    assert_eq!(slugify("snake_case"), "snake_case");
+}
+
+#[test]
+fn test_url_inside_publish_dir() {
+    use allay_base::url::AllayUrlPath;
+    use std::path::PathBuf;
+
+    let file_path = |url| AllayUrlPath::from_url(url).file_path();
+    assert_eq!(
+        file_path("../../etc/passwd.txt"),
+        PathBuf::from("etc/passwd.txt")
+    );
+    assert_eq!(file_path("/a/./b/../c.css"), PathBuf::from("a/c.css"));
+    assert_eq!(file_path("//tmp/x.js?v=1"), PathBuf::from("tmp/x.js"));
+    assert_eq!(file_path("/posts/../../foo"), PathBuf::from("foo.html"));
+    assert_eq!(AllayUrlPath::normalize("/a/b/../"), "a/");
+    assert_eq!(AllayUrlPath::normalize("/../.."), "/");
+}
@@ -0,0 +1,93 @@
+use allay_base::config::DEFAULT_SITE_CONFIG;
+use allay_base::config::validate::{SITE_SCHEMA, Severity, THEME_SCHEMA, validate};
+
+#[test]
+fn test_default_site_config() {
+    assert!(validate("allay.toml", DEFAULT_SITE_CONFIG, &SITE_SCHEMA).is_empty());
+}
+
+#[test]
+fn test_unknown_key() {
+    let config = "title = \"Site\"\ntitel = \"Site\"\n[feed]\nlimits = 10\n";
+    let problems = validate("allay.toml", config, &SITE_SCHEMA);
+    assert_eq!(problems.len(), 2);
+    assert_eq!(problems[0].severity, Severity::Warning);
+    assert_eq!(problems[0].line, 2);
+    assert_eq!(problems[0].key, "titel");
+    assert!(problems[0].message.contains("`title`"));
+    assert_eq!(problems[1].line, 4);
+    assert_eq!(problems[1].key, "feed.limits");
+}
+
+#[test]
+fn test_custom_site_key() {
+    // the custom keys of the site are available in the templates, unless they look like a typo
+    let config = "copyright = \"2025\"\n[social]\ngithub = \"allay\"\n";
+    assert!(validate("allay.toml", config, &SITE_SCHEMA).is_empty());
+    let problems = validate("allay.toml", "[images]\nwidth = [480]\n", &SITE_SCHEMA);
+    assert_eq!(problems[0].key, "images.width");
+}
+
+#[test]
+fn test_integer_range() {
+    let config = "[feed]\nlimit = -1\n[images]\nquality = 300\nwidths = [480, 0x10]\n";
+    let problems = validate("allay.toml", config, &SITE_SCHEMA);
+    assert_eq!(problems.len(), 2);
+    assert!(problems.iter().all(|problem| problem.is_error()));
+    assert_eq!(problems[0].key, "feed.limit");
+    assert_eq!(problems[1].key, "images.quality");
+    assert_eq!(
+        problems[1].message,
+        "expected an integer from 0 to 255, found 300"
+    );
+}
+
+#[test]
+fn test_type_error() {
+    let config = "[feed]\nlimit = \"ten\"\nformats = [\"rss\", \"json\"]\n";
+    let problems = validate("allay.toml", config, &SITE_SCHEMA);
+    assert_eq!(problems.len(), 2);
+    assert!(problems.iter().all(|problem| problem.is_error()));
+    assert_eq!(problems[0].key, "feed.limit");
+    assert_eq!(problems[0].line, 2);
+    assert_eq!(
+        problems[0].to_string(),
+        "error: allay.toml:2: `feed.limit`: expected an integer, found a string"
+    );
+    assert_eq!(problems[1].key, "feed.formats[1]");
+}
+
+#[test]
+fn test_missing_required_key() {
+    let config = "[theme]\nname = \"t\"\nversion = \"0.1.0\"\n";
+    let problems = validate("theme.toml", config, &THEME_SCHEMA);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].is_error());
+    assert_eq!(problems[0].key, "theme");
+    assert!(problems[0].message.contains("`author`"));
+
+    let problems = validate("allay.toml", "[[redirects]]\nto = \"/a\"\n", &SITE_SCHEMA);
+    assert_eq!(problems[0].key, "redirects[0]");
+}
+
+#[test]
+fn test_syntax_error() {
+    let problems = validate(
+        "allay.toml",
+        "title = \"Site\"\nbase_url = \n",
+        &SITE_SCHEMA,
+    );
+    assert!(!problems.is_empty());
+    assert!(problems.iter().all(|problem| problem.is_error() && problem.line == 2));
+}
+
+#[test]
+fn test_theme_params_schema() {
+    let config = "[theme]\nname = \"t\"\nversion = \"0.1.0\"\nauthor = \"a\"\n\
+        [params]\naccent = \"#f60\"\n[params_schema.accent]\ntype = \"color\"\n";
+    let problems = validate("theme.toml", config, &THEME_SCHEMA);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].is_error());
+    assert_eq!(problems[0].key, "params_schema.accent.type");
+    assert_eq!(problems[0].line, 8);
+}
@@ -1,9 +1,15 @@
 mod build;
+mod content;
 mod create;
+mod git;
 mod plugin;
+mod registry;
 mod serve;
+mod theme;
 
 pub use build::build;
+pub use content::new_content;
 pub use create::{init, new};
 pub use plugin::plugin;
 pub use serve::serve;
+pub use theme::theme;
@@ -1,8 +1,12 @@
-use allay_base::config::{BuildArgs, get_theme_config};
+use allay_base::config::{BuildArgs, get_build_config, get_theme_config};
+use allay_base::file;
 use allay_base::log::show_error;
+use allay_base::manifest::{BuildManifest, clean_publish_dir};
+use allay_base::report::BuildStats;
 #[cfg(feature = "plugin")]
 use allay_plugin::PluginManager;
 use anyhow::Ok;
+use std::time::Instant;
 use tracing::instrument;
 
 pub fn load_plugins() -> anyhow::Result<()> {
@@ -38,9 +42,52 @@ pub fn load_plugins() -> anyhow::Result<()> {
 }
 
 /// CLI Build Command
-#[instrument(name = "building the site", skip(_args))]
-pub fn build(_args: &BuildArgs) -> anyhow::Result<()> {
+#[instrument(name = "building the site", skip(args))]
+pub fn build(args: &BuildArgs) -> anyhow::Result<()> {
+    let stats = BuildStats::get_instance();
+    if args.report || args.report_json.is_some() {
+        stats.enable();
+    }
+    let start = Instant::now();
+
     load_plugins()?;
-    allay_publish::generate_once();
+    if args.clean {
+        clean_publish_dir(args.clean_outside)?;
+    }
+
+    let last = BuildManifest::load();
+    let failures = allay_publish::generate_once();
+    #[cfg(feature = "plugin")]
+    PluginManager::instance().save_health()?;
+
+    // the outputs of the failed files are not written, so keep the last ones until they are fixed
+    let manifest = BuildManifest::get_instance();
+    if failures.is_empty() && get_build_config().prune {
+        let removed = manifest.prune(&last)?;
+        if !removed.is_empty() {
+            println!("Removed {} stale file(s)", removed.len());
+        }
+    }
+    manifest.save(&last)?;
+
+    if stats.is_enabled() {
+        let report = stats.report(start.elapsed(), args.slowest);
+        if args.report {
+            print!("{}", report);
+        }
+        if let Some(path) = &args.report_json {
+            file::write_file(path, serde_json::to_string_pretty(&report)?)?;
+        }
+    }
+
+    if !failures.is_empty() {
+        for (path, error) in failures.iter() {
+            eprintln!("❌ {}: {}", path.display(), error);
+        }
+        return Err(anyhow::anyhow!(
+            "{} file(s) failed to build",
+            failures.len()
+        ));
+    }
     Ok(())
 }
@@ -0,0 +1,200 @@
+use allay_base::config::{NewContentArgs, get_allay_config, layered_dirs};
+use allay_base::data::{AllayData, AllayObject};
+use allay_base::file;
+use allay_base::sitemap::SiteMap;
+use allay_base::url::slugify;
+use allay_compiler::Compiler;
+use anyhow::anyhow;
+use chrono::{Local, SecondsFormat};
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tracing::{info, instrument};
+
+/// The front matter of the contents if neither the site nor the theme provides an archetype
+#[derive(Serialize)]
+struct DefaultFrontMatter<'a> {
+    title: &'a str,
+    date: &'a str,
+    draft: bool,
+}
+
+/// The name of the archetype for the contents of any section
+const DEFAULT_KIND: &str = "default";
+
+/// CLI New Content Command
+#[instrument(name = "creating the content", skip(args))]
+pub fn new_content(args: &NewContentArgs) -> anyhow::Result<()> {
+    let content_dir = file::workspace(&get_allay_config().content_dir);
+    let (target, name) = content_target(&args.path, args.bundle, &content_dir)?;
+
+    // the section is the top-level directory of the content, like `posts`
+    let section = Path::new(&args.path)
+        .parent()
+        .and_then(|parent| parent.components().next())
+        .map(|section| section.as_os_str().to_string_lossy().to_string());
+    let kind = args.kind.clone().or(section);
+
+    // the archetypes are rendered without building the site, so no pages are listed
+    SiteMap::set_instance(SiteMap::default());
+    let dirs: Vec<_> = layered_dirs(&get_allay_config().archetype_dir)
+        .into_iter()
+        .map(file::workspace)
+        .collect();
+    let content = match find_archetype(kind.as_deref(), &dirs) {
+        Some(archetype) => {
+            info!("Using the archetype {}", archetype.display());
+            let source = file::read_file_string(&archetype)?;
+            let include_dir = archetype.parent().unwrap_or(Path::new("")).to_path_buf();
+            Compiler::render(&source, archetype_data(&name), include_dir)?
+        }
+        None => default_content(&name)?,
+    };
+
+    file::write_file(&target, content)?;
+    println!("✅ Content created at: {}", target.display());
+    Ok(())
+}
+
+/// Get the file of a new content in the content directory with its name.
+/// The path must stay inside the content directory, and an existing content is never overwritten.
+fn content_target(
+    path: &str,
+    bundle: bool,
+    content_dir: &Path,
+) -> anyhow::Result<(PathBuf, String)> {
+    let mut path = PathBuf::from(path);
+    if !path.components().all(|component| matches!(component, Component::Normal(_))) {
+        return Err(anyhow!(
+            "Content path must be relative inside the content directory: {}",
+            path.display()
+        ));
+    }
+    // the dots in the name are kept, like `v1.2-release` for `v1.2-release.md`
+    if path.extension().is_none_or(|ext| ext != "md") {
+        path.as_mut_os_string().push(".md");
+    }
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow!("Invalid content path: {}", path.display()))?;
+    if bundle {
+        path = path.with_extension("").join("index.md");
+    }
+
+    let target = content_dir.join(&path);
+    if file::file_exists(&target) {
+        return Err(anyhow!("Content already exists: {}", target.display()));
+    }
+    Ok((target, name))
+}
+
+/// Find the archetype of a kind in the archetype directories from the site to the themes,
+/// falling back to the `default` one
+fn find_archetype(kind: Option<&str>, dirs: &[PathBuf]) -> Option<PathBuf> {
+    kind.into_iter()
+        .chain([DEFAULT_KIND])
+        .flat_map(|kind| dirs.iter().map(move |dir| dir.join(kind).with_extension("md")))
+        .find(|archetype| file::file_exists(archetype))
+}
+
+/// The content without any archetype, whose front matter is serialized to stay valid
+/// with any title, like `Foo: bar`
+fn default_content(name: &str) -> anyhow::Result<String> {
+    let data = archetype_data(name);
+    let field = |key: &str| data.get(key).map(|value| value.to_string()).unwrap_or_default();
+    let front_matter = DefaultFrontMatter {
+        title: &field("title"),
+        date: &field("now"),
+        draft: true,
+    };
+    Ok(format!(
+        "---\n{}---\n",
+        serde_yaml::to_string(&front_matter)?
+    ))
+}
+
+/// The data of an archetype, as `this` in its template
+fn archetype_data(name: &str) -> AllayObject {
+    let title = name
+        .split(['-', '_'])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or(String::new(), |first| {
+                first.to_uppercase().chain(chars).collect()
+            })
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let now = Local::now().to_rfc3339_opts(SecondsFormat::Secs, false);
+
+    [("title", title), ("slug", slugify(name)), ("now", now)]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), Arc::new(AllayData::from(value))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let (target, name) = content_target("posts/v1.2-release", false, dir.path()).unwrap();
+        assert_eq!(target, dir.path().join("posts/v1.2-release.md"));
+        assert_eq!(name, "v1.2-release");
+        let (target, _) = content_target("posts/my-post.md", true, dir.path()).unwrap();
+        assert_eq!(target, dir.path().join("posts/my-post/index.md"));
+
+        assert!(content_target("../outside.md", false, dir.path()).is_err());
+        assert!(content_target("posts/../../outside.md", false, dir.path()).is_err());
+        assert!(content_target("/tmp/outside.md", false, dir.path()).is_err());
+
+        // an existing content is never overwritten
+        std::fs::write(dir.path().join("about.md"), "---\n---\n").unwrap();
+        assert!(content_target("about.md", false, dir.path()).is_err());
+        assert!(content_target("about", false, dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_find_archetype() {
+        let site = tempfile::tempdir().unwrap();
+        let theme = tempfile::tempdir().unwrap();
+        let dirs = [site.path().to_path_buf(), theme.path().to_path_buf()];
+        assert_eq!(find_archetype(Some("posts"), &dirs), None);
+
+        std::fs::write(theme.path().join("default.md"), "").unwrap();
+        std::fs::write(theme.path().join("posts.md"), "").unwrap();
+        assert_eq!(
+            find_archetype(None, &dirs),
+            Some(theme.path().join("default.md"))
+        );
+        assert_eq!(
+            find_archetype(Some("posts"), &dirs),
+            Some(theme.path().join("posts.md"))
+        );
+
+        // the site overrides the theme, while the kind goes before the default one
+        std::fs::write(site.path().join("posts.md"), "").unwrap();
+        std::fs::write(site.path().join("default.md"), "").unwrap();
+        assert_eq!(
+            find_archetype(Some("posts"), &dirs),
+            Some(site.path().join("posts.md"))
+        );
+        assert_eq!(
+            find_archetype(Some("page"), &dirs),
+            Some(site.path().join("default.md"))
+        );
+    }
+
+    #[test]
+    fn test_default_content() {
+        let content = default_content("foo:-bar").unwrap();
+        let front_matter = content.trim_start_matches("---\n").trim_end_matches("---\n");
+        let value: serde_yaml::Value = serde_yaml::from_str(front_matter).unwrap();
+        assert_eq!(value["title"].as_str(), Some("Foo: Bar"));
+        assert_eq!(value["draft"].as_bool(), Some(true));
+    }
+}
@@ -1,14 +1,14 @@
+use super::git::clone_repository_with_progress;
 use allay_base::{config::*, file};
 use dialoguer::{Confirm, theme::ColorfulTheme};
-use git2::{FetchOptions, RemoteCallbacks, build::RepoBuilder};
-use std::path::Path;
 use tracing::{info, instrument};
 
 /// CLI Init Command
 pub fn init(args: &InitArgs) -> anyhow::Result<()> {
     new(&NewArgs {
-        dir: ".".into(),
+        dir: Some(".".into()),
         skip_theme: args.skip_theme,
+        command: None,
     })
 }
 
@@ -64,9 +64,7 @@ fn ask_to_clone_default_theme() -> anyhow::Result<()> {
 }
 
 fn clone_default_theme(url: &str) -> anyhow::Result<()> {
-    const THEME_NAME: &str = "Axolotl";
-
-    let target_dir = file::workspace(&get_allay_config().theme_dir).join(THEME_NAME);
+    let target_dir = file::workspace(&get_allay_config().theme_dir).join(DEFAULT_THEME_NAME);
 
     if file::dir_exists(&target_dir) {
         println!("⚠️  Theme directory already exists at: {:?}", target_dir);
@@ -87,32 +85,3 @@ fn clone_default_theme(url: &str) -> anyhow::Result<()> {
         }
     }
 }
-
-fn clone_repository_with_progress(url: &str, into: &Path) -> anyhow::Result<()> {
-    let mut callbacks = RemoteCallbacks::new();
-    callbacks.transfer_progress(|progress| {
-        if progress.received_objects() == progress.total_objects() {
-            println!(
-                "Resolving deltas {}/{}",
-                progress.indexed_deltas(),
-                progress.total_deltas()
-            );
-        } else if progress.total_objects() > 0 {
-            println!(
-                "Receiving objects: {}% ({}/{})",
-                progress.received_objects() * 100 / progress.total_objects(),
-                progress.received_objects(),
-                progress.total_objects()
-            );
-        }
-        true
-    });
-
-    info!("Cloning theme repository from {} to {:?}", url, into);
-
-    let mut fetch_options = FetchOptions::new();
-    fetch_options.remote_callbacks(callbacks);
-
-    RepoBuilder::new().fetch_options(fetch_options).clone(url, into)?;
-    Ok(())
-}
@@ -0,0 +1,145 @@
+use git2::build::{CheckoutBuilder, RepoBuilder};
+use git2::{FetchOptions, RemoteCallbacks, Repository, StatusOptions};
+use std::path::Path;
+use tracing::info;
+
+/// Check if a source looks like a git URL rather than a local directory
+pub fn is_git_url(source: &str) -> bool {
+    ["https://", "http://", "git://", "ssh://", "git@"]
+        .iter()
+        .any(|prefix| source.starts_with(prefix))
+        || source.ends_with(".git")
+}
+
+fn fetch_options_with_progress<'a>() -> FetchOptions<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.transfer_progress(|progress| {
+        if progress.received_objects() == progress.total_objects() {
+            println!(
+                "Resolving deltas {}/{}",
+                progress.indexed_deltas(),
+                progress.total_deltas()
+            );
+        } else if progress.total_objects() > 0 {
+            println!(
+                "Receiving objects: {}% ({}/{})",
+                progress.received_objects() * 100 / progress.total_objects(),
+                progress.received_objects(),
+                progress.total_objects()
+            );
+        }
+        true
+    });
+
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks);
+    fetch_options
+}
+
+pub fn clone_repository_with_progress(url: &str, into: &Path) -> anyhow::Result<()> {
+    info!("Cloning repository from {} to {:?}", url, into);
+
+    RepoBuilder::new()
+        .fetch_options(fetch_options_with_progress())
+        .clone(url, into)?;
+    Ok(())
+}
+
+/// The tracked files modified in the working tree or the index of a repository
+fn modified_files(repo: &Repository) -> anyhow::Result<Vec<String>> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+    let statuses = repo.statuses(Some(&mut options))?;
+    Ok(statuses
+        .iter()
+        .filter(|entry| !entry.status().is_empty())
+        .filter_map(|entry| entry.path().map(String::from))
+        .collect())
+}
+
+/// Fast-forward the current branch of a repository to its remote.
+/// Returns `false` if it is already up to date.
+/// Refuses while tracked files are modified, so that the local edits are never lost.
+pub fn pull_repository_with_progress(dir: &Path) -> anyhow::Result<bool> {
+    let repo = Repository::open(dir)?;
+    let head = repo.head()?;
+    let branch = head.shorthand().ok_or_else(|| anyhow::anyhow!("HEAD is not a branch"))?;
+    let refname = head.name().ok_or_else(|| anyhow::anyhow!("HEAD is not a branch"))?;
+
+    info!("Pulling branch {} of the repository at {:?}", branch, dir);
+    let mut remote = repo.find_remote("origin")?;
+    remote.fetch(&[branch], Some(&mut fetch_options_with_progress()), None)?;
+
+    let fetch_head = repo.find_reference("FETCH_HEAD")?;
+    let fetched = repo.reference_to_annotated_commit(&fetch_head)?;
+    let (analysis, _) = repo.merge_analysis(&[&fetched])?;
+    if analysis.is_up_to_date() {
+        return Ok(false);
+    }
+    if !analysis.is_fast_forward() {
+        return Err(anyhow::anyhow!(
+            "Local changes diverged from the remote, please update it manually"
+        ));
+    }
+
+    let modified = modified_files(&repo)?;
+    if !modified.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Local changes to {} would be overwritten, please commit or revert them first",
+            modified.join(", ")
+        ));
+    }
+
+    // a safe checkout stops at any conflict, like an untracked file in the way, before moving the branch
+    let commit = repo.find_commit(fetched.id())?;
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::default().safe()))?;
+    repo.find_reference(refname)?.set_target(fetched.id(), "fast-forward")?;
+    repo.set_head(refname)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+
+    fn commit_file(repo: &Repository, name: &str, content: &str) {
+        let dir = repo.workdir().unwrap();
+        std::fs::write(dir.join(name), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(name)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("allay", "allay@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, name, &tree, &parents)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_pull_keeps_local_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let origin_dir = dir.path().join("origin");
+        let origin = Repository::init(&origin_dir).unwrap();
+        commit_file(&origin, "theme.toml", "name = \"a\"");
+
+        let local = dir.path().join("local");
+        clone_repository_with_progress(origin_dir.to_str().unwrap(), &local).unwrap();
+        commit_file(&origin, "theme.toml", "name = \"b\"");
+
+        // an edit of a tracked file is refused and kept
+        std::fs::write(local.join("theme.toml"), "name = \"local\"").unwrap();
+        let error = pull_repository_with_progress(&local).unwrap_err();
+        assert!(error.to_string().contains("theme.toml"));
+        let content = std::fs::read_to_string(local.join("theme.toml")).unwrap();
+        assert_eq!(content, "name = \"local\"");
+
+        // once reverted, the update goes on
+        std::fs::write(local.join("theme.toml"), "name = \"a\"").unwrap();
+        assert!(pull_repository_with_progress(&local).unwrap());
+        let content = std::fs::read_to_string(local.join("theme.toml")).unwrap();
+        assert_eq!(content, "name = \"b\"");
+        assert!(!pull_repository_with_progress(&local).unwrap());
+    }
+}
@@ -1,13 +1,23 @@
+use super::registry::Registry;
 use allay_base::{
     config::*,
     data::{AllayData, AllayObject},
     file,
+    plugin_health::{PluginHealth, load_health},
+    plugin_lock::{LockedPlugin, PLUGIN_LOCK_FILE, PluginLock, Verification, sha256_hex},
 };
+use anyhow::anyhow;
+use dialoguer::{Confirm, theme::ColorfulTheme};
 use reqwest::blocking::get;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 pub fn plugin(command: &PluginCommand) -> anyhow::Result<()> {
     match command {
+        PluginCommand::List => list(),
+        PluginCommand::Add(args) => add(args),
+        PluginCommand::Remove(args) => remove(&args.name),
+        PluginCommand::Info(args) => info(&args.name),
         PluginCommand::Update(args) => update(args),
     }
 }
@@ -17,6 +27,376 @@ const PATH: &str = "path";
 const GIT: &str = "git";
 const TAG: &str = "tag";
 const FILE: &str = "file";
+const REGISTRY: &str = "registry";
+const VERSION: &str = "version";
+const SHA256: &str = "sha256";
+
+fn plugins_dir() -> PathBuf {
+    file::workspace(&get_allay_config().plugin_dir)
+}
+
+/// Get the `.wasm` file of a plugin by its name, which must not be a path
+fn plugin_file(name: &str) -> anyhow::Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+        return Err(anyhow!("Invalid plugin name: '{}'", name));
+    }
+    Ok(plugins_dir().join(format!("{}.wasm", name)))
+}
+
+/// The names of the installed plugins, sorted
+fn installed_plugins() -> anyhow::Result<Vec<String>> {
+    let dir = plugins_dir();
+    if !file::dir_exists(&dir) {
+        return Ok(vec![]);
+    }
+    let mut names: Vec<_> = file::read_files(dir)?
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+fn download(url: &str) -> anyhow::Result<Vec<u8>> {
+    let response = get(url)?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(anyhow!("Failed to download {}: {}", url, status));
+    }
+    Ok(response.bytes()?.to_vec())
+}
+
+/// Split `name@requirement` of a plugin in a registry, any version by default
+fn split_requirement(source: &str) -> (&str, &str) {
+    source.split_once('@').unwrap_or((source, "*"))
+}
+
+/// Check the hash of a downloaded or copied plugin against the expected one
+fn check_hash(name: &str, content: &[u8], expected: &str) -> anyhow::Result<()> {
+    let actual = sha256_hex(content);
+    match actual.eq_ignore_ascii_case(expected) {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Plugin '{}' is refused: its SHA-256 {} does not match the expected {}",
+            name,
+            actual,
+            expected
+        )),
+    }
+}
+
+/// Show the hash of a plugin downloaded without an expected one, and ask the user to confirm it
+fn confirm_unchecked(name: &str, content: &[u8]) -> anyhow::Result<()> {
+    println!("   SHA-256 {}", sha256_hex(content));
+    let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "Install plugin '{}' without checking its hash? Pass --sha256 to check it",
+            name
+        ))
+        .default(false)
+        .interact()?;
+    match confirmed {
+        true => Ok(()),
+        false => Err(anyhow!("Plugin '{}' is not installed", name)),
+    }
+}
+
+/// Check a release downloaded again against the lockfile.
+/// A release whose hash changed under the same tag is refused, as its asset was replaced,
+/// unless the hash is pinned in the config or the update is forced.
+fn check_release(
+    name: &str,
+    locked: Option<&LockedPlugin>,
+    source: &str,
+    content: &[u8],
+    pinned: bool,
+    force: bool,
+) -> anyhow::Result<()> {
+    let Some(locked) = locked.filter(|locked| locked.source == source) else {
+        return Ok(());
+    };
+    let actual = sha256_hex(content);
+    if pinned || force || locked.sha256.eq_ignore_ascii_case(&actual) {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "Plugin '{}' is refused: the release {} has the SHA-256 {} instead of the locked {}. \
+         Pin the new hash with `{}` or pass --force to accept it",
+        name,
+        source,
+        actual,
+        locked.sha256,
+        SHA256
+    ))
+}
+
+/// Load a plugin, showing the capabilities granted to it, and return the version it reports
+fn load_plugin(path: &Path) -> anyhow::Result<String> {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "plugin")] {
+            let manifest = allay_plugin::PluginManager::inspect_plugin(path)?;
+            show_grants(&manifest);
+            Ok(manifest.version)
+        } else {
+            let _ = path;
+            Err(anyhow!("Plugins cannot be loaded without the plugin feature"))
+        }
+    }
+}
+
+/// Show the capabilities requested by a plugin, the ones granted by `plugin.toml` and its limits
+#[cfg(feature = "plugin")]
+fn show_grants(manifest: &allay_plugin::PluginManifest) {
+    let granted = allay_plugin::config::granted_capabilities(manifest);
+    let requested = &manifest.capabilities;
+    println!("🔐 Plugin '{}' is granted:", manifest.name);
+    for (kind, granted, requested) in [
+        ("read", &granted.read_dirs, &requested.read_dirs),
+        ("write", &granted.write_dirs, &requested.write_dirs),
+        ("env", &granted.env, &requested.env),
+    ] {
+        match granted == requested {
+            true => println!("   {:<6} {}", kind, grant_list(granted)),
+            false => println!(
+                "   {:<6} {} (overridden in {}, requested: {})",
+                kind,
+                grant_list(granted),
+                PLUGIN_CONFIG_FILE,
+                grant_list(requested)
+            ),
+        }
+    }
+    let limits = allay_plugin::config::plugin_limits(&manifest.name);
+    println!(
+        "   {:<6} {} ms per call, {} MiB of memory",
+        "limits",
+        limits.timeout.as_millis(),
+        limits.memory >> 20
+    );
+}
+
+#[cfg(feature = "plugin")]
+fn grant_list(list: &[String]) -> String {
+    match list.is_empty() {
+        true => "(none)".into(),
+        false => list.join(", "),
+    }
+}
+
+/// Write a plugin into the plugin directory and record it in the lockfile.
+/// A plugin failing to load is not kept.
+fn install(
+    name: &str,
+    content: &[u8],
+    source: String,
+    requirement: Option<String>,
+) -> anyhow::Result<LockedPlugin> {
+    let target = plugin_file(name)?;
+    let previous = std::fs::read(&target).ok();
+    file::write_file(&target, content)?;
+
+    let version = match load_plugin(&target) {
+        Ok(version) => version,
+        Err(e) => {
+            // restore the plugin replaced by an update
+            match previous {
+                Some(previous) => file::write_file(&target, previous)?,
+                None => file::remove_file(&target)?,
+            }
+            return Err(anyhow!("Plugin '{}' cannot be loaded: {}", name, e));
+        }
+    };
+
+    let locked = LockedPlugin {
+        version,
+        sha256: sha256_hex(content),
+        source,
+        requirement,
+    };
+    let mut lock = PluginLock::read_or_default()?;
+    lock.plugins.insert(name.into(), locked.clone());
+    lock.write()?;
+    Ok(locked)
+}
+
+/// Describe the health of a plugin in the last build, like `failing (2 failures)`
+fn health_summary(health: Option<&PluginHealth>) -> String {
+    match health {
+        None => "(not run yet)".into(),
+        Some(health) if health.failures == 0 => health.status().into(),
+        Some(health) => format!("{} ({} failures)", health.status(), health.failures),
+    }
+}
+
+fn list() -> anyhow::Result<()> {
+    let names = installed_plugins()?;
+    let lock = PluginLock::read_or_default()?;
+    if names.is_empty() && lock.plugins.is_empty() {
+        println!("No plugins installed in {:?}", plugins_dir());
+        return Ok(());
+    }
+
+    let health = load_health();
+    for name in names.iter() {
+        let content = std::fs::read(plugin_file(name)?)?;
+        match (lock.plugins.get(name), lock.verify(name, &content)) {
+            (Some(locked), Verification::Verified) => println!(
+                "  {:<20} {:<10} sha256:{}  {}",
+                name,
+                locked.version,
+                &locked.sha256[..12.min(locked.sha256.len())],
+                health_summary(health.get(name))
+            ),
+            (_, Verification::Mismatch { .. }) => {
+                println!("! {:<20} (modified since installed)", name)
+            }
+            _ => println!("? {:<20} (not in {})", name, PLUGIN_LOCK_FILE),
+        }
+    }
+    for name in lock.plugins.keys().filter(|name| !names.contains(name)) {
+        println!("! {:<20} (missing, run `allay plugin update`)", name);
+    }
+    Ok(())
+}
+
+fn add(args: &PluginAddArgs) -> anyhow::Result<()> {
+    let source = args.source.as_str();
+
+    let (default_name, content, source, requirement) = if let Some(registry) = &args.registry {
+        let registry_dir = file::absolute(registry);
+        let registry = Registry::open(&registry_dir)?;
+        let (name, requirement) = split_requirement(source);
+        let entry = registry.resolve(name, requirement)?;
+        println!(
+            "📦 Installing plugin '{}' {} from the registry {}",
+            entry.name,
+            entry.version,
+            registry_dir.display()
+        );
+        if let Some(description) = &entry.description {
+            println!("   {}", description);
+        }
+        let content = std::fs::read(registry.file_of(entry))?;
+        check_hash(&entry.name, &content, &entry.sha256)?;
+        let source = registry_dir.to_string_lossy().to_string();
+        (
+            entry.name.clone(),
+            content,
+            source,
+            Some(requirement.to_string()),
+        )
+    } else if is_url(source) {
+        println!("🌐 Downloading plugin from: {}", source);
+        let last = source.rsplit('/').next().unwrap_or_default();
+        let name = last.trim_end_matches(".wasm").to_string();
+        let content = download(source)?;
+        if args.sha256.is_none() {
+            confirm_unchecked(args.name.as_ref().unwrap_or(&name), &content)?;
+        }
+        (name, content, source.to_string(), None)
+    } else if file::file_exists(source) {
+        println!("📁 Copying plugin from: {}", source);
+        let path = Path::new(source);
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let content = std::fs::read(path)?;
+        let source = file::absolute(path).to_string_lossy().to_string();
+        (name, content, source, None)
+    } else {
+        return Err(anyhow!("Plugin source not found: {}", source));
+    };
+
+    let name = args.name.clone().unwrap_or(default_name);
+    if let Some(expected) = &args.sha256 {
+        check_hash(&name, &content, expected)?;
+    }
+    let target = plugin_file(&name)?;
+    if file::file_exists(&target) {
+        return Err(anyhow!(
+            "Plugin '{}' is already installed at {:?}",
+            name,
+            target
+        ));
+    }
+
+    let locked = install(&name, &content, source, requirement)?;
+    println!(
+        "✅ Plugin '{}' {} installed at {:?}",
+        name, locked.version, target
+    );
+    Ok(())
+}
+
+fn remove(name: &str) -> anyhow::Result<()> {
+    let target = plugin_file(name)?;
+    let mut lock = PluginLock::read_or_default()?;
+    let locked = lock.plugins.remove(name).is_some();
+    if !file::file_exists(&target) && !locked {
+        return Err(anyhow!("Plugin '{}' is not installed", name));
+    }
+
+    if file::file_exists(&target) {
+        file::remove_file(&target)?;
+    }
+    if locked {
+        lock.write()?;
+    }
+    println!("✅ Plugin '{}' removed", name);
+    Ok(())
+}
+
+fn info(name: &str) -> anyhow::Result<()> {
+    let target = plugin_file(name)?;
+    let lock = PluginLock::read_or_default()?;
+    let locked = lock.plugins.get(name);
+    let content = std::fs::read(&target).ok();
+    if content.is_none() && locked.is_none() {
+        return Err(anyhow!("Plugin '{}' is not installed", name));
+    }
+
+    println!("Plugin '{}':", name);
+    println!("  {:<12} {:?}", "file", target);
+    if let Some(locked) = locked {
+        println!("  {:<12} {}", "version", locked.version);
+        println!("  {:<12} {}", "source", locked.source);
+        if let Some(requirement) = &locked.requirement {
+            println!("  {:<12} {}", "requirement", requirement);
+        }
+        println!("  {:<12} {}", "sha256", locked.sha256);
+    }
+    let status = match content {
+        None => "missing".into(),
+        Some(content) => match lock.verify(name, &content) {
+            Verification::Verified => "verified".into(),
+            Verification::Unlocked => format!("not in {}", PLUGIN_LOCK_FILE),
+            Verification::Mismatch { actual, .. } => format!("modified, sha256 {}", actual),
+        },
+    };
+    println!("  {:<12} {}", "status", status);
+    if let Some(health) = load_health().get(name) {
+        println!(
+            "  {:<12} {}, {} calls, {} failures, {} restarts in the last build",
+            "health",
+            health.status(),
+            health.calls,
+            health.failures,
+            health.restarts
+        );
+        if let Some(error) = &health.last_error {
+            println!("  {:<12} {}", "last error", error);
+        }
+    }
+
+    #[cfg(feature = "plugin")]
+    if file::file_exists(&target) {
+        show_grants(&allay_plugin::PluginManager::inspect_plugin(&target)?);
+    }
+    Ok(())
+}
 
 fn update(args: &PluginUpdateArgs) -> anyhow::Result<()> {
     let Some(meta) = get_site_config().get(PLUGIN).cloned() else {
@@ -31,13 +411,13 @@ fn update(args: &PluginUpdateArgs) -> anyhow::Result<()> {
 
     if let Some(name) = &args.name {
         if let Some(info) = meta.get(name) {
-            update_on(name, info)?;
+            update_on(name, info, args.force)?;
         } else {
             eprintln!("No plugin named '{}' found in configuration.", name);
         }
     } else {
         for (name, info) in meta.iter() {
-            update_on(name, info)?;
+            update_on(name, info, args.force)?;
         }
     }
 
@@ -48,42 +428,101 @@ fn get_str<'a>(data: &'a Arc<AllayObject>, key: &str) -> Option<&'a str> {
     data.get(key).and_then(|v| v.as_str().ok())
 }
 
-fn update_on(name: &str, info: &Arc<AllayData>) -> anyhow::Result<()> {
+fn update_on(name: &str, info: &Arc<AllayData>, force: bool) -> anyhow::Result<()> {
     let Ok(info) = info.as_obj() else {
         eprintln!("Plugin '{}' configuration must be an object.", name);
         return Ok(());
     };
 
-    let dir = file::workspace(&get_allay_config().plugin_dir);
-
-    if let Some(path) = get_str(&info, PATH) {
-        println!("Updating plugins in path: {}", path);
-        file::copy(path.into(), dir)?;
+    let mut release = false;
+    let (content, source, requirement) = if let Some(path) = get_str(&info, PATH) {
+        println!("Updating plugin '{}' from path: {}", name, path);
+        let content = std::fs::read(file::workspace(path))?;
+        (content, path.to_string(), None)
+    } else if let Some(registry) = get_str(&info, REGISTRY) {
+        let registry_dir = file::workspace(registry);
+        let requirement = get_str(&info, VERSION).unwrap_or("*");
+        let entry = Registry::open(&registry_dir)?.resolve(name, requirement)?.clone();
+        println!(
+            "Updating plugin '{}' to {} from the registry {}",
+            name, entry.version, registry
+        );
+        let content = std::fs::read(registry_dir.join(&entry.file))?;
+        check_hash(name, &content, &entry.sha256)?;
+        (content, registry.to_string(), Some(requirement.to_string()))
     } else if let Some(git) = get_str(&info, GIT)
         && let Some(tag) = get_str(&info, TAG)
         && let Some(file) = get_str(&info, FILE)
     {
-        let release = format!("{}/releases/download/{}/{}", git, tag, file);
-
-        println!("Downloading plugin '{}' from URL: {}", name, release);
-        let response = get(&release)?;
-        let status = response.status();
-        if status.is_success() {
-            let bytes = response.bytes()?;
-            let plugin_path = dir.join(format!("{}.wasm", name));
-            file::write_file(&plugin_path, &bytes)?;
-            println!(
-                "Plugin '{}' updated successfully at {:?}",
-                name, plugin_path
-            );
-        } else {
-            eprintln!(
-                "Failed to download plugin '{}' from URL: {}. Status: {}",
-                name, release, status
-            );
-        }
+        let url = format!("{}/releases/download/{}/{}", git, tag, file);
+
+        println!("Downloading plugin '{}' from URL: {}", name, url);
+        release = true;
+        (download(&url)?, url, None)
     } else {
-        eprintln!("No plugin source (path or git) found in configuration.");
+        eprintln!("No plugin source (path, registry or git) found in configuration.");
+        return Ok(());
+    };
+
+    // a release can be pinned by its hash, as it may be replaced after being published
+    let pinned = get_str(&info, SHA256);
+    if let Some(expected) = pinned {
+        check_hash(name, &content, expected)?;
+    }
+
+    let lock = PluginLock::read_or_default()?;
+    if release {
+        let locked = lock.plugins.get(name);
+        check_release(name, locked, &source, &content, pinned.is_some(), force)?;
     }
+    if let Some(locked) = lock.plugins.get(name)
+        && locked.sha256 == sha256_hex(&content)
+        && file::file_exists(plugin_file(name)?)
+    {
+        println!("Plugin '{}' is up to date", name);
+        return Ok(());
+    }
+
+    let locked = install(name, &content, source, requirement)?;
+    println!(
+        "Plugin '{}' updated successfully to {} at {:?}",
+        name,
+        locked.version,
+        plugin_file(name)?
+    );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "https://example.com/toc/releases/download/v1/toc.wasm";
+
+    fn locked(content: &[u8]) -> LockedPlugin {
+        LockedPlugin {
+            version: "1.0.0".into(),
+            sha256: sha256_hex(content),
+            source: SOURCE.into(),
+            requirement: None,
+        }
+    }
+
+    #[test]
+    fn test_check_release() {
+        let locked = locked(b"old");
+        assert!(check_release("toc", None, SOURCE, b"new", false, false).is_ok());
+        assert!(check_release("toc", Some(&locked), SOURCE, b"old", false, false).is_ok());
+
+        // a replaced asset under the same tag names both hashes
+        let error = check_release("toc", Some(&locked), SOURCE, b"new", false, false).unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains(&locked.sha256) && message.contains(&sha256_hex(b"new")));
+
+        // accepted when pinned, forced, or from another tag
+        assert!(check_release("toc", Some(&locked), SOURCE, b"new", true, false).is_ok());
+        assert!(check_release("toc", Some(&locked), SOURCE, b"new", false, true).is_ok());
+        let other = SOURCE.replace("v1", "v2");
+        assert!(check_release("toc", Some(&locked), &other, b"new", false, false).is_ok());
+    }
+}
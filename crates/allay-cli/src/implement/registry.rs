@@ -0,0 +1,73 @@
+use allay_base::file;
+use anyhow::anyhow;
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// The index of a local plugin registry, listing every version of the plugins in it
+pub const REGISTRY_INDEX_FILE: &str = "index.toml";
+
+/// A version of a plugin in the registry, like `[[plugins]]` in the index
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegistryEntry {
+    pub name: String,
+    pub version: String,
+    /// the `.wasm` file, relative to the registry directory
+    pub file: String,
+    /// the hex SHA-256 hash of the file, verified when it is installed
+    pub sha256: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RegistryIndex {
+    #[serde(default)]
+    plugins: Vec<RegistryEntry>,
+}
+
+/// A local directory acting as a plugin registry, for installing the plugins offline
+pub struct Registry {
+    dir: PathBuf,
+    index: RegistryIndex,
+}
+
+impl Registry {
+    pub fn open(dir: &Path) -> anyhow::Result<Self> {
+        let index_file = dir.join(REGISTRY_INDEX_FILE);
+        let content = file::read_file_string(&index_file)
+            .map_err(|_| anyhow!("No {} found in {}", REGISTRY_INDEX_FILE, dir.display()))?;
+        let index = toml::from_str(&content)
+            .map_err(|e| anyhow!("Invalid {}: {}", index_file.display(), e))?;
+        Ok(Self {
+            dir: dir.into(),
+            index,
+        })
+    }
+
+    /// Find the newest version of a plugin matching the requirement, like `^0.2`
+    pub fn resolve(&self, name: &str, requirement: &str) -> anyhow::Result<&RegistryEntry> {
+        let req = VersionReq::parse(requirement)?;
+        self.index
+            .plugins
+            .iter()
+            .filter(|entry| entry.name == name)
+            .filter_map(|entry| Some((Version::parse(&entry.version).ok()?, entry)))
+            .filter(|(version, _)| req.matches(version))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, entry)| entry)
+            .ok_or_else(|| {
+                anyhow!(
+                    "No version of plugin '{}' matching {} in the registry {}",
+                    name,
+                    requirement,
+                    self.dir.display()
+                )
+            })
+    }
+
+    /// The path of the file of a plugin version
+    pub fn file_of(&self, entry: &RegistryEntry) -> PathBuf {
+        self.dir.join(&entry.file)
+    }
+}
@@ -0,0 +1,327 @@
+use super::git::{clone_repository_with_progress, is_git_url, pull_repository_with_progress};
+use allay_base::config::validate::{THEME_SCHEMA, validate};
+use allay_base::config::*;
+use allay_base::file;
+#[cfg(feature = "plugin")]
+use allay_plugin::PluginManager;
+use anyhow::anyhow;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub fn theme(command: &ThemeCommand) -> anyhow::Result<()> {
+    match command {
+        ThemeCommand::List => list(),
+        ThemeCommand::Add(args) => add(args),
+        ThemeCommand::Remove(args) => remove(&args.name),
+        ThemeCommand::Use(args) => use_theme(&args.name),
+        ThemeCommand::Update(args) => update(args),
+        ThemeCommand::Params(args) => params(args),
+    }
+}
+
+fn themes_dir() -> PathBuf {
+    file::workspace(&get_allay_config().theme_dir)
+}
+
+/// Get the directory of a theme by its name, which must not be a path
+fn theme_dir(name: &str) -> anyhow::Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+        return Err(anyhow!("Invalid theme name: '{}'", name));
+    }
+    Ok(themes_dir().join(name))
+}
+
+/// Get the directory of an installed theme
+fn installed_theme_dir(name: &str) -> anyhow::Result<PathBuf> {
+    let dir = theme_dir(name)?;
+    match file::dir_exists(&dir) {
+        true => Ok(dir),
+        false => Err(anyhow!("Theme '{}' is not installed", name)),
+    }
+}
+
+/// The names of the installed themes, sorted
+fn installed_themes() -> anyhow::Result<Vec<String>> {
+    let dir = themes_dir();
+    if !file::dir_exists(&dir) {
+        return Ok(vec![]);
+    }
+    let mut names = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir()
+            && let Some(name) = path.file_name()
+        {
+            names.push(name.to_string_lossy().to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// The name of the theme used by the site
+fn current_theme() -> String {
+    get_theme_path()
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Read the config of an installed theme without reporting the problems
+fn installed_theme_config(name: &str) -> Option<ThemeConfig> {
+    let config = file::read_file_string(themes_dir().join(name).join(THEME_CONFIG_FILE));
+    config.ok().and_then(|config| toml::from_str(&config).ok())
+}
+
+/// Read the config of a theme, printing all the problems found in it
+fn read_theme(dir: &Path) -> anyhow::Result<ThemeConfig> {
+    let config_file = dir.join(THEME_CONFIG_FILE);
+    let content = file::read_file_string(&config_file)
+        .map_err(|_| anyhow!("No {} found in {}", THEME_CONFIG_FILE, dir.display()))?;
+
+    let problems = validate(&config_file, &content, &THEME_SCHEMA);
+    for problem in problems.iter() {
+        eprintln!("{problem}");
+    }
+    match problems.iter().filter(|problem| problem.is_error()).count() {
+        0 => Ok(toml::from_str(&content)?),
+        n => Err(anyhow!("{n} error(s) in {}", config_file.display())),
+    }
+}
+
+/// The plugins required by a theme but not installed in the required versions,
+/// like `search ^1.0`
+fn missing_dependencies(theme: &ThemeConfig) -> anyhow::Result<Vec<String>> {
+    let mut required: Vec<_> = theme.dependencies.plugins.iter().collect();
+    if required.is_empty() {
+        return Ok(vec![]);
+    }
+    required.sort();
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "plugin")] {
+            allay_plugin::load_plugins();
+            let plugin_manager = PluginManager::instance();
+            let mut missing = vec![];
+            for (name, version) in required {
+                if !plugin_manager.version_match(name, version)? {
+                    missing.push(format!("{} {}", name, version));
+                }
+            }
+            Ok(missing)
+        } else {
+            eprintln!("⚠️  Plugin dependencies are not supported without the plugin feature");
+            Ok(vec![])
+        }
+    }
+}
+
+/// Warn about the plugins required by a theme but not installed in the required versions
+fn check_dependencies(theme: &ThemeConfig) -> anyhow::Result<()> {
+    for plugin in missing_dependencies(theme)? {
+        eprintln!(
+            "⚠️  Theme '{}' requires plugin {}, which is not installed",
+            theme.meta.name, plugin
+        );
+    }
+    Ok(())
+}
+
+/// Read the config of a fetched theme, which is only installed if it is valid
+/// and all the plugins it requires are installed
+fn check_new_theme(dir: &Path) -> anyhow::Result<ThemeConfig> {
+    let theme = read_theme(dir)?;
+    let missing = missing_dependencies(&theme)?;
+    match missing.is_empty() {
+        true => Ok(theme),
+        false => Err(anyhow!(
+            "Theme '{}' requires plugin(s) {}, please install them first",
+            theme.meta.name,
+            missing.join(", ")
+        )),
+    }
+}
+
+/// Set the theme in the site config
+fn set_site_theme(name: &str) -> anyhow::Result<()> {
+    let config_file = file::workspace(SITE_CONFIG_FILE);
+    let content = file::read_file_string(&config_file)
+        .map_err(|_| anyhow!("No {} found in the site", SITE_CONFIG_FILE))?;
+    file::write_file(&config_file, set_config_string(&content, "theme", name)?)?;
+    Ok(())
+}
+
+fn list() -> anyhow::Result<()> {
+    let names = installed_themes()?;
+    if names.is_empty() {
+        println!("No themes installed in {:?}", themes_dir());
+        return Ok(());
+    }
+
+    let current = current_theme();
+    for name in names {
+        let mark = if name == current { "*" } else { " " };
+        match installed_theme_config(&name) {
+            Some(theme) => println!(
+                "{} {:<20} {:<10} by {}",
+                mark, name, theme.meta.version, theme.meta.author
+            ),
+            None => println!("{} {:<20} (invalid {})", mark, name, THEME_CONFIG_FILE),
+        }
+    }
+    Ok(())
+}
+
+fn add(args: &ThemeAddArgs) -> anyhow::Result<()> {
+    let source = args.source.trim_end_matches(['/', '\\']);
+    let name = match &args.name {
+        Some(name) => name.clone(),
+        None => {
+            let last = source.rsplit(['/', '\\', ':']).next().unwrap_or_default();
+            last.trim_end_matches(".git").to_string()
+        }
+    };
+    let target = theme_dir(&name)?;
+    if file::dir_exists(&target) {
+        return Err(anyhow!(
+            "Theme '{}' is already installed at {:?}",
+            name,
+            target
+        ));
+    }
+
+    if is_git_url(source) {
+        println!("🌐 Cloning theme from: {}", source);
+        clone_repository_with_progress(source, &target)?;
+    } else if file::dir_exists(source) {
+        println!("📁 Copying theme from: {}", source);
+        // the history is not copied, so a local theme is never updated from git
+        for path in file::read_dir_all_files(source)? {
+            let relative = path.strip_prefix(source)?;
+            if !relative.starts_with(".git") {
+                file::copy(path.as_path(), &target.join(relative))?;
+            }
+        }
+    } else {
+        return Err(anyhow!("Theme source not found: {}", source));
+    }
+
+    // an invalid theme or one missing its plugins is not kept
+    let theme = match check_new_theme(&target) {
+        Ok(theme) => theme,
+        Err(e) => {
+            file::remove_dir_recursively(&target)?;
+            return Err(e);
+        }
+    };
+    println!(
+        "✅ Theme '{}' {} installed at {:?}",
+        name, theme.meta.version, target
+    );
+
+    if args.use_theme {
+        set_site_theme(&name)?;
+        println!("✅ The site now uses theme '{}'", name);
+    }
+    Ok(())
+}
+
+fn remove(name: &str) -> anyhow::Result<()> {
+    let dir = installed_theme_dir(name)?;
+    if name == current_theme() {
+        return Err(anyhow!(
+            "Theme '{}' is used by the site, please use another theme first",
+            name
+        ));
+    }
+    // the parent themes are still needed by the themes extending them
+    for other in installed_themes()? {
+        let theme = installed_theme_config(&other);
+        if theme.is_some_and(|theme| theme.meta.extends.as_deref() == Some(name)) {
+            return Err(anyhow!("Theme '{}' is extended by theme '{}'", name, other));
+        }
+    }
+    file::remove_dir_recursively(dir)?;
+    println!("✅ Theme '{}' removed", name);
+    Ok(())
+}
+
+fn use_theme(name: &str) -> anyhow::Result<()> {
+    let dir = installed_theme_dir(name)?;
+    let theme = read_theme(&dir)?;
+    check_dependencies(&theme)?;
+    set_site_theme(name)?;
+    println!("✅ The site now uses theme '{}'", name);
+    Ok(())
+}
+
+fn update(args: &ThemeUpdateArgs) -> anyhow::Result<()> {
+    let names = match &args.name {
+        Some(name) => vec![name.clone()],
+        None => installed_themes()?,
+    };
+
+    for name in names {
+        let dir = installed_theme_dir(&name)?;
+        if !dir.join(".git").exists() {
+            println!("⚠️  Theme '{}' is not installed from git, skipping", name);
+            continue;
+        }
+
+        println!("🌐 Updating theme '{}'", name);
+        if pull_repository_with_progress(&dir)? {
+            let theme = read_theme(&dir)?;
+            println!("✅ Theme '{}' updated to {}", name, theme.meta.version);
+            check_dependencies(&theme)?;
+        } else {
+            println!("✅ Theme '{}' is up to date", name);
+        }
+    }
+    Ok(())
+}
+
+fn params(args: &ThemeParamsArgs) -> anyhow::Result<()> {
+    let name = args.name.clone().unwrap_or_else(current_theme);
+    installed_theme_dir(&name)?;
+
+    // the params of the parent themes are inherited, so the chain is read from the theme up
+    let chain = read_theme_chain(&PathBuf::from(&get_allay_config().theme_dir).join(&name));
+    let defaults = merge_theme_params(&chain);
+    let mut schemas = BTreeMap::new();
+    for layer in chain.iter().rev() {
+        schemas.extend(layer.config.params_schema.clone());
+    }
+
+    let parents: Vec<_> = chain
+        .iter()
+        .skip(1)
+        .map(|layer| layer.path.file_name().unwrap_or_default().to_string_lossy())
+        .collect();
+    match parents.is_empty() {
+        true => println!("Params of theme '{}':", name),
+        false => println!(
+            "Params of theme '{}' (extends {}):",
+            name,
+            parents.join(" -> ")
+        ),
+    }
+    let mut keys: Vec<_> = schemas.keys().chain(defaults.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    if keys.is_empty() {
+        println!("  (none)");
+    }
+    for key in keys {
+        let schema = schemas.get(key);
+        let kind = schema.map_or("any", |schema| schema.kind.as_str());
+        let default = match defaults.get(key) {
+            Some(value) => serde_json::to_string(value.as_ref())?,
+            None => "(none)".into(),
+        };
+        let description = schema.map_or("", |schema| schema.description.as_str());
+        let line = format!("  {:<20} {:<8} {:<20} {}", key, kind, default, description);
+        println!("{}", line.trim_end());
+    }
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use allay_base::config::validate::{ConfigProblem, validate_site_configs, validate_theme_configs};
 use allay_base::config::{CLICommand, get_allay_config, get_cli_config, get_env};
 use allay_base::file;
 use anyhow::Ok;
@@ -8,6 +9,7 @@ use tracing_subscriber::fmt::format::{FmtSpan, format};
 /// Initialization routine for the CLI application
 pub fn initialize() -> anyhow::Result<()> {
     init_root()?;
+    validate_configs()?;
     init_logger()?;
 
     let span = info_span!("initialize");
@@ -19,8 +21,10 @@ pub fn initialize() -> anyhow::Result<()> {
 /// Initialize the root directory based on CLI arguments
 fn init_root() -> anyhow::Result<()> {
     let config = get_cli_config();
-    if let CLICommand::New(args) = &config.command {
-        let dir = &args.dir;
+    // `allay new content` creates a content in the current site instead
+    if let CLICommand::New(args) = &config.command
+        && let Some(dir) = &args.dir
+    {
         if file::dirty_dir(dir)? {
             return Err(anyhow::anyhow!("Directory is not empty"));
         }
@@ -33,6 +37,27 @@ fn init_root() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Validate the config files before anything reads them, printing all the problems found.
+/// The theme config is only validated with a valid site config, as it is located by it,
+/// and not for the theme commands, which validate the themes they install or use.
+fn validate_configs() -> anyhow::Result<()> {
+    let report = |problems: &[ConfigProblem]| {
+        for problem in problems {
+            eprintln!("{problem}");
+        }
+        problems.iter().filter(|problem| problem.is_error()).count()
+    };
+
+    let mut errors = report(&validate_site_configs());
+    if errors == 0 && !matches!(get_cli_config().command, CLICommand::Theme(_)) {
+        errors += report(&validate_theme_configs());
+    }
+    match errors {
+        0 => Ok(()),
+        n => Err(anyhow::anyhow!("{n} error(s) in the config files")),
+    }
+}
+
 /// Initialize the global logger
 fn init_logger() -> anyhow::Result<()> {
     let log_dir = &get_allay_config().log_dir;
@@ -1,7 +1,7 @@
 mod implement;
 pub mod initialize;
 
-use allay_base::config::{AllayCLI, CLICommand, get_cli_config};
+use allay_base::config::{AllayCLI, CLICommand, NewArgs, NewCommand, get_cli_config};
 use implement::*;
 
 pub fn execute() -> anyhow::Result<()> {
@@ -11,10 +11,15 @@ pub fn execute() -> anyhow::Result<()> {
 
 pub fn execute_cli(cli: &AllayCLI) -> anyhow::Result<()> {
     match &cli.command {
+        CLICommand::New(NewArgs {
+            command: Some(NewCommand::Content(args)),
+            ..
+        }) => new_content(args),
         CLICommand::New(args) => new(args),
         CLICommand::Init(args) => init(args),
         CLICommand::Build(args) => build(args),
         CLICommand::Serve(args) => serve(args),
         CLICommand::Plugin(command) => plugin(command),
+        CLICommand::Theme(command) => theme(command),
     }
 }
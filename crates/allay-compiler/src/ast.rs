@@ -154,6 +154,7 @@ pub struct Unary {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Primary {
+    Call(Call),
     Field(Field),
     TopLevel(TopLevel),
     Number(u32),
@@ -183,3 +184,9 @@ pub struct Field {
     pub top_level: Option<TopLevel>,
     pub parts: Vec<GetField>, // at least one part
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    pub name: String,
+    pub arguments: Vec<Expression>,
+}
@@ -1,11 +1,14 @@
 use crate::ast::Template;
 use crate::extract::{convert_to_html, get_meta_and_content};
 use crate::interpret::{Interpretable, Interpreter, PageScope};
-use crate::{CompileOutput, CompileResult};
+use crate::{CompileOutput, CompileResult, magic};
+use allay_base::config::get_language_config;
 use allay_base::lock;
+use allay_base::report::BuildStats;
 use allay_base::template::TemplateKind;
 #[cfg(feature = "plugin")]
 use allay_plugin::PluginManager;
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex, Weak};
 
@@ -19,8 +22,12 @@ pub struct Page {
     path: PathBuf,
     /// the interpret scope of the page
     scope: PageScope,
+    /// the language of the page, inherited by its subpages
+    lang: Option<String>,
     /// the output tokens
     output: Vec<Token>,
+    /// the content files referred to by the internal references in the markdown
+    references: HashSet<PathBuf>,
 
     /// if the page is cachable
     cachable: bool,
@@ -45,7 +52,9 @@ impl Page {
             parent: None,
             path,
             scope: PageScope::new(),
+            lang: None,
             output: Vec::new(),
+            references: HashSet::new(),
 
             cachable: true,
             ready: false,
@@ -62,6 +71,18 @@ impl Page {
         &mut self.scope
     }
 
+    /// The language of the page, or the default language of the site if not specified
+    pub fn lang(&self) -> &str {
+        self.lang.as_deref().unwrap_or(&get_language_config().default_language)
+    }
+
+    /// Update the language of the page from the `lang` key in its scope, if any
+    fn update_lang(&mut self) {
+        if let Some(lang) = self.scope.get_owned(magic::LANG).and_then(|lang| lang.as_str().ok()) {
+            self.lang = Some(lang.into());
+        }
+    }
+
     pub fn set_cachable(&mut self, cachable: bool) {
         self.cachable = cachable;
         self.ready = false;
@@ -77,6 +98,11 @@ impl Page {
         }
     }
 
+    /// The content files referred to by the internal references in the markdown
+    pub fn references(&self) -> &HashSet<PathBuf> {
+        &self.references
+    }
+
     /// Check if the page's output is changed and needs recompiling
     pub fn changed(&self) -> bool {
         !self.cachable || self.dirty
@@ -88,7 +114,9 @@ impl Page {
             parent: None,
             path: self.path.clone(),
             scope: self.scope.clone(),
+            lang: self.lang.clone(),
             output: Vec::new(),
+            references: HashSet::new(),
 
             cachable: self.cachable,
             ready: false,
@@ -144,6 +172,7 @@ impl TokenInserter for Arc<Mutex<Page>> {
         let page = Page {
             parent: Some(Arc::downgrade(self)),
             scope,
+            lang: lock!(self).lang.clone(),
             ..page
         };
         let page = page.into();
@@ -159,27 +188,35 @@ pub trait Compiled {
     fn compile_on(&self, node: &Template, interpreter: &mut Interpreter) -> CompileResult<String>;
     /// Utility function to generate the result string after the compiling
     fn gen_result_str(&self, interpreter: &mut Interpreter) -> CompileResult<String>;
+    /// Utility function to concatenate the output as it is, without converting markdown
+    fn gen_raw_str(&self, interpreter: &mut Interpreter) -> CompileResult<String>;
 }
 
 #[cfg(feature = "plugin")]
 fn after_compile(html: String, ty: TemplateKind) -> String {
     let plugin_manager = PluginManager::instance();
+    let stats = BuildStats::get_instance();
     plugin_manager
-        .plugins()
+        .named_plugins()
         .iter()
-        .filter_map(|plugin| plugin.lock().ok())
-        .fold(html, |html, mut plugin| {
-            plugin.after_compile(html, ty.clone())
+        .filter_map(|(name, plugin)| plugin.lock().ok().map(|plugin| (name, plugin)))
+        .fold(html, |html, (name, mut plugin)| {
+            stats.time_hook(name, "after_compile", || {
+                plugin.after_compile(html, ty.clone())
+            })
         })
 }
 
 impl Compiled for Arc<Mutex<Page>> {
     // The optimized version for compiling a page (by caching the result)
     fn compile(&self, interpreter: &mut Interpreter) -> CompileResult<CompileOutput> {
+        let stats = BuildStats::get_instance();
+        let start = stats.start();
         let mut page = lock!(self);
         let meta = if !page.cachable || !page.ready {
             let (meta, template) = get_meta_and_content(&page.path)?;
             page.scope.merge_data(meta.clone());
+            page.update_lang();
             page.output.clear();
             drop(page);
             self.compile_on(&template, interpreter)?;
@@ -192,6 +229,7 @@ impl Compiled for Arc<Mutex<Page>> {
         };
 
         let page = lock!(self);
+        stats.record_cache("pages", !page.dirty);
         if !page.dirty {
             // use cached result
             return Ok(page.cache.clone());
@@ -203,8 +241,18 @@ impl Compiled for Arc<Mutex<Page>> {
         #[cfg(feature = "plugin")]
         let html = after_compile(html, TemplateKind::from_filename(&lock!(self).path));
 
-        let output = CompileOutput { html, meta };
         let mut page = lock!(self);
+        // the markdown contents are timed as the pages
+        if !TemplateKind::from_filename(&page.path).is_md() {
+            stats.record_template_time(&page.path, start);
+        }
+        let lang = page.lang().into();
+        let output = CompileOutput {
+            html,
+            meta,
+            lang,
+            ..Default::default()
+        };
         page.dirty = false;
         page.cache = output.clone();
         Ok(output)
@@ -227,11 +275,26 @@ impl Compiled for Arc<Mutex<Page>> {
         }
         drop(page);
 
-        let kind = { TemplateKind::from_filename(&lock!(self).path) };
-        if kind.is_md() {
-            result = convert_to_html(&result);
+        let path = lock!(self).path.clone();
+        if TemplateKind::from_filename(&path).is_md() {
+            let mut references = HashSet::new();
+            let html = convert_to_html(&result, &path, &mut references);
+            lock!(self).references = references;
+            result = html?;
         }
 
         Ok(result)
     }
+
+    fn gen_raw_str(&self, interpreter: &mut Interpreter) -> CompileResult<String> {
+        let mut result = String::new();
+        let page = lock!(self);
+        for token in page.output.iter() {
+            match token {
+                Token::Text(t) => result.push_str(t),
+                Token::Page(p) => result.push_str(&p.compile(interpreter)?.html),
+            }
+        }
+        Ok(result)
+    }
 }
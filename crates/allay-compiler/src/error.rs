@@ -1,6 +1,7 @@
 //! Error types for the Allay compiler.
 
 use allay_base::{data::AllayDataError, file::FileError};
+use std::path::PathBuf;
 use thiserror::Error;
 
 /// Errors that can occur during parsing.
@@ -46,6 +47,18 @@ pub enum InterpretError {
     /// Include file error
     #[error("Error in trying to include: {0}")]
     IncludeError(#[from] Box<CompileError>),
+
+    /// Function not found when calling
+    #[error("Function not found: {0}")]
+    FunctionNotFound(String),
+
+    /// Wrong arguments passed to a function
+    #[error("Invalid arguments for function {0}: {1}")]
+    InvalidArguments(String, String),
+
+    /// Internal reference error
+    #[error("{0}")]
+    ReferenceError(#[from] ReferenceError),
 }
 
 /// The result type for interpreter.
@@ -53,6 +66,23 @@ pub enum InterpretError {
 /// This is a type alias for [`Result<T, InterpretError>`]
 pub type InterpretResult<T> = Result<T, InterpretError>;
 
+/// Errors that can occur when resolving an internal reference like `@/posts/foo.md#section`.
+#[derive(Debug, Error)]
+pub enum ReferenceError {
+    /// The referenced content file does not exist
+    #[error("Broken reference {0}: no such content file")]
+    PageNotFound(String),
+
+    /// The referenced content file has no such anchor
+    #[error("Broken reference {0}: no heading with anchor #{1}")]
+    AnchorNotFound(String, String),
+}
+
+/// The result type for resolving references.
+///
+/// This is a type alias for [`Result<T, ReferenceError>`].
+pub type ReferenceResult<T> = Result<T, ReferenceError>;
+
 /// Errors that can occur during compilation (parsing + interpretation).
 #[derive(Debug, Error)]
 pub enum CompileError {
@@ -68,6 +98,15 @@ pub enum CompileError {
     /// Interpretation error
     #[error("{0}")]
     InterpretError(#[from] InterpretError),
+    /// Internal reference error in markdown links
+    #[error("{0}")]
+    ReferenceError(#[from] ReferenceError),
+    /// Invalid output format in `outputs`, which must be a file extension like `json`
+    #[error("Invalid output format: {0:?}")]
+    InvalidOutputFormat(String),
+    /// The template of an output format does not exist in the theme
+    #[error("Template of output format {0} not found: {1}")]
+    OutputTemplateNotFound(String, PathBuf),
 }
 
 /// The result type for compilation.
@@ -1,16 +1,22 @@
 mod cache;
+mod image;
 mod matching;
 mod process;
+mod reference;
 
 use crate::CompileResult;
 use crate::ast::Template;
 use allay_base::data::AllayObject;
+use allay_base::report::BuildStats;
 use allay_base::sitemap::SiteMap;
 use allay_base::{file, read, write};
 use cache::FileCacher;
 pub use matching::*;
-use pulldown_cmark::{Options, Parser, html};
-use std::path::Path;
+use pulldown_cmark::{Event, Options, Parser, Tag, html};
+pub use reference::resolve_reference;
+use reference::{REFERENCE_PREFIX, assign_heading_ids, reference_source};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, LazyLock, RwLock};
 
 static AST_CACHER: LazyLock<RwLock<FileCacher<Arc<Template>>>> =
@@ -22,9 +28,11 @@ pub fn get_meta_and_content<P: AsRef<Path>>(
 ) -> CompileResult<(AllayObject, Arc<Template>)> {
     let last_modified = file::last_modified(&source)?;
 
-    if let Some(ast) = read!(AST_CACHER).get(&source, last_modified) {
+    let cached = read!(AST_CACHER).get(&source, last_modified).cloned();
+    BuildStats::get_instance().record_cache("templates", cached.is_some());
+    if let Some(ast) = cached {
         let meta = get_meta(source)?;
-        return Ok((meta, ast.clone()));
+        return Ok((meta, ast));
     }
 
     let (meta, template) = match_meta_and_content(&source)?;
@@ -39,23 +47,48 @@ pub fn get_meta<P: AsRef<Path>>(source: P) -> CompileResult<AllayObject> {
     let meta = map.urlset.get(&source.as_ref().to_path_buf());
     match meta {
         Some(entry) => Ok(entry.meta().as_ref().clone()),
-        None => match_meta(source),
+        None => match_template_meta(source),
     }
 }
 
-/// Convert markdown text to HTML string using pulldown-cmark
-pub fn convert_to_html(text: &str) -> String {
-    let mut html_output = String::new();
+/// The markdown extensions enabled in Allay
+fn markdown_options() -> Options {
     let mut options = Options::empty();
     options.insert(Options::ENABLE_TABLES);
     options.insert(Options::ENABLE_FOOTNOTES);
     options.insert(Options::ENABLE_STRIKETHROUGH);
     options.insert(Options::ENABLE_GFM);
+    options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
     options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
     options.insert(Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS);
+    options
+}
 
-    let parser = Parser::new_ext(text, options);
-    html::push_html(&mut html_output, parser);
+/// Convert markdown text of a source file to HTML string using pulldown-cmark.
+/// Headings get anchors from their text, internal references in links like
+/// `[text](@/posts/foo.md#section)` are resolved to the current URL of the target,
+/// and local images get responsive variants if image processing is enabled.
+/// The referred content files are added to `references`, including a broken one.
+pub fn convert_to_html<P: AsRef<Path>>(
+    text: &str,
+    source: P,
+    references: &mut HashSet<PathBuf>,
+) -> CompileResult<String> {
+    let mut events: Vec<_> = Parser::new_ext(text, markdown_options()).collect();
+    assign_heading_ids(&mut events);
 
-    html_output
+    for event in events.iter_mut() {
+        if let Event::Start(Tag::Link { dest_url, .. }) = event
+            && let Some(reference) = dest_url.strip_prefix(REFERENCE_PREFIX)
+        {
+            references.insert(reference_source(reference));
+            *dest_url = resolve_reference(reference)?.into();
+        }
+    }
+
+    let events = image::responsive_images(events, source.as_ref());
+
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, events.into_iter());
+    Ok(html_output)
 }
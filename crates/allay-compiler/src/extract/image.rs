@@ -0,0 +1,167 @@
+use allay_base::config::{get_allay_config, get_image_config};
+use allay_base::file;
+use allay_base::image::{ImageProcessor, ImageSrcset, ImageVariant};
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+use std::path::{Component, Path, PathBuf};
+
+/// Resolve the URL of an image in a markdown file to its path relative to the publish directory.
+/// Relative URLs are relative to the directory of the markdown file, like the page bundles.
+/// Returns `None` for external images.
+fn image_path(url: &str, source: &Path) -> Option<PathBuf> {
+    if url.contains("://") || url.starts_with("//") || url.starts_with("data:") {
+        return None;
+    }
+    let url = url.split(['?', '#']).next()?;
+    let path = match url.strip_prefix('/') {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let content_dir = file::workspace(&get_allay_config().content_dir);
+            source.parent()?.strip_prefix(content_dir).ok()?.join(url)
+        }
+    };
+
+    // resolve `.` and `..` in the path
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            _ => {}
+        }
+    }
+    Some(resolved)
+}
+
+/// Get the URL of a variant from the URL of its original image, keeping the directory part
+fn variant_url(url: &str, variant: &ImageVariant) -> String {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let name = variant.path.file_name().unwrap_or_default().to_string_lossy();
+    match url.rsplit_once('/') {
+        Some((dir, _)) => format!("{}/{}", dir, name),
+        None => name.to_string(),
+    }
+}
+
+fn escape_attr(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn render_image(url: &str, alt: &str, title: &str, srcset: &ImageSrcset) -> String {
+    let set = |variants: &[ImageVariant]| {
+        let set: Vec<_> = variants
+            .iter()
+            .map(|variant| format!("{} {}w", variant_url(url, variant), variant.width))
+            .collect();
+        escape_attr(&set.join(", "))
+    };
+    let sizes = format!("(max-width: {0}px) 100vw, {0}px", srcset.width);
+    let title = match title {
+        "" => String::new(),
+        title => format!(r#" title="{}""#, escape_attr(title)),
+    };
+
+    let img = format!(
+        r#"<img src="{}" srcset="{}" sizes="{}" alt="{}"{}>"#,
+        escape_attr(url),
+        set(&srcset.original),
+        sizes,
+        escape_attr(alt),
+        title
+    );
+    if srcset.webp.is_empty() {
+        img
+    } else {
+        format!(
+            r#"<picture><source type="image/webp" srcset="{}" sizes="{}">{}</picture>"#,
+            set(&srcset.webp),
+            sizes,
+            img
+        )
+    }
+}
+
+/// Replace the local images in markdown with responsive ones having `srcset` of their variants,
+/// if image processing is enabled. Images failed to process are kept as they are.
+pub fn responsive_images<'a>(events: Vec<Event<'a>>, source: &Path) -> Vec<Event<'a>> {
+    if get_image_config().widths.is_empty() {
+        return events;
+    }
+
+    let mut result = Vec::with_capacity(events.len());
+    let mut events = events.into_iter();
+    while let Some(event) = events.next() {
+        let Event::Start(Tag::Image {
+            dest_url, title, ..
+        }) = &event
+        else {
+            result.push(event);
+            continue;
+        };
+        let srcset = image_path(dest_url, source)
+            .filter(|path| ImageProcessor::is_image(path))
+            .and_then(|path| ImageProcessor::get_instance().srcset(path).ok());
+        let Some(srcset) = srcset else {
+            result.push(event);
+            continue;
+        };
+
+        // the alt text is the plain text until the end of the image
+        let mut alt = String::new();
+        for event in events.by_ref() {
+            match event {
+                Event::End(TagEnd::Image) => break,
+                Event::Text(text) | Event::Code(text) => alt.push_str(&text),
+                _ => {}
+            }
+        }
+        let html = render_image(dest_url, &alt, title, &srcset);
+        result.push(Event::InlineHtml(CowStr::from(html)));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(path: &str, width: u32, webp: bool) -> ImageVariant {
+        ImageVariant {
+            path: path.into(),
+            width,
+            webp,
+        }
+    }
+
+    #[test]
+    fn test_render_image() {
+        let srcset = ImageSrcset {
+            width: 1600,
+            original: vec![
+                variant("posts/a.480w.jpg", 480, false),
+                variant("posts/a.jpg", 1600, false),
+            ],
+            webp: vec![
+                variant("posts/a.480w.webp", 480, true),
+                variant("posts/a.1600w.webp", 1600, true),
+            ],
+        };
+        assert_eq!(
+            render_image("img/a.jpg", "A \"cat\"", "", &srcset),
+            r#"<picture><source type="image/webp" srcset="img/a.480w.webp 480w, img/a.1600w.webp 1600w" sizes="(max-width: 1600px) 100vw, 1600px"><img src="img/a.jpg" srcset="img/a.480w.jpg 480w, img/a.jpg 1600w" sizes="(max-width: 1600px) 100vw, 1600px" alt="A &quot;cat&quot;"></picture>"#
+        );
+
+        let srcset = ImageSrcset {
+            webp: vec![],
+            ..srcset
+        };
+        assert_eq!(
+            render_image("a.jpg", "", "Cat", &srcset),
+            r#"<img src="a.jpg" srcset="a.480w.jpg 480w, a.jpg 1600w" sizes="(max-width: 1600px) 100vw, 1600px" alt="" title="Cat">"#
+        );
+    }
+}
@@ -11,15 +11,12 @@ use regex::Regex;
 use std::path::Path;
 use std::sync::{Arc, LazyLock};
 
-/// Match and extract metadata and content from a source file
+/// Match and extract metadata and content from a source file.
+/// Templates of other extensions are also accepted, like `page.json` of an output format.
 pub fn match_meta_and_content<P: AsRef<Path>>(
     source: P,
 ) -> CompileResult<(AllayObject, Arc<Template>)> {
     let kind = TemplateKind::from_filename(&source);
-    if let TemplateKind::Other(e) = kind {
-        return Err(CompileError::FileTypeNotSupported(e));
-    }
-
     let content = file::read_file_string(&source)?;
 
     #[cfg(feature = "plugin")]
@@ -34,11 +31,16 @@ pub fn match_meta_and_content<P: AsRef<Path>>(
 
 /// Match and extract metadata from a source file only using regex
 pub fn match_meta<P: AsRef<Path>>(source: P) -> CompileResult<AllayObject> {
-    let kind = TemplateKind::from_filename(&source);
-    if let TemplateKind::Other(e) = kind {
+    if let TemplateKind::Other(e) = TemplateKind::from_filename(&source) {
         return Err(CompileError::FileTypeNotSupported(e));
     }
+    match_template_meta(source)
+}
 
+/// Match and extract metadata from a template of any extension only using regex,
+/// like `page.json` of an output format
+pub(crate) fn match_template_meta<P: AsRef<Path>>(source: P) -> CompileResult<AllayObject> {
+    let kind = TemplateKind::from_filename(&source);
     let content = file::read_file_string(&source)?;
 
     #[cfg(feature = "plugin")]
@@ -1,6 +1,8 @@
 use crate::magic;
-use allay_base::config::get_allay_config;
-use allay_base::data::{AllayData, AllayObject};
+use allay_base::config::{
+    get_allay_config, get_language_config, get_output_config, get_permalink_config,
+};
+use allay_base::data::{AllayList, AllayObject};
 use allay_base::file;
 use allay_base::template::TemplateKind;
 use allay_base::url::AllayUrlPath;
@@ -12,27 +14,55 @@ use std::sync::Arc;
 /// A hook to allow plugins to process the content before compilation
 #[cfg(feature = "plugin")]
 pub fn before_compile(content: String, kind: TemplateKind) -> String {
-    let plugin_manager = PluginManager::instance();
-    plugin_manager.plugins().iter().fold(content, |content, plugin| {
-        use allay_base::lock;
+    use allay_base::lock;
+    use allay_base::report::BuildStats;
 
+    let plugin_manager = PluginManager::instance();
+    let stats = BuildStats::get_instance();
+    plugin_manager.named_plugins().iter().fold(content, |content, (name, plugin)| {
         let mut plugin = lock!(plugin);
-        plugin.before_compile(content, kind.clone())
+        stats.time_hook(name, "before_compile", || {
+            plugin.before_compile(content, kind.clone())
+        })
     })
 }
 
 /// A preprocessing step to add default metadata fields
 pub fn meta_preprocess<P: AsRef<Path>>(source: P, mut meta: AllayObject) -> AllayObject {
-    meta.entry(magic::URL.into()).or_insert_with(|| {
-        // Add the `url` field to the metadata
-        let entry =
-            match source.as_ref().strip_prefix(file::workspace(&get_allay_config().content_dir)) {
-                Ok(e) => e.with_extension(TemplateKind::Html.extension()),
-                // ignore if the file is not under the content directory
-                Err(_) => return Arc::new(AllayData::default()),
-            };
-        let url = AllayUrlPath::from(entry).as_ref().to_string_lossy().to_string();
-        Arc::new(url.into())
-    });
+    let content_dir = file::workspace(&get_allay_config().content_dir);
+    // ignore if the file is not under the content directory
+    let entry = source.as_ref().strip_prefix(content_dir).ok();
+
+    // the templates have no URL, which would hide the URL of the article they wrap
+    if let Some(entry) = entry
+        && !meta.contains_key(magic::URL)
+    {
+        // Add the `url` field to the metadata from the permalink pattern of the section or the path,
+        // prefixed by the language if not the default one
+        let languages = get_language_config();
+        let (lang, path) = languages.split_language(entry);
+        let url = match get_permalink_config().resolve(&path, &meta) {
+            Some(link) if lang != languages.default_language => format!("{}/{}", lang, link),
+            Some(link) => link,
+            None => {
+                let entry = languages.localize(entry);
+                let entry = entry.with_extension(TemplateKind::Html.extension());
+                AllayUrlPath::from(entry).as_ref().to_string_lossy().to_string()
+            }
+        };
+        meta.insert(magic::URL.into(), Arc::new(url.into()));
+    }
+
+    if let Some(entry) = entry {
+        // Add the `lang` field from the language suffix of the file name
+        meta.entry(magic::LANG.into())
+            .or_insert_with(|| Arc::new(get_language_config().split_language(entry).0.into()));
+
+        // Resolve the `outputs` field from the front matter or the section, always with HTML
+        let (_, path) = get_language_config().split_language(entry);
+        let formats = get_output_config().formats_of(&path, &meta);
+        let formats: AllayList = formats.into_iter().map(|f| Arc::new(f.into())).collect();
+        meta.insert(magic::OUTPUTS.into(), Arc::new(formats.into()));
+    }
     meta
 }
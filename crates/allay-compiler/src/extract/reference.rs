@@ -0,0 +1,140 @@
+use super::cache::FileCacher;
+use super::markdown_options;
+use crate::{ReferenceError, ReferenceResult, magic};
+use allay_base::config::get_allay_config;
+use allay_base::sitemap::SiteMap;
+use allay_base::url::slugify;
+use allay_base::{file, read, write};
+use pulldown_cmark::{CowStr, Event, Parser, Tag, TagEnd};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// The prefix of an internal reference in markdown links, like `[text](@/posts/foo.md)`
+pub const REFERENCE_PREFIX: &str = "@/";
+
+static ANCHOR_CACHER: LazyLock<RwLock<FileCacher<Arc<HashSet<String>>>>> =
+    LazyLock::new(|| RwLock::new(FileCacher::new()));
+
+/// Give every heading without an explicit id an anchor generated from its text.
+/// Repeated anchors are suffixed with `-1`, `-2`, ... in order.
+pub fn assign_heading_ids(events: &mut [Event]) {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+
+    for i in 0..events.len() {
+        let Event::Start(Tag::Heading { id, .. }) = &events[i] else {
+            continue;
+        };
+        if let Some(id) = id {
+            seen.entry(id.to_string()).or_default();
+            continue;
+        }
+
+        let text: String = events[i + 1..]
+            .iter()
+            .take_while(|e| !matches!(e, Event::End(TagEnd::Heading(_))))
+            .filter_map(|e| match e {
+                Event::Text(t) | Event::Code(t) => Some(t.as_ref()),
+                _ => None,
+            })
+            .collect();
+
+        let slug = slugify(&text);
+        let count = seen.entry(slug.clone()).or_default();
+        let anchor = if *count == 0 {
+            slug
+        } else {
+            format!("{}-{}", slug, count)
+        };
+        *count += 1;
+
+        if let Event::Start(Tag::Heading { id, .. }) = &mut events[i] {
+            *id = Some(CowStr::from(anchor));
+        }
+    }
+}
+
+/// Get all heading anchors of a markdown source file, using cache if available
+fn anchors_of(source: &Path) -> Option<Arc<HashSet<String>>> {
+    let last_modified = file::last_modified(source).ok()?;
+    if let Some(anchors) = read!(ANCHOR_CACHER).get(source, last_modified) {
+        return Some(anchors.clone());
+    }
+
+    let content = file::read_file_string(source).ok()?;
+    let mut events: Vec<_> = Parser::new_ext(&content, markdown_options()).collect();
+    assign_heading_ids(&mut events);
+    let anchors: HashSet<_> = events
+        .into_iter()
+        .filter_map(|e| match e {
+            Event::Start(Tag::Heading { id: Some(id), .. }) => Some(id.to_string()),
+            _ => None,
+        })
+        .collect();
+
+    let anchors = Arc::new(anchors);
+    write!(ANCHOR_CACHER).insert(source, last_modified, anchors.clone());
+    Some(anchors)
+}
+
+/// The content file of an internal reference like `posts/foo.md#section` in the workspace
+pub fn reference_source(reference: &str) -> PathBuf {
+    let path = reference.split('#').next().unwrap_or_default();
+    file::workspace(&get_allay_config().content_dir).join(path.trim_start_matches('/'))
+}
+
+/// Resolve an internal reference like `posts/foo.md#section` (relative to the content directory)
+/// to the current URL of the target page.
+/// It fails if the target file or the anchor in it does not exist.
+pub fn resolve_reference(reference: &str) -> ReferenceResult<String> {
+    let display = format!("{}{}", REFERENCE_PREFIX, reference);
+    let (path, anchor) = match reference.split_once('#') {
+        Some((path, anchor)) => (path, Some(anchor).filter(|a| !a.is_empty())),
+        None => (reference, None),
+    };
+    let path = Path::new(path.trim_start_matches('/'));
+
+    let url = {
+        let map = SiteMap::read();
+        let entry = map.urlset.get(path).ok_or(ReferenceError::PageNotFound(display.clone()))?;
+        entry.meta().get(magic::URL).and_then(|url| url.as_str().ok().map(String::from))
+    }
+    .ok_or(ReferenceError::PageNotFound(display.clone()))?;
+
+    if let Some(anchor) = anchor
+        && !anchors_of(&reference_source(reference)).is_some_and(|a| a.contains(anchor))
+    {
+        return Err(ReferenceError::AnchorNotFound(display, anchor.into()));
+    }
+
+    // make the URL absolute, so that it works on every page and the base url can be added
+    let url = if url.starts_with('/') || url.contains("://") {
+        url
+    } else {
+        format!("/{}", url)
+    };
+    Ok(match anchor {
+        Some(anchor) => format!("{}#{}", url, anchor),
+        None => url,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heading_ids() {
+        let source = "# Intro\n\n## Usage\n\n## Usage\n\n### Custom {#my-id}\n";
+        let mut events: Vec<_> = Parser::new_ext(source, markdown_options()).collect();
+        assign_heading_ids(&mut events);
+        let ids: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Start(Tag::Heading { id: Some(id), .. }) => Some(id.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids, vec!["intro", "usage", "usage-1", "my-id"]);
+    }
+}
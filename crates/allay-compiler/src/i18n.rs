@@ -0,0 +1,127 @@
+//! Multilingual support, including the translations of pages and the translation string tables.
+
+use allay_base::config::{get_allay_config, get_language_config, layered_dirs};
+use allay_base::data::{AllayData, AllayList, AllayObject};
+use allay_base::file;
+use allay_base::log::NoPanicUnwrap;
+use allay_base::sitemap::SiteMap;
+use allay_base::{read, write};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicU32};
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// The other language versions of all the pages in the site map.
+/// Files like `foo.en.md` and `foo.zh.md` are grouped as translations of each other.
+/// It is rebuilt lazily whenever the site map version changes.
+#[derive(Debug)]
+pub struct TranslationIndex {
+    cache_version: AtomicU32,
+    data: RwLock<HashMap<PathBuf, Arc<AllayData>>>,
+}
+
+impl TranslationIndex {
+    pub fn get_instance() -> &'static TranslationIndex {
+        static INSTANCE: OnceLock<TranslationIndex> = OnceLock::new();
+        let instance = INSTANCE.get_or_init(|| TranslationIndex {
+            cache_version: AtomicU32::new(u32::MAX),
+            data: RwLock::new(HashMap::new()),
+        });
+        instance.update();
+        instance
+    }
+
+    /// Get the other language versions of a source file in the workspace, ordered by language.
+    /// Returns an empty list if the file is not a content page.
+    pub fn translations_of<P: AsRef<Path>>(&self, source: P) -> Arc<AllayData> {
+        let content_dir = file::workspace(&get_allay_config().content_dir);
+        source
+            .as_ref()
+            .strip_prefix(content_dir)
+            .ok()
+            .and_then(|path| read!(self.data).get(path).cloned())
+            .unwrap_or_else(|| Arc::new(AllayList::new().into()))
+    }
+
+    fn update(&self) {
+        let version = SiteMap::read().version();
+        if self.cache_version.load(atomic::Ordering::SeqCst) == version {
+            return;
+        }
+
+        self.cache_version.store(version, atomic::Ordering::SeqCst);
+
+        // group the pages by the path without language suffix
+        let mut groups: HashMap<PathBuf, Vec<(String, PathBuf, Arc<AllayObject>)>> = HashMap::new();
+        for (path, entry) in SiteMap::read().urlset.iter() {
+            let (lang, key) = get_language_config().split_language(path);
+            groups.entry(key).or_default().push((lang, path.clone(), entry.meta()));
+        }
+
+        let mut data = HashMap::new();
+        for mut group in groups.into_values() {
+            group.sort_by(|(l1, _, _), (l2, _, _)| l1.cmp(l2));
+            for (_, path, _) in group.iter() {
+                let others = group
+                    .iter()
+                    .filter(|(_, other, _)| other != path)
+                    .map(|(_, _, meta)| Arc::new(AllayData::Object(meta.clone())))
+                    .collect::<AllayList>();
+                data.insert(path.clone(), Arc::new(others.into()));
+            }
+        }
+        *write!(self.data) = data;
+    }
+}
+
+/// The translation string tables of all languages, like `i18n/zh.toml`.
+/// The tables of the site override the ones of the theme key by key,
+/// which override the ones of the parent themes.
+fn string_tables() -> &'static HashMap<String, AllayObject> {
+    static INSTANCE: OnceLock<HashMap<String, AllayObject>> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| {
+        let mut dirs: Vec<_> = layered_dirs(&get_allay_config().i18n_dir)
+            .into_iter()
+            .map(file::workspace)
+            .collect();
+        dirs.reverse();
+
+        let mut tables: HashMap<String, AllayObject> = HashMap::new();
+        for dir in dirs.iter().filter(|dir| file::dir_exists(dir)) {
+            for path in file::read_files(dir).unwrap_or_default() {
+                let Some(lang) = path.file_stem().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                if path.extension().is_none_or(|ext| ext != "toml") {
+                    continue;
+                }
+                let table = file::read_file_string(&path)
+                    .map_err(Into::into)
+                    .and_then(|content| AllayData::from_toml(&content))
+                    .expect_on(|e| format!("Invalid translation table {:?}: {e}", path));
+                AllayData::merge_object(tables.entry(lang.into()).or_default(), table);
+            }
+        }
+        tables
+    })
+}
+
+/// Look up a dotted key like `nav.home` in a string table
+fn lookup(table: &AllayObject, key: &str) -> Option<Arc<AllayData>> {
+    let (first, rest) = key.split_once('.').map_or((key, None), |(f, r)| (f, Some(r)));
+    let data = table.get(first)?;
+    match rest {
+        None => Some(data.clone()),
+        Some(rest) => lookup(data.as_obj().ok()?.as_ref(), rest),
+    }
+}
+
+/// Translate a key of the string tables into the given language.
+/// Falls back to the default language if the key is missing in the given one.
+pub fn translate(lang: &str, key: &str) -> Option<Arc<AllayData>> {
+    let tables = string_tables();
+    [lang, &get_language_config().default_language]
+        .into_iter()
+        .find_map(|lang| tables.get(lang).and_then(|table| lookup(table, key)))
+}
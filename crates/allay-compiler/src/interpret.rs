@@ -1,3 +1,4 @@
+mod function;
 mod interpreter;
 mod scope;
 mod traits;
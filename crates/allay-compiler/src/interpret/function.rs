@@ -0,0 +1,185 @@
+//! Built-in functions that can be called in templates, like `{: ref("posts/foo.md") :}`
+
+use crate::env::Page;
+use crate::extract::resolve_reference;
+use crate::i18n::translate;
+use crate::{InterpretError, InterpretResult};
+use allay_base::asset::AssetManifest;
+use allay_base::data::AllayData;
+use allay_base::image::ImageProcessor;
+use allay_base::lock;
+use std::sync::{Arc, Mutex};
+
+/// Call a built-in function with the evaluated arguments
+pub(crate) fn call_function(
+    name: &str,
+    args: &[Arc<AllayData>],
+    page: &Arc<Mutex<Page>>,
+) -> InterpretResult<Arc<AllayData>> {
+    match name {
+        "ref" => reference(args, page),
+        "t" => translation(args, page),
+        "asset" => asset(args, page),
+        "image" => image(args),
+        "json" => json(args),
+        "plain" => plain(args),
+        _ => Err(InterpretError::FunctionNotFound(name.into())),
+    }
+}
+
+/// Get the argument at `index` as a string
+fn str_arg<'a>(func: &str, args: &'a [Arc<AllayData>], index: usize) -> InterpretResult<&'a str> {
+    args.get(index)
+        .ok_or_else(|| invalid_args(func, format!("missing argument {}", index + 1)))?
+        .as_str()
+        .map_err(|_| invalid_args(func, format!("argument {} must be a string", index + 1)))
+}
+
+fn invalid_args(func: &str, reason: String) -> InterpretError {
+    InterpretError::InvalidArguments(func.into(), reason)
+}
+
+/// `ref(path)`: the current URL of a content file like `posts/foo.md#section`,
+/// failing if the file or the anchor does not exist
+fn reference(args: &[Arc<AllayData>], page: &Arc<Mutex<Page>>) -> InterpretResult<Arc<AllayData>> {
+    let path = str_arg("ref", args, 0)?;
+    let path = path.strip_prefix("@/").unwrap_or(path);
+
+    // the target URL may change with the site map
+    lock!(page).set_cachable(false);
+    Ok(Arc::new(resolve_reference(path)?.into()))
+}
+
+/// `t(key)`: the translation of a key like `nav.home` in the language of the current page,
+/// or the key itself if it is not translated
+fn translation(
+    args: &[Arc<AllayData>],
+    page: &Arc<Mutex<Page>>,
+) -> InterpretResult<Arc<AllayData>> {
+    let key = str_arg("t", args, 0)?;
+    let lang = lock!(page).lang().to_string();
+    Ok(translate(&lang, key).unwrap_or_else(|| Arc::new(key.into())))
+}
+
+/// `asset(path)`: the URL of a static asset like `css/style.css`,
+/// which is fingerprinted like `/css/style.3f2a1c9e.css` if enabled by the `[assets]` config
+fn asset(args: &[Arc<AllayData>], page: &Arc<Mutex<Page>>) -> InterpretResult<Arc<AllayData>> {
+    let path = str_arg("asset", args, 0)?.trim_start_matches('/');
+    if !AssetManifest::should_fingerprint(path) {
+        return Ok(Arc::new(format!("/{}", path).into()));
+    }
+
+    // the fingerprint changes with the content of the asset
+    lock!(page).set_cachable(false);
+    let asset = AssetManifest::get_instance()
+        .fingerprint(path)
+        .map_err(|e| invalid_args("asset", e.to_string()))?;
+    Ok(Arc::new(
+        format!("/{}", asset.path.to_string_lossy()).into(),
+    ))
+}
+
+/// `image(path, width, format)`: the URL of a processed variant of an image like `photos/a.jpg`,
+/// resized to the width (not larger than the original) and converted if the format is `"webp"`
+fn image(args: &[Arc<AllayData>]) -> InterpretResult<Arc<AllayData>> {
+    let path = str_arg("image", args, 0)?.trim_start_matches('/');
+    let width = match args.get(1).map(AsRef::as_ref) {
+        None | Some(AllayData::Null) => None,
+        Some(AllayData::Int(width)) if *width > 0 => Some(*width as u32),
+        Some(_) => {
+            return Err(invalid_args(
+                "image",
+                "argument 2 must be a positive integer".into(),
+            ));
+        }
+    };
+    let webp = match args.get(2) {
+        None => false,
+        Some(_) => match str_arg("image", args, 2)? {
+            "webp" => true,
+            "original" => false,
+            format => {
+                return Err(invalid_args(
+                    "image",
+                    format!("unknown format `{}`", format),
+                ));
+            }
+        },
+    };
+
+    let variant = ImageProcessor::get_instance()
+        .variant(path, width, webp)
+        .map_err(|e| invalid_args("image", e.to_string()))?;
+    Ok(Arc::new(
+        format!("/{}", variant.path.to_string_lossy()).into(),
+    ))
+}
+
+/// `json(value)`: the value encoded as JSON, like a quoted and escaped string,
+/// for the templates of JSON output formats
+fn json(args: &[Arc<AllayData>]) -> InterpretResult<Arc<AllayData>> {
+    let value = args.first().ok_or_else(|| invalid_args("json", "missing argument 1".into()))?;
+    let text =
+        serde_json::to_string(value.as_ref()).map_err(|e| invalid_args("json", e.to_string()))?;
+    Ok(Arc::new(text.into()))
+}
+
+/// `plain(html)`: the text of an HTML string without tags, like the `content` of an article,
+/// for the templates of plain text output formats
+fn plain(args: &[Arc<AllayData>]) -> InterpretResult<Arc<AllayData>> {
+    let html = str_arg("plain", args, 0)?;
+    Ok(Arc::new(strip_html(html).into()))
+}
+
+/// Remove the tags in HTML and decode the common entities, keeping the line breaks.
+/// The contents of `<script>` and `<style>` are removed with their tags.
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = rest[1..end].to_ascii_lowercase();
+        rest = &rest[end + 1..];
+        let name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or_default();
+        if matches!(name, "script" | "style") && !tag.ends_with('/') {
+            // skip to the closing tag, or the end if it is missing
+            let close = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&close) {
+                Some(pos) => rest[pos..].find('>').map_or("", |end| &rest[pos + end + 1..]),
+                None => "",
+            };
+        }
+    }
+    text.push_str(rest);
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    text.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(
+            strip_html("<h1 id=\"a\">Tom &amp; Jerry</h1>\n<p>1 &lt; 2, <code>x</code></p>\n"),
+            "Tom & Jerry\n1 < 2, x"
+        );
+        assert_eq!(
+            strip_html(
+                "<style>p { color: red; }</style><p>a</p><SCRIPT src=\"x\">if (1 < 2) {}</Script>b"
+            ),
+            "ab"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::env::{Compiled, Page, TokenInserter};
+use crate::interpret::function::call_function;
 use crate::interpret::scope::PageScope;
 use crate::interpret::traits::{DataProvider, Variable};
 use crate::interpret::var::{LocalVar, PagesVar, SiteVar};
@@ -7,6 +8,7 @@ use crate::{ast::*, magic};
 use allay_base::data::AllayData;
 use allay_base::data::{AllayDataError, AllayList};
 use allay_base::lock;
+use allay_base::report::BuildStats;
 use itertools::Itertools;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
@@ -18,16 +20,17 @@ fn converse_error(err: String) -> InterpretError {
 /// The global Allay interpreter context
 #[derive(Debug)]
 pub struct Interpreter {
-    include_dir: PathBuf,
-    shortcode_dir: PathBuf,
+    include_dirs: Vec<PathBuf>,
+    shortcode_dirs: Vec<PathBuf>,
 }
 
 impl Interpreter {
-    /// Create a new interpreter with the given include and shortcode directories
-    pub fn new(include_dir: PathBuf, shortcode_dir: PathBuf) -> Interpreter {
+    /// Create a new interpreter with the given include and shortcode directories,
+    /// where the files in the former directories override the ones in the latter
+    pub fn new(include_dirs: Vec<PathBuf>, shortcode_dirs: Vec<PathBuf>) -> Interpreter {
         Interpreter {
-            include_dir,
-            shortcode_dir,
+            include_dirs,
+            shortcode_dirs,
         }
     }
 }
@@ -172,11 +175,13 @@ mod file_finder {
         None
     }
 
-    pub fn try_find_file<P: AsRef<Path>>(path: P) -> InterpretResult<PathBuf> {
-        let path = path.as_ref();
-        find_file(path).ok_or(InterpretError::IncludePathNotFound(
-            path.to_path_buf().to_string_lossy().to_string(),
-        ))
+    /// Find a file in the first directory having it
+    pub fn try_find_file<P: AsRef<Path>>(dirs: &[PathBuf], name: P) -> InterpretResult<PathBuf> {
+        let name = name.as_ref();
+        dirs.iter().find_map(|dir| find_file(dir.join(name))).ok_or_else(|| {
+            let path = dirs.first().map_or(name.to_path_buf(), |dir| dir.join(name));
+            InterpretError::IncludePathNotFound(path.to_string_lossy().to_string())
+        })
     }
 }
 
@@ -197,7 +202,8 @@ impl Interpretable for IncludeCommand {
         };
 
         let scope = PageScope::new_from(inherited.as_obj()?, params);
-        let path = file_finder::try_find_file(ctx.include_dir.join(&self.path))?;
+        let path = file_finder::try_find_file(&ctx.include_dirs, &self.path)?;
+        BuildStats::get_instance().record_include(&self.path);
         page.insert_subpage(path, scope);
         Ok(())
     }
@@ -222,7 +228,8 @@ impl Interpretable for SingleShortcode {
         let inherited = lock!(page).scope().cur_scope().create_this().get_data();
 
         let scope = PageScope::new_from(inherited.as_obj()?, params);
-        let path = file_finder::try_find_file(ctx.shortcode_dir.join(&self.name))?;
+        let path = file_finder::try_find_file(&ctx.shortcode_dirs, &self.name)?;
+        BuildStats::get_instance().record_shortcode(&self.name);
         page.insert_subpage(path, scope);
 
         Ok(())
@@ -249,7 +256,8 @@ impl Interpretable for BlockShortcode {
             .map_err(|e| InterpretError::IncludeError(Box::new(e)))?;
         scope.add_key(magic::INNER.into(), Arc::new(AllayData::from(inner)));
 
-        let path = file_finder::try_find_file(ctx.shortcode_dir.join(&self.name))?;
+        let path = file_finder::try_find_file(&ctx.shortcode_dirs, &self.name)?;
+        BuildStats::get_instance().record_shortcode(&self.name);
         page.insert_subpage(path, scope);
         Ok(())
     }
@@ -490,6 +498,7 @@ impl Interpretable for Primary {
             Primary::Boolean(bool) => Ok(Arc::new((*bool).into())),
             Primary::String(str) => Ok(Arc::new(str.clone().into())),
             Primary::Expression(exp) => exp.interpret(ctx, page),
+            Primary::Call(call) => call.interpret(ctx, page),
             Primary::Field(field) => field.interpret(ctx, page),
             Primary::TopLevel(top) => top.interpret(ctx, page),
             Primary::Null => Ok(Arc::new(AllayData::Null)),
@@ -497,6 +506,20 @@ impl Interpretable for Primary {
     }
 }
 
+impl Interpretable for Call {
+    type Output = Arc<AllayData>;
+
+    fn interpret(
+        &self,
+        ctx: &mut Interpreter,
+        page: &Arc<Mutex<Page>>,
+    ) -> InterpretResult<Self::Output> {
+        let args: AllayList =
+            self.arguments.iter().map(|e| e.interpret(ctx, page)).try_collect()?;
+        call_function(&self.name, &args, page)
+    }
+}
+
 impl Interpretable for Field {
     type Output = Arc<AllayData>;
 
@@ -506,6 +529,7 @@ impl Interpretable for Field {
         page: &Arc<Mutex<Page>>,
     ) -> InterpretResult<Self::Output> {
         let mut page = lock!(page);
+        let pages;
         let scope = page.scope();
         let var: &dyn Variable = match self.top_level.as_ref().unwrap_or(&TopLevel::This) {
             TopLevel::This => &scope.cur_scope().create_this(),
@@ -513,7 +537,8 @@ impl Interpretable for Field {
             TopLevel::Param => scope.get_param(),
             TopLevel::Pages => {
                 page.set_cachable(false);
-                PagesVar::get_instance()
+                pages = PagesVar::get_instance().of_language(page.lang());
+                &pages
             }
             TopLevel::Variable(id) => {
                 scope.get_local(id).ok_or(InterpretError::VariableNotFound(id.clone()))?
@@ -532,6 +557,7 @@ impl Interpretable for TopLevel {
         page: &Arc<Mutex<Page>>,
     ) -> InterpretResult<Self::Output> {
         let mut page = lock!(page);
+        let pages;
         let scope = page.scope();
         let var: &dyn Variable = match self {
             TopLevel::This => &scope.cur_scope().create_this(),
@@ -539,7 +565,8 @@ impl Interpretable for TopLevel {
             TopLevel::Param => scope.get_param(),
             TopLevel::Pages => {
                 page.set_cachable(false);
-                PagesVar::get_instance()
+                pages = PagesVar::get_instance().of_language(page.lang());
+                &pages
             }
             TopLevel::Variable(id) => {
                 scope.get_local(id).ok_or(InterpretError::VariableNotFound(id.clone()))?
@@ -43,6 +43,10 @@ impl PageScope {
         self.merged.take();
     }
 
+    pub fn get_owned(&self, key: &str) -> Option<&Arc<AllayData>> {
+        self.owned.get(key)
+    }
+
     pub fn merge_data(&mut self, other: AllayObject) {
         other.into_iter().for_each(|(k, v)| {
             Arc::make_mut(&mut self.owned).insert(k, v);
@@ -1,17 +1,20 @@
 use crate::ast::GetField;
 use crate::interpret::traits::{DataProvider, Variable};
 use crate::{InterpretResult, magic};
-use allay_base::config::get_site_config;
-use allay_base::data::{AllayData, AllayList};
+use allay_base::config::{get_env, get_language_config, get_site_config, get_theme_params};
+use allay_base::data::{AllayData, AllayList, AllayObject};
 #[cfg(feature = "plugin")]
 use allay_base::lock;
 use allay_base::log::NoPanicUnwrap;
+#[cfg(feature = "plugin")]
+use allay_base::report::BuildStats;
 use allay_base::sitemap::{SiteMap, UrlEntry};
 use allay_base::{read, write};
 #[cfg(feature = "plugin")]
-use allay_plugin::PluginManager;
+use allay_plugin::{Plugin, PluginManager};
 #[cfg(feature = "plugin")]
-use std::process::exit;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::sync::atomic::{self, AtomicU32};
 use std::sync::{Arc, OnceLock, RwLock};
 
@@ -25,16 +28,40 @@ impl SiteVar {
     pub fn get_instance() -> &'static SiteVar {
         static INSTANCE: OnceLock<SiteVar> = OnceLock::new();
         INSTANCE.get_or_init(|| {
-            let mut data = get_site_config()
-                .get("params")
-                .map(|params| {
-                    params.as_obj().expect_("Site params should be an obj").as_ref().clone()
-                })
-                .unwrap_or_default();
+            let config = get_site_config();
+            // the site params override the default ones of the themes key by key
+            let mut params = get_theme_params().clone();
+            if let Some(site_params) = config.get(magic::PARAMS) {
+                let site_params = site_params.as_obj().expect_("Site params should be an obj");
+                AllayData::merge_object(&mut params, site_params.as_ref().clone());
+            }
+
+            // the params are also kept at the top level as before, over the site config
+            let mut data: AllayObject =
+                config.iter().map(|(key, value)| (key.clone(), value.clone())).collect();
+            data.extend(params.clone());
+            data.insert(magic::PARAMS.into(), Arc::new(params.into()));
 
             let base_url = SiteMap::read().base_url.clone();
             data.insert(magic::BASE_URL.into(), Arc::new(base_url.into()));
 
+            let languages = get_language_config();
+            let default_language = languages.default_language.clone();
+            data.insert(
+                magic::DEFAULT_LANGUAGE.into(),
+                Arc::new(default_language.into()),
+            );
+            let languages = languages
+                .languages
+                .iter()
+                .map(|(code, lang)| {
+                    let name = lang.name.clone().unwrap_or(code.clone());
+                    (code.clone(), Arc::new(name.into()))
+                })
+                .collect::<AllayObject>();
+            data.insert(magic::LANGUAGES.into(), Arc::new(languages.into()));
+            data.insert(magic::ENVIRONMENT.into(), Arc::new(get_env().name().into()));
+
             let data = Arc::new(data.into());
             SiteVar { data }
         })
@@ -49,10 +76,12 @@ impl DataProvider for SiteVar {
 
 impl Variable for SiteVar {}
 
+/// The global pages variable, listing all the visible pages grouped by languages
 #[derive(Debug)]
 pub struct PagesVar {
     cache_version: AtomicU32,
     data: RwLock<Arc<AllayData>>,
+    by_language: RwLock<HashMap<String, Arc<AllayData>>>,
 }
 
 impl PagesVar {
@@ -61,39 +90,85 @@ impl PagesVar {
         let instance = INSTANCE.get_or_init(|| PagesVar {
             cache_version: AtomicU32::new(u32::MAX),
             data: RwLock::new(Arc::new(AllayList::new().into())),
+            by_language: RwLock::new(HashMap::new()),
         });
         instance.update();
         instance
     }
 
+    /// Get the visible pages in the given language, keeping the order of all pages
+    pub fn of_language(&self, lang: &str) -> LocalVar {
+        let data = read!(self.by_language).get(lang).cloned();
+        LocalVar::create(data.unwrap_or_else(|| Arc::new(AllayList::new().into())))
+    }
+
+    /// Group the pages by their `lang` field, pages without it are in the default language
+    fn group_by_language(data: &AllayData) -> HashMap<String, Arc<AllayData>> {
+        let default = &get_language_config().default_language;
+        let mut groups: HashMap<String, AllayList> = HashMap::new();
+        for page in data.as_list().unwrap_or_default().iter() {
+            let lang = page
+                .as_obj()
+                .ok()
+                .and_then(|meta| {
+                    meta.get(magic::LANG).and_then(|l| l.as_str().ok()).map(String::from)
+                })
+                .unwrap_or(default.clone());
+            groups.entry(lang).or_default().push(page.clone());
+        }
+        groups.into_iter().map(|(lang, pages)| (lang, Arc::new(pages.into()))).collect()
+    }
+
+    /// Sort the pages by the sort plugin, keeping their order if the plugin fails
     #[cfg(feature = "plugin")]
     fn sort_page_var(data: AllayData) -> AllayData {
-        let plugin_manager = PluginManager::instance();
-        let plugins = plugin_manager.plugins();
-        let mut enabled_plugin =
-            plugins.iter().filter(|plugin| lock!(plugin).sort_enabled().unwrap_or(false));
+        Self::sort_pages(data, PluginManager::instance().named_plugins())
+    }
 
-        let Some(plugin) = enabled_plugin.next().cloned() else {
+    /// Sort the pages by the first of the plugins enabling the sort
+    #[cfg(feature = "plugin")]
+    fn sort_pages(data: AllayData, plugins: Vec<(String, Plugin)>) -> AllayData {
+        let stats = BuildStats::get_instance();
+        let mut enabled_plugin = plugins.iter().filter(|(name, plugin)| {
+            stats.time_hook(name, "sort_enabled", || {
+                lock!(plugin).sort_enabled().unwrap_or(false)
+            })
+        });
+
+        let Some((name, plugin)) = enabled_plugin.next().cloned() else {
             return data;
         };
 
-        if enabled_plugin.next().is_some() {
-            eprintln!("Error: multiple sort plugins enabled, only one is allowed");
-            exit(1);
+        if let Some((other, _)) = enabled_plugin.next() {
+            eprintln!(
+                "⚠️  Multiple sort plugins enabled, only '{}' is used and '{}' is ignored",
+                name, other
+            );
         }
 
-        if let AllayData::List(list) = data {
-            let mut list: Vec<_> = list
-                .iter()
-                .map(|item| (item.clone(), serde_json::to_string(item.as_ref()).unwrap()))
-                .collect();
-            list.sort_by(|(_, json1), (_, json2)| {
-                lock!(plugin).get_sort_order(json1, json2).unwrap()
-            });
-            list.into_iter().map(|(item, _)| item).collect::<AllayList>().into()
-        } else {
-            eprintln!("Error: sort plugin enabled but data is not a list");
-            exit(1);
+        let AllayData::List(list) = &data else {
+            eprintln!(
+                "⚠️  Sort plugin '{}' enabled but the pages are not a list",
+                name
+            );
+            return data;
+        };
+        let list: Vec<_> = list
+            .iter()
+            .map(|item| (item.clone(), serde_json::to_string(item.as_ref()).unwrap()))
+            .collect();
+        let sorted = stats.time_hook(&name, "get_sort_order", || {
+            let mut plugin = lock!(plugin);
+            try_merge_sort(list, &mut |(_, json1), (_, json2)| {
+                plugin.get_sort_order(json1, json2)
+            })
+        });
+        match sorted {
+            Ok(sorted) => sorted.into_iter().map(|(item, _)| item).collect::<AllayList>().into(),
+            Err(error) => {
+                eprintln!("⚠️  {}, keeping the original order of the pages", error);
+                data
+            }
         }
     }
 
@@ -121,6 +196,7 @@ impl PagesVar {
         #[cfg(feature = "plugin")]
         let data = Self::sort_page_var(data);
 
+        *write!(self.by_language) = Self::group_by_language(&data);
         *write!(self.data) = Arc::new(data);
     }
 }
@@ -133,6 +209,37 @@ impl DataProvider for PagesVar {
 
 impl Variable for PagesVar {}
 
+/// Sort stably by a comparison which may fail, stopping at the first error.
+/// Unlike `slice::sort_by`, it never panics on a comparison which is not a total order,
+/// as a plugin may answer inconsistently before failing.
+#[cfg(feature = "plugin")]
+fn try_merge_sort<T, E>(
+    mut items: Vec<T>,
+    compare: &mut impl FnMut(&T, &T) -> Result<Ordering, E>,
+) -> Result<Vec<T>, E> {
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+    let right = items.split_off(items.len() / 2);
+    let left = try_merge_sort(items, compare)?;
+    let right = try_merge_sort(right, compare)?;
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        // take from the left on ties to keep the sort stable
+        let next = match compare(b, a)? {
+            Ordering::Less => right.next(),
+            _ => left.next(),
+        };
+        merged.extend(next);
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
 /// The special variable `this`, which points to the current scope data
 #[derive(Clone)]
 pub struct ThisVar<'a> {
@@ -270,4 +377,67 @@ mod tests {
         ));
         assert_eq!(local.create_this().get_data().as_list().unwrap().len(), 2);
     }
+
+    #[cfg(feature = "plugin")]
+    fn sort_titles(titles: &[&str], sorts: u32) -> Vec<String> {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = PluginManager::default();
+        let path = allay_plugin_host::testing::faulty_plugin_after(dir.path(), sorts);
+        manager.register_plugin(&path, dir.path()).unwrap();
+
+        let pages: AllayData = titles
+            .iter()
+            .map(|title| {
+                Arc::new(AllayData::from(AllayObject::from([(
+                    "title".into(),
+                    Arc::new(AllayData::from(*title)),
+                )])))
+            })
+            .collect::<AllayList>()
+            .into();
+        let sorted = PagesVar::sort_pages(pages, manager.named_plugins());
+        sorted
+            .as_list()
+            .unwrap()
+            .iter()
+            .map(|page| page.as_obj().unwrap()["title"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[cfg(feature = "plugin")]
+    #[test]
+    fn test_failed_sort() {
+        // the faulty plugin sorts the pages by their lengths, until it fails
+        let titles = ["ccc", "a", "eeeee", "bb", "dddd"];
+        assert_eq!(
+            sort_titles(&titles, u32::MAX),
+            ["a", "bb", "ccc", "dddd", "eeeee"]
+        );
+        assert_eq!(sort_titles(&titles, 0), titles);
+        // failing after a few comparisons, with some of the pages already moved
+        assert_eq!(sort_titles(&titles, 3), titles);
+    }
+
+    #[cfg(feature = "plugin")]
+    #[test]
+    fn test_try_merge_sort() {
+        let mut compare = |a: &(i32, char), b: &(i32, char)| Ok::<_, ()>(a.0.cmp(&b.0));
+        let items = vec![(3, 'a'), (1, 'b'), (3, 'c'), (2, 'd'), (1, 'e')];
+        assert_eq!(
+            try_merge_sort(items.clone(), &mut compare).unwrap(),
+            [(1, 'b'), (1, 'e'), (2, 'd'), (3, 'a'), (3, 'c')]
+        );
+
+        // an inconsistent comparison never panics, and the first error stops the sort
+        let mut calls = 0;
+        let mut failing = |_: &(i32, char), _: &(i32, char)| {
+            calls += 1;
+            match calls {
+                1 | 2 => Ok(Ordering::Less),
+                3 => Ok(Ordering::Greater),
+                _ => Err(calls),
+            }
+        };
+        assert_eq!(try_merge_sort(items, &mut failing), Err(4));
+    }
 }
@@ -4,19 +4,24 @@ mod ast;
 mod env;
 mod error;
 mod extract;
+mod i18n;
 mod interpret;
 mod misc;
 mod parse;
+mod related;
 
-use allay_base::config::{get_allay_config, get_theme_config, get_theme_path};
+use allay_base::config::{get_allay_config, layered_dirs, template_dirs};
 use allay_base::data::AllayObject;
+use allay_base::report::BuildStats;
 use allay_base::{file, lock};
 use env::{Compiled, Page};
 pub use error::*;
 pub use extract::{get_meta, match_meta};
+use i18n::TranslationIndex;
 use interpret::Interpreter;
+use related::RelatedIndex;
 use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::path::{Path, PathBuf};
@@ -39,6 +44,22 @@ mod magic {
     pub const HIDDEN: &str = "hidden";
     /// base url for the site
     pub const BASE_URL: &str = "base_url";
+    /// the params of the site, over the default ones of the theme
+    pub const PARAMS: &str = "params";
+    /// the ranked list of related pages of an article
+    pub const RELATED: &str = "related";
+    /// the language of the page (auto-generated from the file name if not specified)
+    pub const LANG: &str = "lang";
+    /// the other language versions of an article
+    pub const TRANSLATIONS: &str = "translations";
+    /// the code of the default language of the site
+    pub const DEFAULT_LANGUAGE: &str = "default_language";
+    /// the languages of the site
+    pub const LANGUAGES: &str = "languages";
+    /// the environment of the build, `production` or `development` (from `ALLAY_ENV`)
+    pub const ENVIRONMENT: &str = "environment";
+    /// the output formats of an article, like `["html", "json"]` (default from the section config)
+    pub const OUTPUTS: &str = "outputs";
 }
 
 #[derive(Debug, Clone, Default)]
@@ -47,6 +68,10 @@ pub struct CompileOutput {
     pub html: String,
     /// The metadata extracted from the source file
     pub meta: AllayObject,
+    /// The language of the page
+    pub lang: String,
+    /// The other output formats of an article rendered by their templates, like `json`
+    pub formats: BTreeMap<String, String>,
 }
 
 /// The main Allay compiler structure with caching optimization.
@@ -63,6 +88,11 @@ pub struct Compiler<K> {
     published: HashMap<PathBuf, K>,
     /// A mapping from keys to their compiled pages.
     cached: RefCell<HashMap<K, Arc<Mutex<Page>>>>,
+    /// The keys of the other output formats of the published pages, like "foo.md" -> [("json", key)]
+    alternates: HashMap<PathBuf, Vec<(String, K)>>,
+    /// A mapping from content files to the articles referring to them by internal references,
+    /// like "foo.md" -> ["bar.md"] for a link `@/foo.md` in "bar.md"
+    referring: HashMap<PathBuf, HashSet<PathBuf>>,
 }
 
 impl<K> Compiler<K>
@@ -95,12 +125,42 @@ where
     /// Recompile the changed pages.
     pub fn refresh_pages(&self) -> HashMap<PathBuf, CompileResult<CompileOutput>> {
         let mut results = HashMap::new();
+        let related = RelatedIndex::get_instance();
+        let translations = TranslationIndex::get_instance();
 
         for (path, k) in self.published.iter() {
-            if let Some(page) = self.cache(k)
-                && lock!(page).changed()
-            {
-                let res = page.compile(&mut Self::default_interpreter());
+            let Some(page) = self.cache(k) else {
+                continue;
+            };
+            let alternates = self.alternates.get(path).into_iter().flatten();
+            let pages: Vec<_> = std::iter::once(page.clone())
+                .chain(alternates.filter_map(|(_, key)| self.cache(key)))
+                .collect();
+
+            let mut changed = false;
+            for page in pages {
+                // only articles hold related pages and translations,
+                // which need recompiling when the lists change
+                let mut page = lock!(page);
+                for (key, current) in [
+                    (magic::RELATED, related.related_of(path)),
+                    (magic::TRANSLATIONS, translations.translations_of(path)),
+                ] {
+                    if page.scope().get_owned(key).is_some_and(|old| *old != current) {
+                        page.scope_mut().add_key(key.into(), current);
+                        page.clear();
+                    }
+                }
+                changed |= page.changed();
+            }
+
+            if changed {
+                let stats = BuildStats::get_instance();
+                let start = stats.start();
+                let res = page
+                    .compile(&mut Self::default_interpreter())
+                    .and_then(|output| self.compile_alternates(path, output));
+                stats.record_page_time(path, start);
                 results.insert(path.clone(), res);
             }
         }
@@ -108,12 +168,27 @@ where
         results
     }
 
+    /// Compile the other output formats of a published page into its output
+    fn compile_alternates(
+        &self,
+        path: &Path,
+        mut output: CompileOutput,
+    ) -> CompileResult<CompileOutput> {
+        for (format, key) in self.alternates.get(path).into_iter().flatten() {
+            if let Some(page) = self.cache(key) {
+                let text = page.compile(&mut Self::default_interpreter())?.html;
+                output.formats.insert(format.clone(), text);
+            }
+        }
+        Ok(output)
+    }
+
     /// Create a new compiler instance with default settings.
     fn default_interpreter() -> Interpreter {
-        let theme = file::workspace(get_theme_path());
-        let include_dir = theme.join(&get_theme_config().config.templates.dir);
-        let shortcode_dir = theme.join(&get_allay_config().shortcode_dir);
-        Interpreter::new(include_dir, shortcode_dir)
+        let include_dirs = template_dirs().into_iter().map(file::workspace).collect();
+        let shortcode_dirs = layered_dirs(&get_allay_config().shortcode_dir);
+        let shortcode_dirs = shortcode_dirs.into_iter().map(file::workspace).collect();
+        Interpreter::new(include_dirs, shortcode_dirs)
     }
 
     /// Mark a source file as modified, so that all cached pages depending on it will be cleared.
@@ -134,9 +209,37 @@ where
     pub fn remove<P: AsRef<Path>>(&mut self, source: P) {
         if let Some(deps) = self.influenced.remove(source.as_ref()) {
             self.published.remove(source.as_ref());
+            self.alternates.remove(source.as_ref());
             for dep in deps {
                 self.cached.borrow_mut().remove(&dep);
             }
         }
     }
+
+    /// The articles referring to a content file by internal references, which need compiling
+    /// again when it changes, as its URL or anchors may change.
+    /// An article may no longer refer to it, and it is compiled again anyway.
+    pub fn referring<P: AsRef<Path>>(&self, source: P) -> Vec<PathBuf> {
+        let articles = self.referring.get(source.as_ref()).into_iter().flatten();
+        articles.cloned().collect()
+    }
+
+    /// Forget the published pages depending on a source file, returning them to be compiled again.
+    /// This is needed when the file is overridden by the one in another directory or falls back
+    /// to it, like a site template overriding the theme one, as a page keeps its wrapper path.
+    pub fn forget_dependents<P: AsRef<Path>>(&mut self, source: P) -> Vec<PathBuf> {
+        let Some(keys) = self.influenced.get(source.as_ref()) else {
+            return vec![];
+        };
+        let pages: Vec<_> = self
+            .published
+            .iter()
+            .filter(|(_, key)| keys.contains(key))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for page in pages.iter() {
+            self.remove(page);
+        }
+        pages
+    }
 }
@@ -3,11 +3,19 @@
 
 use crate::env::{Compiled, Page};
 use crate::extract::{convert_to_html, get_meta, match_raw_content};
-use crate::interpret::Interpreter;
-use crate::{CompileOutput, CompileResult, Compiler, magic};
-use allay_base::config::{get_theme_config, get_theme_path};
-use allay_base::file;
+use crate::i18n::TranslationIndex;
+use crate::interpret::{Interpretable, Interpreter};
+use crate::parse::parse_raw;
+use crate::related::RelatedIndex;
+use crate::{CompileError, CompileOutput, CompileResult, Compiler, magic};
+use allay_base::config::{
+    OutputConfig, find_template, get_allay_config, get_theme_chain, get_theme_config, layered_dirs,
+};
+use allay_base::data::{AllayData, AllayObject};
+use allay_base::report::BuildStats;
 use allay_base::template::FileKind;
+use allay_base::{file, lock};
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
@@ -29,12 +37,49 @@ impl Compiler<String> {
         include_dir: P,
         shortcode_dir: P,
     ) -> CompileResult<String> {
-        let mut interpreter =
-            Interpreter::new(include_dir.as_ref().into(), shortcode_dir.as_ref().into());
+        let mut interpreter = Interpreter::new(
+            vec![include_dir.as_ref().into()],
+            vec![shortcode_dir.as_ref().into()],
+        );
         let page = Page::new(source.as_ref().into());
         page.into().compile(&mut interpreter).map(|o| o.html)
     }
 
+    /// Compile the content of an article alone, without its wrapper template.
+    /// This method does not use any caching mechanism.
+    pub fn content<P: AsRef<Path>>(article: P) -> CompileResult<String> {
+        let front_matter = get_meta(&article)?;
+        if front_matter
+            .get(magic::RAW)
+            .is_some_and(|value| value.as_bool().unwrap_or(false))
+        {
+            let references = &mut HashSet::new();
+            return convert_to_html(&match_raw_content(&article)?, &article, references);
+        }
+        let page = Page::new(article.as_ref().into()).into();
+        page.compile(&mut Self::default_interpreter()).map(|o| o.html)
+    }
+
+    /// Render a whole source string as a template with the data as `this`, keeping its whitespace.
+    /// The front matter is rendered too, as used for the archetypes of new contents.
+    /// This method does not use any caching mechanism.
+    pub fn render<P: AsRef<Path>>(
+        source: &str,
+        data: AllayObject,
+        include_dir: P,
+    ) -> CompileResult<String> {
+        let template = parse_raw(source)?;
+        let shortcode_dirs = layered_dirs(&get_allay_config().shortcode_dir);
+        let shortcode_dirs = shortcode_dirs.into_iter().map(file::workspace).collect();
+        let mut interpreter = Interpreter::new(vec![include_dir.as_ref().into()], shortcode_dirs);
+
+        let mut page = Page::new(PathBuf::new());
+        page.scope_mut().merge_data(data);
+        let page = page.into();
+        template.interpret(&mut interpreter, &page)?;
+        page.gen_raw_str(&mut interpreter)
+    }
+
     /// Compile a source file with caching mechanism.
     ///
     /// # Arguments
@@ -45,11 +90,15 @@ impl Compiler<String> {
         source: P,
         kind: &FileKind,
     ) -> CompileResult<CompileOutput> {
-        match kind {
-            FileKind::Article => self.article(source),
-            FileKind::Custom => self.custom(source),
+        let stats = BuildStats::get_instance();
+        let start = stats.start();
+        let res = match kind {
+            FileKind::Article => self.article(&source),
+            FileKind::Custom => self.custom(&source),
             _ => unreachable!("Only article and general can be the compile entry"),
-        }
+        };
+        stats.record_page_time(source, start);
+        res
     }
 
     /// Compile a general file
@@ -63,7 +112,10 @@ impl Compiler<String> {
             return page.compile(interpreter);
         }
 
-        let page = Page::new(source.clone()).into();
+        let mut page = Page::new(source.clone());
+        // the custom pages are not in the content directory, so their URL is empty by default
+        page.scope_mut().add_key(magic::URL.into(), Arc::new(AllayData::default()));
+        let page = page.into();
 
         self.publish(source, key.clone());
         self.remember(key, page.clone());
@@ -71,7 +123,7 @@ impl Compiler<String> {
         page.compile(interpreter)
     }
 
-    /// Get the wrapper path for an article
+    /// Get the wrapper name for an article, relative to the template directories
     fn get_article_wrapper(article: impl AsRef<Path>) -> CompileResult<PathBuf> {
         let meta = get_meta(article)?;
 
@@ -79,11 +131,14 @@ impl Compiler<String> {
         let wrapper =
             meta.get(magic::TEMPLATE).and_then(|data| data.as_str().ok()).unwrap_or(default);
 
-        let path = file::workspace(
-            get_theme_path().join(&get_theme_config().config.templates.dir).join(wrapper),
-        );
+        Ok(wrapper.into())
+    }
 
-        Ok(path)
+    /// Find a wrapper in the site and the themes, or the path it is missing at in the theme
+    fn find_wrapper(name: impl AsRef<Path>) -> PathBuf {
+        let name = name.as_ref();
+        find_template(name)
+            .unwrap_or_else(|| file::workspace(get_theme_chain()[0].template_dir()).join(name))
     }
 
     /// Generate a unique cache key for an article with its wrapper
@@ -97,18 +152,20 @@ impl Compiler<String> {
 
     /// Compile an article
     fn article(&mut self, article: impl AsRef<Path>) -> CompileResult<CompileOutput> {
-        let wrapper = Self::get_article_wrapper(&article)?;
+        let wrapper_name = Self::get_article_wrapper(&article)?;
+        let wrapper = Self::find_wrapper(&wrapper_name);
 
         let mut page = Page::new(wrapper.clone());
         let front_matter = get_meta(&article)?;
 
         // replace the "content" key with the article page
+        let mut references = HashSet::new();
         let content = if front_matter
             .get(magic::RAW)
             .is_some_and(|value| value.as_bool().unwrap_or(false))
         {
             // raw content, do not compile the markdown
-            convert_to_html(&match_raw_content(&article)?)
+            convert_to_html(&match_raw_content(&article)?, &article, &mut references)
         } else {
             let key = Self::default_key(&article);
             let article_page =
@@ -117,11 +174,41 @@ impl Compiler<String> {
             // however, the actual page published is the wrapper page, so do not use `publish` here
             self.remember(key.clone(), article_page.clone());
             self.listen(&article, key);
-            article_page.compile(&mut Self::default_interpreter())?.html
+            let content = article_page.compile(&mut Self::default_interpreter());
+            references.clone_from(lock!(article_page).references());
+            content.map(|output| output.html)
         };
+        // record the references before failing, so that a broken one is checked again later
+        for target in references {
+            self.referring.entry(target).or_default().insert(article.as_ref().into());
+        }
+        let content = content?;
         page.scope_mut().add_key(magic::CONTENT.into(), Arc::new(content.into()));
         // let the front matter of the article accessible in the wrapper
+        let formats = Self::get_article_formats(&front_matter)?;
         page.scope_mut().merge_data(front_matter);
+        let related = RelatedIndex::get_instance().related_of(&article);
+        page.scope_mut().add_key(magic::RELATED.into(), related);
+        let translations = TranslationIndex::get_instance().translations_of(&article);
+        page.scope_mut().add_key(magic::TRANSLATIONS.into(), translations);
+
+        // the other output formats share the scope, each with the template of its extension
+        let mut alternates = vec![];
+        for format in formats {
+            let template = Self::find_wrapper(wrapper_name.with_extension(&format));
+            if !template.is_file() {
+                return Err(CompileError::OutputTemplateNotFound(format, template));
+            }
+            let mut alternate = Page::new(template.clone());
+            *alternate.scope_mut() = page.scope().clone();
+
+            let key = Self::wrapper_article_key(&template, &article);
+            self.listen(&article, key.clone());
+            self.listen(template, key.clone());
+            self.remember(key.clone(), alternate.into());
+            alternates.push((format, key));
+        }
+        self.alternates.insert(article.as_ref().into(), alternates);
 
         let page = page.into();
 
@@ -133,6 +220,25 @@ impl Compiler<String> {
         self.listen(wrapper.clone(), key.clone());
         self.remember(key, page.clone());
 
-        page.compile(&mut Self::default_interpreter())
+        let output = page.compile(&mut Self::default_interpreter())?;
+        self.compile_alternates(article.as_ref(), output)
+    }
+
+    /// Get the output formats of an article other than HTML, which are file extensions
+    fn get_article_formats(meta: &AllayObject) -> CompileResult<Vec<String>> {
+        let Some(formats) = meta.get(magic::OUTPUTS).and_then(|data| data.as_list().ok()) else {
+            return Ok(vec![]);
+        };
+        let mut result = vec![];
+        for format in formats.iter() {
+            let format = format.to_string();
+            if !format.chars().all(|c| c.is_ascii_alphanumeric()) || format.is_empty() {
+                return Err(CompileError::InvalidOutputFormat(format));
+            }
+            if format != OutputConfig::HTML {
+                result.push(format);
+            }
+        }
+        Ok(result)
     }
 }
@@ -1,7 +1,7 @@
 mod parser;
 
 use crate::ParseResult;
-use crate::ast::File;
+use crate::ast::{File, Template};
 use parser::ASTBuilder;
 use pest::Parser;
 use pest_derive::Parser;
@@ -16,3 +16,10 @@ pub fn parse_file(source: &str) -> ParseResult<File> {
     let tokens = TemplateParser::parse(Rule::file, source).map_err(Box::new)?.next().unwrap();
     File::build(tokens)
 }
+
+/// Parse a whole source string into a [`Template`] keeping its whitespace,
+/// without extracting the front matter.
+pub fn parse_raw(source: &str) -> ParseResult<Template> {
+    let tokens = TemplateParser::parse(Rule::raw_file, source).map_err(Box::new)?.next().unwrap();
+    Template::build(tokens)
+}
@@ -73,7 +73,8 @@ impl ASTBuilder for Template {
     fn build(pair: Pair<Rule>) -> ParseResult<Template> {
         pair.into_inner()
             .filter_map(|item| match item.as_rule() {
-                Rule::control => Some(Control::build(item)),
+                Rule::control | Rule::raw_control => Some(Control::build(item)),
+                Rule::raw_text => Some(Ok(Control::Text(item.as_str().to_string()))),
                 Rule::EOI => None,
                 _ => parser_unreachable!(),
             })
@@ -408,7 +409,7 @@ impl ASTBuilder for Comparison {
 
         Ok(Comparison {
             left: parser_unwrap!(left),
-            right: operator.and_then(|op| right.map(|r| (op, r))),
+            right: operator.zip(right),
         })
     }
 }
@@ -475,6 +476,7 @@ impl ASTBuilder for Primary {
     fn build(pair: Pair<Rule>) -> ParseResult<Primary> {
         let item = single_inner(pair);
         match item.as_rule() {
+            Rule::call => Call::build(item).map(Primary::Call),
             Rule::field => Field::build(item).map(Primary::Field),
             Rule::top_level => TopLevel::build(item).map(Primary::TopLevel),
             Rule::number => item
@@ -498,6 +500,23 @@ impl ASTBuilder for Primary {
     }
 }
 
+impl ASTBuilder for Call {
+    fn build(pair: Pair<Rule>) -> ParseResult<Call> {
+        let mut name = String::new();
+        let mut arguments = vec![];
+
+        for item in pair.into_inner() {
+            match item.as_rule() {
+                Rule::call_pattern => name = single_inner(item).as_str().to_string(),
+                Rule::expression => arguments.push(Expression::build(item)?),
+                _ => parser_unreachable!(),
+            }
+        }
+
+        Ok(Call { name, arguments })
+    }
+}
+
 impl ASTBuilder for Field {
     fn build(pair: Pair<Rule>) -> ParseResult<Field> {
         let inner = pair.into_inner();
@@ -550,7 +569,7 @@ mod tests {
     use crate::ParseError;
     use crate::ast::Meta::Yaml;
     use crate::ast::*;
-    use crate::parse::parse_file;
+    use crate::parse::{parse_file, parse_raw};
 
     #[test]
     fn test_parse_only_text() {
@@ -911,4 +930,37 @@ After comment.
             }
         );
     }
+
+    #[test]
+    fn test_call() {
+        let source = r#"{: ref("posts/foo.md", .name) :}"#;
+        let ast = parse_file(source).unwrap();
+
+        let Control::Substitution(Substitution { expr }) = &ast.template.0[0] else {
+            panic!("expected a substitution");
+        };
+        let exp = &expr.0.0[0].0[0].left.left.left.exp;
+        let Primary::Call(call) = exp else {
+            panic!("expected a function call, got {:?}", exp);
+        };
+        assert_eq!(call.name, "ref");
+        assert_eq!(call.arguments.len(), 2);
+
+        let ast = parse_file("{: now() :}").unwrap();
+        assert!(matches!(
+            &ast.template.0[0],
+            Control::Substitution(Substitution { expr })
+                if matches!(&expr.0.0[0].0[0].left.left.left.exp, Primary::Call(c) if c.arguments.is_empty())
+        ));
+    }
+
+    #[test]
+    fn test_parse_raw() {
+        let source = "---\ntitle: \"{: .title :}\"\n---\n";
+        let ast = parse_raw(source).unwrap();
+        assert_eq!(ast.0.len(), 3);
+        assert_eq!(ast.0[0], Control::Text("---\ntitle: \"".to_string()));
+        assert!(matches!(ast.0[1], Control::Substitution(_)));
+        assert_eq!(ast.0[2], Control::Text("\"\n---\n".to_string()));
+    }
 }
@@ -0,0 +1,163 @@
+//! Related content suggestions based on the shared front-matter terms of pages.
+
+use crate::magic;
+use allay_base::config::{get_allay_config, get_related_config};
+use allay_base::data::{AllayData, AllayList, AllayObject};
+use allay_base::file;
+use allay_base::sitemap::SiteMap;
+use allay_base::{read, write};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicU32};
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// The ranked related pages of all the pages in the site map.
+/// It is rebuilt lazily whenever the site map version changes.
+#[derive(Debug)]
+pub struct RelatedIndex {
+    cache_version: AtomicU32,
+    data: RwLock<HashMap<PathBuf, Arc<AllayData>>>,
+}
+
+impl RelatedIndex {
+    pub fn get_instance() -> &'static RelatedIndex {
+        static INSTANCE: OnceLock<RelatedIndex> = OnceLock::new();
+        let instance = INSTANCE.get_or_init(|| RelatedIndex {
+            cache_version: AtomicU32::new(u32::MAX),
+            data: RwLock::new(HashMap::new()),
+        });
+        instance.update();
+        instance
+    }
+
+    /// Get the related pages of a source file in the workspace.
+    /// Returns an empty list if the file is not a content page.
+    pub fn related_of<P: AsRef<Path>>(&self, source: P) -> Arc<AllayData> {
+        let content_dir = file::workspace(&get_allay_config().content_dir);
+        source
+            .as_ref()
+            .strip_prefix(content_dir)
+            .ok()
+            .and_then(|path| read!(self.data).get(path).cloned())
+            .unwrap_or_else(|| Arc::new(AllayList::new().into()))
+    }
+
+    fn update(&self) {
+        let version = SiteMap::read().version();
+        if self.cache_version.load(atomic::Ordering::SeqCst) == version {
+            return;
+        }
+
+        self.cache_version.store(version, atomic::Ordering::SeqCst);
+
+        // only the pages in the same language can be related
+        let mut groups: HashMap<String, Vec<_>> = HashMap::new();
+        for (path, entry) in SiteMap::read().urlset.iter() {
+            let meta = entry.meta();
+            if is_hidden(&meta) {
+                continue;
+            }
+            let lang =
+                meta.get(magic::LANG).and_then(|lang| lang.as_str().ok()).unwrap_or_default();
+            groups.entry(lang.into()).or_default().push((path.clone(), meta.clone()));
+        }
+
+        let config = get_related_config();
+        *write!(self.data) = groups
+            .values()
+            .flat_map(|pages| rank(pages, &config.weights, config.limit))
+            .collect();
+    }
+}
+
+fn is_hidden(meta: &AllayObject) -> bool {
+    meta.get(magic::HIDDEN).is_some_and(|data| data.as_bool().unwrap_or(false))
+}
+
+/// Collect the normalized terms of a page under a front-matter key.
+/// A list contributes all its items, while a single value is treated as one term.
+fn terms(meta: &AllayObject, key: &str) -> HashSet<String> {
+    let normalize = |data: &AllayData| data.to_string().trim().to_lowercase();
+    match meta.get(key).map(Arc::as_ref) {
+        None | Some(AllayData::Null) => HashSet::new(),
+        Some(AllayData::List(list)) => list.iter().map(|item| normalize(item)).collect(),
+        Some(data) => HashSet::from([normalize(data)]),
+    }
+}
+
+/// Score how related two pages are by the weighted count of their shared terms
+fn score(a: &AllayObject, b: &AllayObject, weights: &HashMap<String, i64>) -> i64 {
+    weights
+        .iter()
+        .map(|(key, weight)| terms(a, key).intersection(&terms(b, key)).count() as i64 * weight)
+        .sum()
+}
+
+/// Rank the related pages for each page, keeping at most `limit` pages with a positive score.
+/// Pages with equal scores are ordered by their paths to keep the output stable.
+fn rank(
+    pages: &[(PathBuf, Arc<AllayObject>)],
+    weights: &HashMap<String, i64>,
+    limit: usize,
+) -> HashMap<PathBuf, Arc<AllayData>> {
+    pages
+        .iter()
+        .map(|(path, meta)| {
+            let mut scored: Vec<_> = pages
+                .iter()
+                .filter(|(other, _)| other != path)
+                .map(|(other, other_meta)| (score(meta, other_meta, weights), other, other_meta))
+                .filter(|(score, _, _)| *score > 0)
+                .collect();
+            scored.sort_by(|(s1, p1, _), (s2, p2, _)| s2.cmp(s1).then_with(|| p1.cmp(p2)));
+
+            let related = scored
+                .into_iter()
+                .take(limit)
+                .map(|(_, _, meta)| Arc::new(AllayData::Object(meta.clone())))
+                .collect::<AllayList>();
+            (path.clone(), Arc::new(related.into()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(path: &str, tags: &[&str], category: &str) -> (PathBuf, Arc<AllayObject>) {
+        let tags = tags.iter().map(|t| Arc::new(AllayData::from(*t))).collect::<AllayList>();
+        let meta = AllayObject::from([
+            (magic::URL.into(), Arc::new(AllayData::from(path))),
+            ("tags".into(), Arc::new(tags.into())),
+            ("categories".into(), Arc::new(category.into())),
+        ]);
+        (path.into(), Arc::new(meta))
+    }
+
+    fn urls(data: &AllayData) -> Vec<String> {
+        data.as_list()
+            .unwrap()
+            .iter()
+            .map(|meta| meta.as_obj().unwrap()[magic::URL].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_rank_related() {
+        let pages = [
+            page("a", &["rust", "wasm"], "dev"),
+            page("b", &["Rust"], "life"),
+            page("c", &["rust", "wasm"], "dev"),
+            page("d", &["cooking"], "life"),
+        ];
+        let weights = HashMap::from([("tags".into(), 2), ("categories".into(), 1)]);
+        let related = rank(&pages, &weights, 5);
+
+        assert_eq!(urls(&related[Path::new("a")]), vec!["c", "b"]);
+        assert_eq!(urls(&related[Path::new("d")]), vec!["b"]);
+
+        let related = rank(&pages, &weights, 1);
+        assert_eq!(urls(&related[Path::new("b")]), vec!["a"]);
+    }
+}
@@ -188,3 +188,16 @@ fn test_comments() {
         vec!["<p>Before", "comment", "After", "comment</p>"]
     )
 }
+
+#[test]
+fn test_call() {
+    let content = r#"{: plain("<b>bold</b>") :}"#;
+    assert_eq!(to_tokens(get_compile_res(content)), vec!["<p>bold</p>"]);
+
+    // a call has no whitespace before its parenthesis
+    let temp_dir = tempdir().unwrap();
+    let include_dir = create_include_dir(&temp_dir);
+    let shortcode_dir = create_shortcode_dir(&temp_dir);
+    let source_file = create_test_file(&temp_dir, "source.md", r#"{: plain ("x") :}"#);
+    assert!(Compiler::raw(source_file, include_dir, shortcode_dir).is_err());
+}
@@ -6,6 +6,7 @@ pub mod sort_page;
 
 use crate::plugin_info;
 pub use compiler::CompilerComponent;
+pub use export::allay::plugin::capability::Capabilities;
 use export::exports::allay::plugin as wit;
 pub use listen::ListenComponent;
 pub use route::RouteComponent;
@@ -21,4 +22,8 @@ impl export::Guest for PluginGuest {
     fn version() -> String {
         plugin_info().version.to_string()
     }
+
+    fn capabilities() -> Capabilities {
+        (plugin_info().capabilities)()
+    }
 }
@@ -42,7 +42,35 @@ pub trait Plugin: Sync + Send {
     where
         Self: Sized;
 
+    /// Access to the site requested by the plugin, nothing by default.
+    /// The directories and environment variables not granted are not visible to the plugin,
+    /// and the site may grant other ones in its `plugin.toml`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use allay_plugin_api::Capabilities;
+    /// fn capabilities() -> Capabilities {
+    ///     Capabilities {
+    ///         read_dirs: vec!["contents".into()],
+    ///         write_dirs: vec!["data/my-plugin".into()],
+    ///         env: vec!["MY_PLUGIN_TOKEN".into()],
+    ///     }
+    /// }
+    /// ```
+    fn capabilities() -> Capabilities
+    where
+        Self: Sized,
+    {
+        Capabilities {
+            read_dirs: vec![],
+            write_dirs: vec![],
+            env: vec![],
+        }
+    }
+
     /// Create a new instance of the plugin.
+    /// It is called on the first call of a component, once the capabilities are granted.
     fn new() -> Self
     where
         Self: Sized;
@@ -51,20 +79,24 @@ pub trait Plugin: Sync + Send {
 struct PluginInfo {
     name: &'static str,
     version: &'static str,
+    capabilities: fn() -> Capabilities,
+    new: fn() -> Box<dyn Plugin>,
 }
 
 static PLUGIN: OnceLock<Box<dyn Plugin>> = OnceLock::new();
 static PLUGIN_INFO: OnceLock<PluginInfo> = OnceLock::new();
 
 pub fn register_plugin<T: Plugin + 'static>() {
-    let name = T::name();
-    let version = T::version();
-    PLUGIN_INFO.get_or_init(|| PluginInfo { name, version });
-    PLUGIN.get_or_init(|| Box::new(T::new()));
+    PLUGIN_INFO.get_or_init(|| PluginInfo {
+        name: T::name(),
+        version: T::version(),
+        capabilities: T::capabilities,
+        new: || Box::new(T::new()),
+    });
 }
 
 fn plugin() -> &'static dyn Plugin {
-    PLUGIN.get_or_init(|| panic!("Plugin not registered")).as_ref()
+    PLUGIN.get_or_init(|| (plugin_info().new)()).as_ref()
 }
 
 fn plugin_info() -> &'static PluginInfo {
@@ -4,5 +4,6 @@ mod listen;
 mod route;
 mod sort_page;
 
-pub use export::Plugin;
+pub use export::PluginBase as Plugin;
+pub use export::allay::plugin::capability::Capabilities;
 pub use export::exports::allay::plugin as wit;
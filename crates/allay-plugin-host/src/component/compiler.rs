@@ -14,16 +14,16 @@ impl From<TemplateKind> for FileType {
 
 impl PluginHost {
     pub fn before_compile(&mut self, source: String, ty: TemplateKind) -> String {
-        self.plugin
-            .allay_plugin_compiler()
-            .call_before_compile(&mut self.store, &source, ty.into())
-            .unwrap_or(source)
+        self.call("before_compile", |plugin, store| {
+            plugin.allay_plugin_compiler().call_before_compile(store, &source, ty.into())
+        })
+        .unwrap_or(source)
     }
 
     pub fn after_compile(&mut self, compiled: String, ty: TemplateKind) -> String {
-        self.plugin
-            .allay_plugin_compiler()
-            .call_after_compile(&mut self.store, &compiled, ty.into())
-            .unwrap_or(compiled)
+        self.call("after_compile", |plugin, store| {
+            plugin.allay_plugin_compiler().call_after_compile(store, &compiled, ty.into())
+        })
+        .unwrap_or(compiled)
     }
 }
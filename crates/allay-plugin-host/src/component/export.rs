@@ -1,4 +1,4 @@
 wasmtime::component::bindgen!({
     path: "../allay-plugin-api/wit",
-    world: "plugin",
+    world: "plugin-base",
 });
@@ -1,15 +1,24 @@
-use crate::{PluginHost, WasmOk};
+use crate::PluginHost;
 
 impl PluginHost {
     pub fn on_create(&mut self, source: String) {
-        self.plugin.allay_plugin_listen().call_on_create(&mut self.store, &source).ok_();
+        self.call("on_create", |plugin, store| {
+            plugin.allay_plugin_listen().call_on_create(store, &source)
+        })
+        .ok();
     }
 
     pub fn on_modify(&mut self, source: String) {
-        self.plugin.allay_plugin_listen().call_on_modify(&mut self.store, &source).ok_();
+        self.call("on_modify", |plugin, store| {
+            plugin.allay_plugin_listen().call_on_modify(store, &source)
+        })
+        .ok();
     }
 
     pub fn on_remove(&mut self, source: String) {
-        self.plugin.allay_plugin_listen().call_on_remove(&mut self.store, &source).ok_();
+        self.call("on_remove", |plugin, store| {
+            plugin.allay_plugin_listen().call_on_remove(store, &source)
+        })
+        .ok();
     }
 }
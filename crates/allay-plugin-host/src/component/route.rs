@@ -74,14 +74,16 @@ impl From<route::Response> for Response {
 
 impl PluginHost {
     pub fn handle_request(&mut self, request: route::Request) -> wasmtime::Result<route::Response> {
-        self.plugin.allay_plugin_route().call_handle(&mut self.store, &request)
+        self.call("handle", |plugin, store| {
+            plugin.allay_plugin_route().call_handle(store, &request)
+        })
     }
 
     pub fn route_paths(&mut self) -> wasmtime::Result<Vec<(Method, String)>> {
         let path = self
-            .plugin
-            .allay_plugin_route()
-            .call_route_paths(&mut self.store)?
+            .call("route_paths", |plugin, store| {
+                plugin.allay_plugin_route().call_route_paths(store)
+            })?
             .into_iter()
             .map(|(method, path)| (method.into(), path))
             .collect();
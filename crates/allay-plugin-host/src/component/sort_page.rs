@@ -13,7 +13,9 @@ fn to_ordering(order: i8) -> Ordering {
 
 impl PluginHost {
     pub fn sort_enabled(&mut self) -> wasmtime::Result<bool> {
-        self.plugin.allay_plugin_sort_page().call_sort_enabled(&mut self.store)
+        self.call("sort_enabled", |plugin, store| {
+            plugin.allay_plugin_sort_page().call_sort_enabled(store)
+        })
     }
 
     pub fn get_sort_order(
@@ -21,9 +23,11 @@ impl PluginHost {
         page_meta1: &str,
         page_meta2: &str,
     ) -> wasmtime::Result<Ordering> {
-        self.plugin
-            .allay_plugin_sort_page()
-            .call_get_sort_order(&mut self.store, page_meta1, page_meta2)
-            .map(to_ordering)
+        self.call("get_sort_order", |plugin, store| {
+            plugin
+                .allay_plugin_sort_page()
+                .call_get_sort_order(store, page_meta1, page_meta2)
+        })
+        .map(to_ordering)
     }
 }
@@ -1,31 +1,99 @@
+use allay_base::lock;
+use allay_base::plugin_health::PluginHealth;
+pub use component::Capabilities;
 use component::Plugin;
 pub use component::wit::route::{Header, Method, Request, Response};
-use std::path::Path;
-use std::sync::Arc;
+pub use limits::{LimitError, PluginLimits};
+use limits::{MemoryLimiter, describe, engine};
+use std::path::{Component as PathComponent, Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use tracing::warn;
-use wasmtime::component::{Component, Linker, ResourceTable};
-use wasmtime::{Config, Engine, Store};
+use wasmtime::Store;
+use wasmtime::component::{Component, Instance, Linker, ResourceTable};
 use wasmtime_wasi::{DirPerms, FilePerms, WasiCtx, WasiCtxView, WasiView};
 
 mod component;
+mod limits;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+/// The export of the capabilities requested by a plugin, missing in the older plugins
+const CAPABILITIES_EXPORT: &str = "capabilities";
 
-#[derive(Default)]
 struct PluginState {
     table: ResourceTable,
     ctx: WasiCtx,
+    limiter: MemoryLimiter,
 }
 
 impl PluginState {
-    fn with_dir(dir: &Path) -> Self {
-        let ctx = WasiCtx::builder()
-            .preopened_dir(dir, ".", DirPerms::all(), FilePerms::all())
-            .unwrap()
-            .build();
+    fn new(ctx: WasiCtx, limits: &PluginLimits) -> Self {
         Self {
             table: ResourceTable::new(),
             ctx,
+            limiter: MemoryLimiter {
+                limit: limits.memory,
+            },
+        }
+    }
+
+    /// Preopen only the granted directories of the site, each at its path relative to the root
+    fn with_grants(
+        root: &Path,
+        grants: &Capabilities,
+        limits: &PluginLimits,
+    ) -> wasmtime::Result<Self> {
+        let mut builder = WasiCtx::builder();
+        for dir in grants.read_dirs.iter() {
+            let host_dir = granted_dir(root, dir)?;
+            if !host_dir.is_dir() {
+                warn!(
+                    "Directory {:?} granted to the plugin does not exist",
+                    host_dir
+                );
+                continue;
+            }
+            builder.preopened_dir(host_dir, dir, DirPerms::READ, FilePerms::READ)?;
         }
+        for dir in grants.write_dirs.iter() {
+            let host_dir = granted_dir(root, dir)?;
+            std::fs::create_dir_all(&host_dir)?;
+            builder.preopened_dir(host_dir, dir, DirPerms::all(), FilePerms::all())?;
+        }
+        for (key, value) in granted_env(&grants.env) {
+            builder.env(key, value);
+        }
+        Ok(Self::new(builder.build(), limits))
+    }
+
+    /// Create a store of a plugin, with its memory limited
+    fn into_store(self) -> Store<PluginState> {
+        let mut store = Store::new(engine(), self);
+        store.limiter(|state| &mut state.limiter);
+        store
+    }
+}
+
+/// Get a granted directory in the site, which must not be outside of the root
+fn granted_dir(root: &Path, dir: &str) -> wasmtime::Result<PathBuf> {
+    let path = Path::new(dir);
+    let inside = path
+        .components()
+        .all(|component| matches!(component, PathComponent::Normal(_) | PathComponent::CurDir));
+    if !inside {
+        return Err(wasmtime::Error::msg(format!(
+            "Directory '{}' is outside of the site",
+            dir
+        )));
     }
+    Ok(root.join(path))
+}
+
+/// Get the granted environment variables which are set, the others are never visible
+fn granted_env(keys: &[String]) -> Vec<(String, String)> {
+    keys.iter()
+        .filter_map(|key| std::env::var(key).ok().map(|value| (key.clone(), value)))
+        .collect()
 }
 
 impl WasiView for PluginState {
@@ -37,45 +105,319 @@ impl WasiView for PluginState {
     }
 }
 
+/// What a plugin reports about itself before anything is granted to it
+#[derive(Debug, Clone)]
+pub struct PluginManifest {
+    pub name: String,
+    pub version: String,
+    pub capabilities: Capabilities,
+}
+
+/// What the site grants to a plugin
+#[derive(Debug, Clone)]
+pub struct PluginSettings {
+    pub capabilities: Capabilities,
+    pub limits: PluginLimits,
+}
+
+/// The health of a plugin, shared with its manager to be read while the plugin is busy
+pub type SharedHealth = Arc<Mutex<PluginHealth>>;
+
 pub struct PluginHost {
+    name: String,
+    limits: PluginLimits,
+    capabilities: Capabilities,
+    working_dir: PathBuf,
+    component: Component,
+    linker: Linker<PluginState>,
     store: Store<PluginState>,
     plugin: Arc<Plugin>,
+    health: SharedHealth,
 }
 
 impl PluginHost {
-    pub fn new(wasm_path: &Path, working_dir: &Path) -> wasmtime::Result<Self> {
-        let engine = Engine::new(&Config::default())?;
-        let component = Component::from_file(&engine, wasm_path)?;
-        let mut store = Store::new(&engine, PluginState::with_dir(working_dir));
+    /// Load a plugin with the settings chosen from its manifest.
+    /// The plugin is first instantiated without any access to read the manifest,
+    /// then again with the granted directories and environment variables.
+    pub fn new(
+        wasm_path: &Path,
+        working_dir: &Path,
+        settings: impl FnOnce(&PluginManifest) -> PluginSettings,
+    ) -> wasmtime::Result<Self> {
+        let (component, linker) = Self::compile(wasm_path)?;
+        let manifest = Self::read_manifest(wasm_path, &component, &linker)?;
+        let PluginSettings {
+            capabilities,
+            limits,
+        } = settings(&manifest);
+
+        let (store, plugin) = Self::instantiate(
+            &manifest.name,
+            &component,
+            &linker,
+            working_dir,
+            &capabilities,
+            &limits,
+        )?;
+        Ok(Self {
+            name: manifest.name,
+            limits,
+            capabilities,
+            working_dir: working_dir.into(),
+            component,
+            linker,
+            store,
+            plugin: Arc::new(plugin),
+            health: SharedHealth::default(),
+        })
+    }
+
+    /// Instantiate a plugin in a new store with its grants and limits
+    fn instantiate(
+        name: &str,
+        component: &Component,
+        linker: &Linker<PluginState>,
+        working_dir: &Path,
+        capabilities: &Capabilities,
+        limits: &PluginLimits,
+    ) -> wasmtime::Result<(Store<PluginState>, Plugin)> {
+        let state = PluginState::with_grants(working_dir, capabilities, limits)?;
+        let mut store = state.into_store();
+        store.set_epoch_deadline(limits.deadline());
+        let plugin = Plugin::instantiate(&mut store, component, linker)
+            .and_then(|plugin| plugin.call_init_plugin(&mut store).map(|_| plugin))
+            .map_err(|e| describe(e, name, "init-plugin", limits))?;
+        Ok((store, plugin))
+    }
+
+    /// Read the manifest of a plugin without granting it anything
+    pub fn probe(wasm_path: &Path) -> wasmtime::Result<PluginManifest> {
+        let (component, linker) = Self::compile(wasm_path)?;
+        Self::read_manifest(wasm_path, &component, &linker)
+    }
+
+    fn compile(wasm_path: &Path) -> wasmtime::Result<(Component, Linker<PluginState>)> {
+        let component = Component::from_file(engine(), wasm_path)?;
 
-        let mut linker = Linker::new(&engine);
+        let mut linker = Linker::new(engine());
         wasmtime_wasi::p2::add_to_linker_sync(&mut linker)?;
+        Ok((component, linker))
+    }
 
-        let plugin = Plugin::instantiate(&mut store, &component, &linker)?;
-        plugin.call_init_plugin(&mut store)?;
-        let plugin = Arc::new(plugin);
+    /// Read the manifest within the default limits, as the plugin is not known yet
+    fn read_manifest(
+        wasm_path: &Path,
+        component: &Component,
+        linker: &Linker<PluginState>,
+    ) -> wasmtime::Result<PluginManifest> {
+        let limits = PluginLimits::default();
+        let mut store = PluginState::new(WasiCtx::default(), &limits).into_store();
+        store.set_epoch_deadline(limits.deadline());
+        let file = wasm_path.file_name().unwrap_or_default().to_string_lossy();
+        let manifest = linker.instantiate(&mut store, component).and_then(|instance| {
+            let plugin = Plugin::new(&mut store, &instance)?;
+            plugin.call_init_plugin(&mut store)?;
+            Ok(PluginManifest {
+                name: plugin.call_name(&mut store)?,
+                version: plugin.call_version(&mut store)?,
+                capabilities: Self::requested_capabilities(&file, &instance, &mut store)?,
+            })
+        });
+        manifest.map_err(|e| describe(e, &file, "manifest", &limits))
+    }
 
-        let host = Self { store, plugin };
-        Ok(host)
+    /// Call the optional `capabilities` export of a plugin. The plugins built before it was added
+    /// may read the site, as they did before, but nothing is written or passed from the environment.
+    fn requested_capabilities(
+        file: &str,
+        instance: &Instance,
+        store: &mut Store<PluginState>,
+    ) -> wasmtime::Result<Capabilities> {
+        let Some(func) = instance.get_func(&mut *store, CAPABILITIES_EXPORT) else {
+            warn!(
+                "Plugin {} does not request its capabilities, granting it read-only access to the site",
+                file
+            );
+            return Ok(Capabilities {
+                read_dirs: vec![".".into()],
+                write_dirs: vec![],
+                env: vec![],
+            });
+        };
+        let func = func.typed::<(), (Capabilities,)>(&*store)?;
+        let (capabilities,) = func.call(&mut *store, ())?;
+        func.post_return(&mut *store)?;
+        Ok(capabilities)
+    }
+
+    /// Call a hook of the plugin within its limits, naming the plugin and the hook on errors.
+    /// A disabled plugin is not called at all.
+    fn call<T>(
+        &mut self,
+        hook: &str,
+        f: impl FnOnce(&Plugin, &mut Store<PluginState>) -> wasmtime::Result<T>,
+    ) -> wasmtime::Result<T> {
+        if lock!(self.health).disabled {
+            return Err(wasmtime::Error::msg(format!(
+                "Plugin '{}' is disabled",
+                self.name
+            )));
+        }
+
+        self.store.set_epoch_deadline(self.limits.deadline());
+        match f(&self.plugin, &mut self.store) {
+            Ok(value) => {
+                lock!(self.health).succeed();
+                Ok(value)
+            }
+            Err(e) => {
+                let e = describe(e, &self.name, hook, &self.limits);
+                self.fail(&e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Record a failed call, and reinstantiate the plugin as a trap may leave its state corrupted,
+    /// or disable it after too many failures in a row
+    fn fail(&mut self, error: &wasmtime::Error) {
+        // a plugin hitting its limits is likely stuck, so it is reported besides the log
+        if error.is::<LimitError>() {
+            eprintln!("⚠️  {}", error);
+        }
+        warn!("WASM call failed: {:#}", error);
+
+        {
+            let mut health = lock!(self.health);
+            if health.fail(format!("{:#}", error), self.limits.max_failures) {
+                eprintln!(
+                    "⚠️  Plugin '{}' is disabled after {} failures in a row: {}",
+                    self.name, health.consecutive_failures, error
+                );
+                return;
+            }
+        }
+
+        // the health is not locked while reinstantiating, so it can still be read meanwhile
+        let instance = Self::instantiate(
+            &self.name,
+            &self.component,
+            &self.linker,
+            &self.working_dir,
+            &self.capabilities,
+            &self.limits,
+        );
+        let mut health = lock!(self.health);
+        match instance {
+            Ok((store, plugin)) => {
+                self.store = store;
+                self.plugin = Arc::new(plugin);
+                health.restarts += 1;
+            }
+            Err(e) => {
+                health.disabled = true;
+                eprintln!(
+                    "⚠️  Plugin '{}' is disabled as it cannot be reinstantiated: {:#}",
+                    self.name, e
+                );
+            }
+        }
     }
 
     pub fn plugin_name(&mut self) -> wasmtime::Result<String> {
-        self.plugin.call_name(&mut self.store)
+        self.call("name", |plugin, store| plugin.call_name(store))
     }
 
     pub fn plugin_version(&mut self) -> wasmtime::Result<String> {
-        self.plugin.call_version(&mut self.store)
+        self.call("version", |plugin, store| plugin.call_version(store))
+    }
+
+    pub fn limits(&self) -> &PluginLimits {
+        &self.limits
     }
-}
 
-trait WasmOk<T> {
-    fn ok_(self);
+    pub fn health(&self) -> SharedHealth {
+        self.health.clone()
+    }
 }
 
-impl<T> WasmOk<T> for wasmtime::Result<T> {
-    fn ok_(self) {
-        if let Err(e) = self {
-            warn!("WASM call failed: {}", e);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_granted_dir() {
+        let root = Path::new("/site");
+        assert_eq!(
+            granted_dir(root, "data/auth").unwrap(),
+            PathBuf::from("/site/data/auth")
+        );
+        assert_eq!(granted_dir(root, ".").unwrap(), PathBuf::from("/site/."));
+        assert!(granted_dir(root, "..").is_err());
+        assert!(granted_dir(root, "data/../../etc").is_err());
+        assert!(granted_dir(root, "/etc").is_err());
+    }
+
+    #[test]
+    fn test_granted_env() {
+        // SAFETY: the variables are only used by this test
+        unsafe {
+            std::env::set_var("ALLAY_TEST_GRANTED", "yes");
+            std::env::set_var("ALLAY_TEST_HIDDEN", "no");
         }
+        let keys = [
+            "ALLAY_TEST_GRANTED".to_string(),
+            "ALLAY_TEST_UNSET".to_string(),
+        ];
+        assert_eq!(
+            granted_env(&keys),
+            vec![("ALLAY_TEST_GRANTED".to_string(), "yes".to_string())]
+        );
+    }
+
+    fn faulty_host(dir: &Path, max_failures: u32) -> PluginHost {
+        let path = testing::faulty_plugin(dir);
+        PluginHost::new(&path, dir, |manifest| PluginSettings {
+            capabilities: manifest.capabilities.clone(),
+            limits: PluginLimits {
+                max_failures,
+                ..Default::default()
+            },
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_reinstantiate() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut host = faulty_host(dir.path(), 3);
+        assert_eq!(host.plugin_name().unwrap(), testing::FAULTY_PLUGIN);
+
+        let error = host.get_sort_order("{}", "{}").unwrap_err();
+        assert!(format!("{:#}", error).contains("`get_sort_order`"));
+        let health = lock!(host.health).clone();
+        assert_eq!((health.failures, health.restarts), (1, 1));
+        assert!(!health.disabled);
+
+        // the poisoned instance is replaced, so the next call works
+        assert!(host.sort_enabled().unwrap());
+        let health = lock!(host.health).clone();
+        assert_eq!((health.consecutive_failures, health.restarts), (0, 1));
+    }
+
+    #[test]
+    fn test_disable() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut host = faulty_host(dir.path(), 2);
+        assert!(host.get_sort_order("{}", "{}").is_err());
+        assert!(host.get_sort_order("{}", "{}").is_err());
+        let health = lock!(host.health).clone();
+        assert!(health.disabled);
+        assert_eq!((health.failures, health.restarts), (2, 1));
+
+        // a disabled plugin is not called anymore
+        let error = host.sort_enabled().unwrap_err();
+        assert!(error.to_string().contains("is disabled"));
+        assert_eq!(lock!(host.health).calls, 2);
     }
 }
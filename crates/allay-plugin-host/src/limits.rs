@@ -0,0 +1,203 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+use thiserror::Error;
+use wasmtime::{Config, Engine, ResourceLimiter, Trap};
+
+/// The interval of the epochs, the precision of the time limits
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// The limits of the resources used by a plugin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PluginLimits {
+    /// the longest time of a single call of a hook
+    pub timeout: Duration,
+    /// the largest size of the memory in bytes
+    pub memory: usize,
+    /// the failed calls in a row before the plugin is disabled
+    pub max_failures: u32,
+}
+
+impl PluginLimits {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+    pub const DEFAULT_MEMORY: usize = 512 << 20;
+    pub const DEFAULT_MAX_FAILURES: u32 = 3;
+
+    /// The number of epochs before a call is interrupted, at least one
+    pub(crate) fn deadline(&self) -> u64 {
+        (self.timeout.as_millis() / EPOCH_TICK.as_millis()).max(1) as u64
+    }
+}
+
+impl Default for PluginLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Self::DEFAULT_TIMEOUT,
+            memory: Self::DEFAULT_MEMORY,
+            max_failures: Self::DEFAULT_MAX_FAILURES,
+        }
+    }
+}
+
+/// A limit hit by a plugin, naming the plugin and the hook running
+#[derive(Error, Debug)]
+pub enum LimitError {
+    #[error("Plugin '{plugin}' exceeded its time limit of {} ms in `{hook}`", .timeout.as_millis())]
+    Timeout {
+        plugin: String,
+        hook: String,
+        timeout: Duration,
+    },
+
+    #[error("Plugin '{plugin}' exceeded its memory limit of {} MiB in `{hook}`", .memory >> 20)]
+    Memory {
+        plugin: String,
+        hook: String,
+        memory: usize,
+    },
+}
+
+/// The error of a memory growing over the limit, trapping the plugin
+#[derive(Error, Debug)]
+#[error("memory limit of {limit} bytes exceeded, {desired} bytes requested")]
+pub(crate) struct MemoryExceeded {
+    limit: usize,
+    desired: usize,
+}
+
+/// Limit the memory of a plugin, trapping instead of failing the growth
+pub(crate) struct MemoryLimiter {
+    pub limit: usize,
+}
+
+impl ResourceLimiter for MemoryLimiter {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        match desired > self.limit {
+            true => Err(MemoryExceeded {
+                limit: self.limit,
+                desired,
+            }
+            .into()),
+            false => Ok(true),
+        }
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: usize,
+        _desired: usize,
+        _maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        Ok(true)
+    }
+}
+
+/// The engine shared by all the plugins, with its epoch advanced by a background thread
+pub(crate) fn engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| {
+        let mut config = Config::default();
+        config.epoch_interruption(true);
+        let engine = Engine::new(&config).expect("Failed to create the plugin engine");
+
+        let ticker = engine.clone();
+        std::thread::Builder::new()
+            .name("plugin-epoch".into())
+            .spawn(move || {
+                loop {
+                    std::thread::sleep(EPOCH_TICK);
+                    ticker.increment_epoch();
+                }
+            })
+            .expect("Failed to start the plugin epoch thread");
+        engine
+    })
+}
+
+/// Describe an error of a call with the limit hit, if any
+pub(crate) fn describe(
+    error: wasmtime::Error,
+    plugin: &str,
+    hook: &str,
+    limits: &PluginLimits,
+) -> wasmtime::Error {
+    if error.downcast_ref::<Trap>() == Some(&Trap::Interrupt) {
+        LimitError::Timeout {
+            plugin: plugin.into(),
+            hook: hook.into(),
+            timeout: limits.timeout,
+        }
+        .into()
+    } else if error.downcast_ref::<MemoryExceeded>().is_some() {
+        LimitError::Memory {
+            plugin: plugin.into(),
+            hook: hook.into(),
+            memory: limits.memory,
+        }
+        .into()
+    } else {
+        error.context(format!("Plugin '{}' failed in `{}`", plugin, hook))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmtime::{Instance, Module, Store};
+
+    const LIMITS: PluginLimits = PluginLimits {
+        timeout: Duration::from_millis(50),
+        memory: 1 << 20,
+        max_failures: 1,
+    };
+
+    fn call(wat: &str, func: &str) -> wasmtime::Error {
+        let module = Module::new(engine(), wat).unwrap();
+        let mut store = Store::new(
+            engine(),
+            MemoryLimiter {
+                limit: LIMITS.memory,
+            },
+        );
+        store.limiter(|limiter| limiter);
+        store.set_epoch_deadline(LIMITS.deadline());
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let func = instance.get_typed_func::<(), i32>(&mut store, func).unwrap();
+        let error = func.call(&mut store, ()).unwrap_err();
+        describe(error, "looper", "on-create", &LIMITS)
+    }
+
+    #[test]
+    fn test_timeout() {
+        let error = call(
+            r#"(module (func (export "spin") (result i32) (loop $l (br $l)) (i32.const 0)))"#,
+            "spin",
+        );
+        assert!(matches!(
+            error.downcast_ref::<LimitError>(),
+            Some(LimitError::Timeout { .. })
+        ));
+        let message = error.to_string();
+        assert!(message.contains("'looper'") && message.contains("`on-create`"));
+        assert!(message.contains("50 ms"));
+    }
+
+    #[test]
+    fn test_memory() {
+        let error = call(
+            r#"(module (memory 1) (func (export "grow") (result i32) (memory.grow (i32.const 100))))"#,
+            "grow",
+        );
+        assert!(matches!(
+            error.downcast_ref::<LimitError>(),
+            Some(LimitError::Memory { .. })
+        ));
+        let message = error.to_string();
+        assert!(message.contains("'looper'") && message.contains("`on-create`"));
+        assert!(message.contains("1 MiB"));
+    }
+}
@@ -0,0 +1,100 @@
+//! A plugin failing on purpose, for testing how the failures of the plugins are handled
+
+use std::path::{Path, PathBuf};
+use wit_component::{ComponentEncoder, StringEncoding, embed_component_metadata};
+use wit_parser::Resolve;
+
+/// The name reported by the faulty plugin
+pub const FAULTY_PLUGIN: &str = "faulty";
+
+/// The core module of the faulty plugin. Its sort is enabled, and `get-sort-order` compares
+/// the lengths of the pages until it has answered `{sorts}` times. Then it traps, and leaves
+/// the instance poisoned so that `sort-enabled` traps too until it is reinstantiated.
+/// The other hooks do nothing.
+const FAULTY_MODULE: &str = r#"
+(module
+    (memory (export "memory") 1)
+    (global $heap (mut i32) (i32.const 1024))
+    (global $poisoned (mut i32) (i32.const 0))
+    (global $sorts (mut i32) (i32.const 0))
+    (data (i32.const 0) "faulty")
+    (data (i32.const 8) "0.1.0")
+
+    (func (export "cabi_realloc") (param i32 i32 i32 i32) (result i32)
+        (local $ptr i32)
+        (local.set $ptr (i32.and (i32.add (global.get $heap) (i32.const 7)) (i32.const -8)))
+        (global.set $heap (i32.add (local.get $ptr) (local.get 3)))
+        (local.get $ptr))
+
+    ;; a string or a list returned at 16, the area from 32 is left empty for the records
+    (func $list (param $ptr i32) (param $len i32) (result i32)
+        (i32.store (i32.const 16) (local.get $ptr))
+        (i32.store (i32.const 20) (local.get $len))
+        (i32.const 16))
+
+    (func (export "name") (result i32) (call $list (i32.const 0) (i32.const 6)))
+    (func (export "version") (result i32) (call $list (i32.const 8) (i32.const 5)))
+    (func (export "capabilities") (result i32) (i32.const 32))
+    (func (export "init-plugin"))
+
+    (func (export "allay:plugin/route@0.1.0#handle")
+        (param i32 i32 i32 i32 i32 i32 i32) (result i32)
+        (i32.const 32))
+    (func (export "allay:plugin/route@0.1.0#route-paths") (result i32)
+        (call $list (i32.const 0) (i32.const 0)))
+
+    (func (export "allay:plugin/compiler@0.1.0#before-compile")
+        (param i32 i32 i32) (result i32)
+        (call $list (local.get 0) (local.get 1)))
+    (func (export "allay:plugin/compiler@0.1.0#after-compile")
+        (param i32 i32 i32) (result i32)
+        (call $list (local.get 0) (local.get 1)))
+
+    (func (export "allay:plugin/listen@0.1.0#on-create") (param i32 i32))
+    (func (export "allay:plugin/listen@0.1.0#on-modify") (param i32 i32))
+    (func (export "allay:plugin/listen@0.1.0#on-remove") (param i32 i32))
+
+    (func (export "allay:plugin/sort-page@0.1.0#sort-enabled") (result i32)
+        (if (global.get $poisoned) (then unreachable))
+        (i32.const 1))
+    (func (export "allay:plugin/sort-page@0.1.0#get-sort-order")
+        (param i32 i32 i32 i32) (result i32)
+        (if (i32.ge_u (global.get $sorts) (i32.const {sorts}))
+            (then (global.set $poisoned (i32.const 1)) unreachable))
+        (global.set $sorts (i32.add (global.get $sorts) (i32.const 1)))
+        (i32.sub
+            (i32.gt_u (local.get 1) (local.get 3))
+            (i32.lt_u (local.get 1) (local.get 3))))
+)
+"#;
+
+/// Write the faulty plugin as `faulty.wasm` in a directory, returning its path.
+/// Its first `get-sort-order` traps.
+pub fn faulty_plugin(dir: &Path) -> PathBuf {
+    faulty_plugin_after(dir, 0)
+}
+
+/// Write the faulty plugin as `faulty.wasm` in a directory, returning its path.
+/// Its `get-sort-order` compares the lengths of the pages the given times before trapping.
+pub fn faulty_plugin_after(dir: &Path, sorts: u32) -> PathBuf {
+    let mut resolve = Resolve::default();
+    let (package, _) = resolve
+        .push_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("../allay-plugin-api/wit"))
+        .expect("Failed to read the plugin WIT");
+    let world = resolve
+        .select_world(&[package], Some("plugin"))
+        .expect("Failed to find the plugin world");
+
+    let module = FAULTY_MODULE.replace("{sorts}", &sorts.to_string());
+    let mut module = wat::parse_str(module).expect("Failed to parse the faulty plugin");
+    embed_component_metadata(&mut module, &resolve, world, StringEncoding::UTF8)
+        .expect("Failed to embed the plugin world");
+    let component = ComponentEncoder::default()
+        .module(&module)
+        .and_then(|encoder| encoder.validate(true).encode())
+        .expect("Failed to encode the faulty plugin");
+
+    let path = dir.join(format!("{}.wasm", FAULTY_PLUGIN));
+    std::fs::write(&path, component).expect("Failed to write the faulty plugin");
+    path
+}
@@ -1,9 +1,21 @@
+use allay_base::config::PLUGIN_CONFIG_FILE;
 use allay_base::data::{AllayData, AllayObject};
 use allay_base::file;
+use allay_plugin_host::{Capabilities, PluginLimits, PluginManifest, PluginSettings};
 use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 use tracing::warn;
 
-const PLUGIN_CONFIG_FILE: &str = "plugin.toml";
+/// The key of the capabilities granted to a plugin in its config, overriding the requested ones
+const CAPABILITIES: &str = "capabilities";
+const READ_DIRS: &str = "read_dirs";
+const WRITE_DIRS: &str = "write_dirs";
+const ENV: &str = "env";
+/// The key of the resource limits of a plugin in its config
+const LIMITS: &str = "limits";
+const TIMEOUT_MS: &str = "timeout_ms";
+const MEMORY_MB: &str = "memory_mb";
+const MAX_FAILURES: &str = "max_failures";
 
 pub fn get_plugin_config(name: &str) -> Arc<AllayObject> {
     static PLUGINS_CONFIG: OnceLock<Arc<AllayObject>> = OnceLock::new();
@@ -33,3 +45,77 @@ pub fn get_plugin_config(name: &str) -> Arc<AllayObject> {
         }
     }
 }
+
+/// The capabilities granted to a plugin: the requested ones,
+/// with each list in `[<name>.capabilities]` of `plugin.toml` replacing the requested one
+pub fn granted_capabilities(manifest: &PluginManifest) -> Capabilities {
+    let mut grants = manifest.capabilities.clone();
+    let config = get_plugin_config(&manifest.name);
+    let Some(overrides) = config.get(CAPABILITIES) else {
+        return grants;
+    };
+    let Ok(overrides) = overrides.as_obj() else {
+        warn!("Capabilities of plugin '{}' must be a table", manifest.name);
+        return grants;
+    };
+
+    for (key, granted) in [
+        (READ_DIRS, &mut grants.read_dirs),
+        (WRITE_DIRS, &mut grants.write_dirs),
+        (ENV, &mut grants.env),
+    ] {
+        let Some(value) = overrides.get(key) else {
+            continue;
+        };
+        let strings = value.as_list().ok().and_then(|list| {
+            list.iter().map(|item| item.as_str().map(String::from).ok()).collect()
+        });
+        match strings {
+            Some(strings) => *granted = strings,
+            None => warn!(
+                "Capability '{}' of plugin '{}' must be a list of strings",
+                key, manifest.name
+            ),
+        }
+    }
+    grants
+}
+
+/// The limits of a plugin in `[<name>.limits]` of `plugin.toml`, the default ones for the others
+pub fn plugin_limits(name: &str) -> PluginLimits {
+    let mut limits = PluginLimits::default();
+    let config = get_plugin_config(name);
+    let Some(overrides) = config.get(LIMITS).and_then(|limits| limits.as_obj().ok()) else {
+        return limits;
+    };
+
+    let positive = |key: &str| {
+        let value = overrides.get(key)?;
+        let value = value.as_int().ok().filter(|value| *value > 0);
+        if value.is_none() {
+            warn!(
+                "Limit '{}' of plugin '{}' must be a positive integer",
+                key, name
+            );
+        }
+        value.map(|value| value as u64)
+    };
+    if let Some(timeout) = positive(TIMEOUT_MS) {
+        limits.timeout = Duration::from_millis(timeout);
+    }
+    if let Some(memory) = positive(MEMORY_MB) {
+        limits.memory = (memory as usize).saturating_mul(1 << 20);
+    }
+    if let Some(max_failures) = positive(MAX_FAILURES) {
+        limits.max_failures = max_failures as u32;
+    }
+    limits
+}
+
+/// The settings of a plugin chosen by the site
+pub fn plugin_settings(manifest: &PluginManifest) -> PluginSettings {
+    PluginSettings {
+        capabilities: granted_capabilities(manifest),
+        limits: plugin_limits(&manifest.name),
+    }
+}
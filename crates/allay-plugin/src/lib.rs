@@ -2,8 +2,11 @@ pub mod config;
 pub mod manager;
 pub mod types;
 
+use allay_base::plugin_lock::{PLUGIN_LOCK_FILE, PluginLock, Verification};
 use allay_base::{config::get_allay_config, file};
+pub use allay_plugin_host::{Capabilities, LimitError, PluginLimits, PluginManifest};
 pub use manager::{Plugin, PluginManager};
+use std::path::Path;
 use tracing::{info, warn};
 
 pub fn load_plugins() {
@@ -12,14 +15,32 @@ pub fn load_plugins() {
 
     let manager = PluginManager::instance();
 
+    // without a lockfile, the plugins are loaded unchecked as before
+    let lock = match PluginLock::read() {
+        Ok(lock) => lock,
+        Err(e) => {
+            eprintln!(
+                "Failed to read {}, no plugins loaded: {}",
+                PLUGIN_LOCK_FILE, e
+            );
+            return;
+        }
+    };
+
     // find all .wasm files in the plugin directory and register them
     if let Ok(paths) = file::read_files(&dir) {
         for path in paths {
             if let Some(ext) = path.extension()
                 && ext == "wasm"
             {
+                if let Some(lock) = &lock
+                    && !verify_plugin(lock, &path)
+                {
+                    continue;
+                }
                 if let Err(e) = manager.register_plugin(&path, &file::absolute_root()) {
-                    eprintln!("Failed to register plugin from {:?}: {}", path, e)
+                    eprintln!("Failed to register plugin from {:?}: {:#}", path, e);
+                    manager.record_load_failure(&path, &e);
                 } else {
                     info!("Registered plugin from {:?}", path);
                 }
@@ -29,3 +50,33 @@ pub fn load_plugins() {
         warn!("Failed to read plugin directory {:?}", dir);
     }
 }
+
+/// Check a plugin against the lockfile, returning whether it can be loaded.
+/// A plugin not recorded or modified is refused, so that the lockfile cannot be bypassed.
+fn verify_plugin(lock: &PluginLock, path: &Path) -> bool {
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    let content = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Failed to read plugin from {:?}: {}", path, e);
+            return false;
+        }
+    };
+    match lock.verify(&name, &content) {
+        Verification::Verified => true,
+        Verification::Unlocked => {
+            eprintln!(
+                "Plugin {:?} is refused: it is not in {}, install it with `allay plugin add`",
+                path, PLUGIN_LOCK_FILE
+            );
+            false
+        }
+        Verification::Mismatch { expected, actual } => {
+            eprintln!(
+                "Plugin {:?} is refused: its SHA-256 {} does not match {} in {}",
+                path, actual, expected, PLUGIN_LOCK_FILE
+            );
+            false
+        }
+    }
+}
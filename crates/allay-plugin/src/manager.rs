@@ -1,18 +1,28 @@
+use crate::config::plugin_settings;
 use allay_base::log::NoPanicUnwrap;
-use allay_base::{lock, read};
-use allay_plugin_host::PluginHost;
+use allay_base::plugin_health::{self, PluginHealth};
+use allay_base::{lock, read, write};
+use allay_plugin_host::{PluginHost, PluginManifest, SharedHealth};
 use semver::{Version, VersionReq};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use std::sync::{Arc, Mutex, OnceLock, RwLock};
 
 pub type Plugin = Arc<Mutex<PluginHost>>;
 
 /// Manager for plugins.
-/// Handles registration and retrieval of plugins
+/// Handles registration and retrieval of plugins, and tracks their health.
+/// The plugins are found by their reported names, while their health is kept
+/// by their installed file stems, as in the lockfile and `allay plugin list`.
 #[derive(Default)]
 pub struct PluginManager {
-    plugins: RwLock<HashMap<String, Plugin>>,
+    plugins: RwLock<HashMap<String, (Plugin, SharedHealth)>>,
+    health: RwLock<BTreeMap<String, SharedHealth>>,
+}
+
+/// The installed name of a plugin, the stem of its file
+fn installed_name(wasm_path: &Path) -> String {
+    wasm_path.file_stem().unwrap_or_default().to_string_lossy().into()
 }
 
 impl PluginManager {
@@ -22,22 +32,65 @@ impl PluginManager {
     }
 
     pub fn register_plugin(&self, wasm_path: &Path, working_dir: &Path) -> anyhow::Result<()> {
-        let mut host = PluginHost::new(wasm_path, working_dir)?;
+        let mut host = PluginHost::new(wasm_path, working_dir, plugin_settings)?;
         let name = host.plugin_name()?;
+        let health = host.health();
+        write!(self.health).insert(installed_name(wasm_path), health.clone());
         let mut plugins = self.plugins.write().expect_("Failed to acquire write lock on plugins");
 
-        plugins.insert(name, Arc::new(Mutex::new(host)));
+        plugins.insert(name, (Arc::new(Mutex::new(host)), health));
         Ok(())
     }
 
+    /// Record a plugin failing to load, which is reported as disabled
+    pub fn record_load_failure(&self, wasm_path: &Path, error: &anyhow::Error) {
+        let health = PluginHealth {
+            last_error: Some(format!("{:#}", error)),
+            disabled: true,
+            ..Default::default()
+        };
+        write!(self.health).insert(installed_name(wasm_path), Arc::new(Mutex::new(health)));
+    }
+
+    /// The health of all the plugins, including the disabled ones
+    pub fn health(&self) -> BTreeMap<String, PluginHealth> {
+        let health = read!(self.health);
+        health
+            .iter()
+            .map(|(name, health)| (name.clone(), lock!(health).clone()))
+            .collect()
+    }
+
+    /// Save the health of the plugins for `allay plugin list`
+    pub fn save_health(&self) -> anyhow::Result<()> {
+        Ok(plugin_health::save_health(&self.health())?)
+    }
+
+    /// Read the name, version and requested capabilities of a plugin without registering it
+    pub fn inspect_plugin(wasm_path: &Path) -> anyhow::Result<PluginManifest> {
+        PluginHost::probe(wasm_path)
+    }
+
     pub fn get_plugin(&self, name: &str) -> Option<Plugin> {
         let plugins = read!(self.plugins);
-        plugins.get(name).cloned()
+        plugins.get(name).map(|(plugin, _)| plugin.clone())
     }
 
+    /// Get all the plugins not disabled
     pub fn plugins(&self) -> Vec<Plugin> {
+        self.named_plugins().into_iter().map(|(_, plugin)| plugin).collect()
+    }
+
+    /// Get all the plugins not disabled with their names, sorted by the names
+    pub fn named_plugins(&self) -> Vec<(String, Plugin)> {
         let plugins = read!(self.plugins);
-        plugins.values().cloned().collect()
+        let mut plugins: Vec<_> = plugins
+            .iter()
+            .filter(|(_, (_, health))| !lock!(health).disabled)
+            .map(|(name, (plugin, _))| (name.clone(), plugin.clone()))
+            .collect();
+        plugins.sort_by(|(a, _), (b, _)| a.cmp(b));
+        plugins
     }
 
     pub fn plugin_names(&self) -> Vec<String> {
@@ -48,7 +101,7 @@ impl PluginManager {
     pub fn version_match(&self, name: &str, req_version: &str) -> anyhow::Result<bool> {
         let req = VersionReq::parse(req_version)?;
         let plugins = read!(self.plugins);
-        let Some(plugin) = plugins.get(name) else {
+        let Some((plugin, _)) = plugins.get(name) else {
             return Ok(false);
         };
         let mut plugin = lock!(plugin);
@@ -57,3 +110,37 @@ impl PluginManager {
         Ok(req.matches(&version))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use allay_plugin_host::PluginLimits;
+    use allay_plugin_host::testing::{FAULTY_PLUGIN, faulty_plugin};
+
+    #[test]
+    fn test_disabled_plugin() {
+        let dir = tempfile::tempdir().unwrap();
+        // installed under another name than the one it reports
+        let path = dir.path().join("faulty-sort.wasm");
+        std::fs::rename(faulty_plugin(dir.path()), &path).unwrap();
+
+        let manager = PluginManager::default();
+        manager.register_plugin(&path, dir.path()).unwrap();
+        manager.record_load_failure(&dir.path().join("broken.wasm"), &anyhow::anyhow!("broken"));
+        assert_eq!(manager.plugins().len(), 1);
+        assert_eq!(
+            manager.health().keys().collect::<Vec<_>>(),
+            vec!["broken", "faulty-sort"]
+        );
+
+        let plugin = manager.get_plugin(FAULTY_PLUGIN).unwrap();
+        for _ in 0..PluginLimits::DEFAULT_MAX_FAILURES {
+            assert!(lock!(plugin).get_sort_order("{}", "{}").is_err());
+        }
+        assert!(manager.plugins().is_empty());
+        assert!(manager.named_plugins().is_empty());
+        let health = manager.health();
+        assert!(health["faulty-sort"].disabled && health["broken"].disabled);
+        assert_eq!(health["faulty-sort"].restarts, 2);
+    }
+}
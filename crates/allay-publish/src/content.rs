@@ -1,5 +1,6 @@
 use crate::generator::{FileGenerator, FileGeneratorOptions, FileListener};
-use allay_base::config::{get_allay_config, get_theme_config, get_theme_path};
+use allay_base::config::{custom_dirs, get_allay_config, static_dirs, template_dirs};
+use allay_base::file;
 use allay_base::template::FileKind;
 use std::path::PathBuf;
 
@@ -20,35 +21,40 @@ impl ContentGeneratorWorker {
             .dest_root(publish.clone())
             .kind(FileKind::Article)
             .map_to_html(true)
+            .localize(true)
+            .follow_url(true)
             .build();
-        let custom_generator = FileGeneratorOptions::default()
-            .src_root(get_theme_path().join(&get_theme_config().config.custom_dir))
-            .dest_root(publish.clone())
-            .kind(FileKind::Custom)
-            .map_to_html(true)
-            .build();
-        let static_generator = FileGeneratorOptions::default()
-            .src_root(get_allay_config().statics_dir.clone().into())
-            .dest_root(publish.clone())
-            .build();
-        let theme_static_generator = FileGeneratorOptions::default()
-            .src_root(get_theme_path().join(&get_theme_config().config.static_dir))
-            .dest_root(publish)
-            .build();
-        let wrapper_generator = FileGeneratorOptions::default()
-            .src_root(get_theme_path().join(&get_theme_config().config.templates.dir))
-            .kind(FileKind::Wrapper)
-            .build();
+        let mut gens = vec![article_generator];
+        gens.extend(Self::layered_generators(custom_dirs(), |options| {
+            options.dest_root(publish.clone()).kind(FileKind::Custom).map_to_html(true)
+        }));
+        gens.extend(Self::layered_generators(static_dirs(), |options| {
+            options.dest_root(publish.clone()).fingerprint(true).sass(true)
+        }));
+        gens.extend(Self::layered_generators(template_dirs(), |options| {
+            options.kind(FileKind::Wrapper)
+        }));
 
-        Self {
-            gens: vec![
-                article_generator,
-                custom_generator,
-                static_generator,
-                theme_static_generator,
-                wrapper_generator,
-            ],
-        }
+        Self { gens }
+    }
+
+    /// Create a generator for each existing directory of the layers, like the site and the themes
+    fn layered_generators(
+        layers: Vec<PathBuf>,
+        options: impl Fn(FileGeneratorOptions) -> FileGeneratorOptions,
+    ) -> Vec<FileGenerator> {
+        let layers: Vec<_> = layers
+            .into_iter()
+            .filter(|dir| file::dir_exists(file::workspace(dir)))
+            .collect();
+        layers
+            .iter()
+            .map(|dir| {
+                let generator =
+                    FileGeneratorOptions::default().src_root(dir.clone()).layers(layers.clone());
+                options(generator).build()
+            })
+            .collect()
     }
 
     /// Start the generator worker
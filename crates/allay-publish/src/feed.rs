@@ -0,0 +1,351 @@
+//! RSS and Atom feeds of the whole site, each section and each taxonomy term.
+//! Feeds are regenerated whenever the site map changes, or a page in them is compiled again.
+
+use crate::process::{absolute_url, escape_xml, rewrite_links};
+use crate::visibility::parse_date;
+use allay_base::config::{
+    FeedFormat, get_allay_config, get_feed_config, get_language_config, get_site_config,
+};
+use allay_base::data::{AllayData, AllayObject};
+use allay_base::sitemap::SiteMap;
+use allay_base::url::slugify;
+use allay_base::{file, lock};
+use allay_compiler::Compiler;
+use chrono::{DateTime, FixedOffset, Local};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use tracing::warn;
+
+/// An entry of a feed
+#[derive(Debug, Clone)]
+struct FeedItem {
+    title: String,
+    link: String,
+    date: DateTime<FixedOffset>,
+    content: String,
+}
+
+/// A feed of a list of pages
+#[derive(Debug, Clone, Default)]
+struct Feed {
+    title: String,
+    description: String,
+    /// the absolute URL of the page listing the items
+    link: String,
+    /// the absolute URL of the feed itself
+    feed_link: String,
+    items: Vec<FeedItem>,
+}
+
+/// The full contents of the pages in the feeds, so that only the changed pages
+/// are compiled again when the feeds are regenerated.
+/// A content is forgotten whenever its page is compiled again, like when a shortcode it uses changes.
+static CONTENTS: LazyLock<Mutex<HashMap<PathBuf, String>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Whether a content in the feeds is forgotten since they were last generated
+static STALE: AtomicBool = AtomicBool::new(false);
+
+/// Forget the content of a page compiled again, relative to the content directory
+fn forget_content_in(content_dir: &Path, src: &Path) {
+    let Ok(path) = src.strip_prefix(content_dir) else {
+        return;
+    };
+    if lock!(CONTENTS).remove(path).is_some() {
+        STALE.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Forget the content of a page compiled again, so that the feeds get its new content
+pub(crate) fn forget_content(src: &Path) {
+    forget_content_in(&file::workspace(&get_allay_config().content_dir), src);
+}
+
+/// Generate the feeds again if a content in them is forgotten
+pub(crate) fn refresh_feeds() {
+    if STALE.load(Ordering::SeqCst) {
+        write_feeds();
+    }
+}
+
+/// A page in the site map to be put into feeds
+struct FeedPage {
+    path: PathBuf,
+    lastmod: u64,
+    meta: Arc<AllayObject>,
+}
+
+impl FeedPage {
+    fn str_field(&self, key: &str) -> Option<String> {
+        self.meta.get(key).and_then(|data| data.as_str().ok()).map(String::from)
+    }
+
+    /// The date of the page from the front-matter, or the last modified time
+    fn date(&self) -> DateTime<FixedOffset> {
+        ["date", "publish_date"]
+            .iter()
+            .filter_map(|key| self.meta.get(*key))
+            .find_map(|data| match data.as_ref() {
+                AllayData::String(text) => parse_date(text),
+                // a TOML datetime
+                AllayData::Object(obj) => {
+                    obj.values().next().and_then(|v| parse_date(&v.to_string()))
+                }
+                _ => None,
+            })
+            .or_else(|| DateTime::from_timestamp(self.lastmod as i64, 0).map(|date| date.into()))
+            .unwrap_or_else(|| Local::now().fixed_offset())
+    }
+
+    /// All the terms of the page under a taxonomy key
+    fn terms(&self, key: &str) -> Vec<String> {
+        match self.meta.get(key).map(AsRef::as_ref) {
+            None | Some(AllayData::Null) => vec![],
+            Some(AllayData::List(list)) => list.iter().map(|term| term.to_string()).collect(),
+            Some(data) => vec![data.to_string()],
+        }
+    }
+}
+
+fn render_rss(feed: &Feed) -> String {
+    let mut items = String::new();
+    for item in feed.items.iter() {
+        items.push_str(&format!(
+            "<item><title>{}</title><link>{}</link><guid>{}</guid><pubDate>{}</pubDate><description>{}</description></item>",
+            escape_xml(&item.title),
+            escape_xml(&item.link),
+            escape_xml(&item.link),
+            item.date.to_rfc2822(),
+            escape_xml(&item.content),
+        ));
+    }
+    let build_date = feed.items.first().map(|item| item.date.to_rfc2822()).unwrap_or_default();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom"><channel><title>{}</title><link>{}</link><description>{}</description><atom:link href="{}" rel="self" type="application/rss+xml"/><lastBuildDate>{}</lastBuildDate>{}</channel></rss>"#,
+        escape_xml(&feed.title),
+        escape_xml(&feed.link),
+        escape_xml(&feed.description),
+        escape_xml(&feed.feed_link),
+        build_date,
+        items,
+    )
+}
+
+fn render_atom(feed: &Feed) -> String {
+    let mut entries = String::new();
+    for item in feed.items.iter() {
+        entries.push_str(&format!(
+            r#"<entry><title>{}</title><link href="{}"/><id>{}</id><updated>{}</updated><content type="html">{}</content></entry>"#,
+            escape_xml(&item.title),
+            escape_xml(&item.link),
+            escape_xml(&item.link),
+            item.date.to_rfc3339(),
+            escape_xml(&item.content),
+        ));
+    }
+    let updated = feed.items.first().map_or(Local::now().fixed_offset(), |item| item.date);
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><feed xmlns="http://www.w3.org/2005/Atom"><title>{}</title><subtitle>{}</subtitle><link href="{}"/><link href="{}" rel="self"/><id>{}</id><updated>{}</updated>{}</feed>"#,
+        escape_xml(&feed.title),
+        escape_xml(&feed.description),
+        escape_xml(&feed.link),
+        escape_xml(&feed.feed_link),
+        escape_xml(&feed.feed_link),
+        updated.to_rfc3339(),
+        entries,
+    )
+}
+
+/// Group the pages into feeds by their output directories, like `posts` or `tags/rust`.
+/// Pages in a language other than the default one are grouped under its prefix, like `zh/posts`.
+fn group_pages<'a>(
+    pages: &'a [FeedPage],
+    site_title: &str,
+) -> BTreeMap<PathBuf, (String, Vec<&'a FeedPage>)> {
+    let config = get_feed_config();
+    let languages = get_language_config();
+
+    let mut groups: BTreeMap<PathBuf, (String, Vec<&FeedPage>)> = BTreeMap::new();
+    let mut add = |dir: PathBuf, title: String, page| {
+        groups.entry(dir).or_insert_with(|| (title, vec![])).1.push(page);
+    };
+
+    for page in pages {
+        let (lang, path) = languages.split_language(&page.path);
+        let lang = page.str_field("lang").unwrap_or(lang);
+        let root = if lang == languages.default_language {
+            PathBuf::new()
+        } else {
+            PathBuf::from(&lang)
+        };
+
+        add(root.clone(), site_title.into(), page);
+
+        if config.sections
+            && path.components().count() > 1
+            && let Some(section) = path.components().next()
+        {
+            let section = section.as_os_str().to_string_lossy();
+            add(
+                root.join(&*section),
+                format!("{} - {}", site_title, section),
+                page,
+            );
+        }
+
+        for key in config.taxonomies.iter() {
+            for term in page.terms(key) {
+                let dir = root.join(key).join(slugify(&term));
+                add(dir, format!("{} - {}", site_title, term), page);
+            }
+        }
+    }
+    groups
+}
+
+/// Generate all the feeds from the current site map
+pub fn write_feeds() {
+    let config = get_feed_config();
+    if !config.enabled || config.formats.is_empty() {
+        return;
+    }
+
+    let site = get_site_config();
+    let site_str =
+        |key: &str| site.get(key).and_then(|d| d.as_str().ok()).unwrap_or_default().to_string();
+    let base_url = site_str("base_url");
+    let site_title = site_str("title");
+    let description = site_str("description");
+
+    // collect the pages first to release the lock of the site map before compiling
+    let mut pages: Vec<_> = SiteMap::read()
+        .urlset
+        .iter()
+        .map(|(path, entry)| FeedPage {
+            path: path.clone(),
+            lastmod: entry.lastmod,
+            meta: entry.meta(),
+        })
+        .filter(|page| !page.meta.get("hidden").is_some_and(|d| d.as_bool().unwrap_or(false)))
+        .collect();
+    pages.sort_by(|a, b| b.date().cmp(&a.date()).then_with(|| a.path.cmp(&b.path)));
+
+    let content_dir = file::workspace(&get_allay_config().content_dir);
+    let publish_dir = file::workspace(&get_allay_config().publish_dir);
+    let mut contents = lock!(CONTENTS);
+    STALE.store(false, Ordering::SeqCst);
+    // forget the pages no longer in the site map
+    let paths: HashSet<_> = pages.iter().map(|page| &page.path).collect();
+    contents.retain(|path, _| paths.contains(path));
+    let mut content_of = |page: &FeedPage| -> String {
+        let summary =
+            || page.str_field("summary").or(page.str_field("description")).unwrap_or_default();
+        if !config.full_content {
+            return summary();
+        }
+        if let Some(content) = contents.get(&page.path) {
+            return content.clone();
+        }
+        match Compiler::content(content_dir.join(&page.path)) {
+            Ok(html) => {
+                let content = rewrite_links(html, &base_url);
+                contents.insert(page.path.clone(), content.clone());
+                content
+            }
+            Err(e) => {
+                warn!("Failed to compile {:?} for feeds: {}", page.path, e);
+                summary()
+            }
+        }
+    };
+
+    for (dir, (title, group)) in group_pages(&pages, &site_title) {
+        let items = group
+            .into_iter()
+            .take(config.limit)
+            .map(|page| FeedItem {
+                title: page.str_field("title").unwrap_or_default(),
+                link: absolute_url(&base_url, &page.str_field("url").unwrap_or_default()),
+                date: page.date(),
+                content: content_of(page),
+            })
+            .collect();
+        let mut feed = Feed {
+            title,
+            description: description.clone(),
+            link: absolute_url(&base_url, &format!("{}/", dir.to_string_lossy())),
+            items,
+            ..Default::default()
+        };
+
+        for format in config.formats.iter() {
+            let path = dir.join(format.file_name());
+            feed.feed_link = absolute_url(&base_url, &path.to_string_lossy());
+            let xml = match format {
+                FeedFormat::Rss => render_rss(&feed),
+                FeedFormat::Atom => render_atom(&feed),
+            };
+            if let Err(e) = file::write_file(publish_dir.join(&path), xml) {
+                warn!("Failed to write feed {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed() -> Feed {
+        Feed {
+            title: "Tom & Jerry".into(),
+            description: "A <cartoon>".into(),
+            link: "https://example.com/".into(),
+            feed_link: "https://example.com/rss.xml".into(),
+            items: vec![FeedItem {
+                title: "\"Hello\"".into(),
+                link: "https://example.com/posts/hello".into(),
+                date: DateTime::parse_from_rfc3339("2025-01-01T08:00:00+08:00").unwrap(),
+                content: "<p>Hi</p>".into(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_forget_content() {
+        let content_dir = Path::new("./contents");
+        lock!(CONTENTS).insert("posts/a.md".into(), "<p>A</p>".into());
+        STALE.store(false, Ordering::SeqCst);
+
+        // the pages outside of the contents, or not in the feeds, are ignored
+        forget_content_in(content_dir, Path::new("./templates/posts/a.md"));
+        forget_content_in(content_dir, Path::new("./contents/posts/b.md"));
+        assert!(!STALE.load(Ordering::SeqCst));
+
+        forget_content_in(content_dir, Path::new("./contents/posts/a.md"));
+        assert!(!lock!(CONTENTS).contains_key(Path::new("posts/a.md")));
+        assert!(STALE.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_render_rss() {
+        let rss = render_rss(&feed());
+        assert!(rss.contains("<title>Tom &amp; Jerry</title>"));
+        assert!(rss.contains("<description>A &lt;cartoon&gt;</description>"));
+        assert!(rss.contains("<title>&quot;Hello&quot;</title>"));
+        assert!(rss.contains("<pubDate>Wed, 1 Jan 2025 08:00:00 +0800</pubDate>"));
+        assert!(rss.contains("<description>&lt;p&gt;Hi&lt;/p&gt;</description>"));
+    }
+
+    #[test]
+    fn test_render_atom() {
+        let atom = render_atom(&feed());
+        assert!(atom.contains(r#"<link href="https://example.com/rss.xml" rel="self"/>"#));
+        assert!(atom.contains("<updated>2025-01-01T08:00:00+08:00</updated>"));
+        assert!(atom.contains(r#"<content type="html">&lt;p&gt;Hi&lt;/p&gt;</content>"#));
+    }
+}
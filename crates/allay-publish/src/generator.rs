@@ -1,12 +1,22 @@
+use crate::feed;
+use crate::minify;
 use crate::process::postprocess;
+use crate::sass;
+use crate::visibility::is_visible;
+use allay_base::asset::AssetManifest;
+use allay_base::config::get_language_config;
 use allay_base::file::{self, FileResult};
+use allay_base::image::ImageProcessor;
 use allay_base::lock;
+use allay_base::report::BuildStats;
 use allay_base::template::{FileKind, TemplateKind};
-use allay_compiler::Compiler;
+use allay_base::url::AllayUrlPath;
+use allay_compiler::{CompileOutput, CompileResult, Compiler, match_meta};
 use notify::event::{EventKind, ModifyKind, RenameMode};
 use notify_debouncer_full::{DebounceEventResult, DebouncedEvent, new_debouncer};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::sync::{LazyLock, Mutex};
 use std::thread;
@@ -39,6 +49,12 @@ pub trait FileListener: Send + Sync {
 
     /// and triggering the `on_create` event for each file except those that satisfy the `skip` condition.
     fn cold_start(&self) {
+        self.create_all();
+    }
+
+    /// Trigger the `on_create` event for each file in the root directory.
+    /// Do not override this function unless necessary.
+    fn create_all(&self) {
         let root = file::absolute_workspace(self.root());
         for entry in WalkDir::new(&root).follow_links(true) {
             match entry {
@@ -173,6 +189,9 @@ pub trait FileMapper {
 /// Note: all path parameters here are both the path relative to the workspace root.
 pub struct FileGenerator {
     options: FileGeneratorOptions,
+    /// whether the files are being generated for the first time, when every page is compiled
+    /// anyway, so the pages depending on a file need not compiling again
+    cold_starting: AtomicBool,
 }
 
 /// Options for the [`FileGenerator`].
@@ -182,6 +201,11 @@ pub struct FileGeneratorOptions {
     dest_root: PathBuf,
     kind: FileKind,
     map_to_html: bool,
+    localize: bool,
+    follow_url: bool,
+    fingerprint: bool,
+    sass: bool,
+    layers: Vec<PathBuf>,
 }
 
 /// Global compiler instance for all file generators
@@ -192,87 +216,457 @@ static COMPILER: LazyLock<Mutex<Compiler<String>>> =
 static FILE_MAP: LazyLock<Mutex<HashMap<PathBuf, PathBuf>>> =
     LazyLock::new(|| Mutex::new(HashMap::new()));
 
+/// The other output formats written for the articles, like "foo.md" -> ["foo.json"]
+static ALTERNATES: LazyLock<Mutex<HashMap<PathBuf, HashSet<PathBuf>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The source files failed in their latest compilation, with the error messages
+static FAILURES: LazyLock<Mutex<HashMap<PathBuf, String>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Get all the source files failed to compile currently, sorted by path
+pub fn failures() -> Vec<(PathBuf, String)> {
+    let mut failures: Vec<_> = lock!(FAILURES).clone().into_iter().collect();
+    failures.sort();
+    failures
+}
+
 impl FileGenerator {
     /// Create a new file generator.
     pub fn new(options: FileGeneratorOptions) -> Self {
-        Self { options }
+        Self {
+            options,
+            cold_starting: AtomicBool::new(false),
+        }
+    }
+
+    fn is_cold_starting(&self) -> bool {
+        self.cold_starting.load(Ordering::Relaxed)
     }
 
     /// Determine whether the file should not be compiled.
+    /// All the wrapper files are templates, like `page.json` of an output format.
     fn no_compile(&self, src: &PathBuf) -> bool {
-        matches!(self.options.kind, FileKind::Static)
-            || !TemplateKind::from_filename(src).is_template()
+        match self.options.kind {
+            FileKind::Static => true,
+            FileKind::Wrapper => false,
+            _ => !TemplateKind::from_filename(src).is_template(),
+        }
+    }
+
+    /// The same file in the other layers, split into the ones before and after this generator
+    fn layered(&self, src: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+        let Ok(path) = src.strip_prefix(file::workspace(self.src_root())) else {
+            return Default::default();
+        };
+        let layers = &self.options.layers;
+        let own = layers.iter().position(|root| *root == self.options.src_root);
+        let (before, after) = layers.split_at(own.map_or(0, |own| own + 1));
+        let files = |roots: &[PathBuf]| -> Vec<PathBuf> {
+            roots
+                .iter()
+                .filter(|root| **root != self.options.src_root)
+                .map(|root| file::workspace(root).join(path))
+                .collect()
+        };
+        (files(before), files(after))
+    }
+
+    /// Determine whether the file is overridden by the same one in a layer before
+    fn overridden(&self, src: &Path) -> bool {
+        self.layered(src).0.iter().any(|file| file.is_file())
+    }
+
+    /// Determine whether the file shadows the same one in a layer after, like a site template
+    /// overriding the theme one, or the theme one falls back to it
+    fn shadowing(&self, src: &Path) -> bool {
+        self.layered(src).1.iter().any(|file| file.is_file())
+    }
+
+    /// Compile the pages using a template again, since it overrides the same one in the other
+    /// layers or falls back to it, like a site template overriding the theme one
+    fn relayer_template(&self, src: &Path) -> FileResult<()> {
+        let (before, after) = self.layered(src);
+        let mut pages = vec![];
+        for template in before.iter().chain([&src.to_path_buf()]).chain(after.iter()) {
+            pages.extend(lock!(COMPILER).forget_dependents(template));
+        }
+        for page in pages {
+            let kind = match TemplateKind::from_filename(&page).is_md() {
+                true => FileKind::Article,
+                false => FileKind::Custom,
+            };
+            let res = lock!(COMPILER).compile_file(&page, &kind);
+            let dest = lock!(FILE_MAP).get(&page).cloned();
+            if let Some(dest) = dest {
+                self.write_compiled(&page, &dest, res)?;
+            }
+        }
+        self.refresh()
     }
 
     /// What to do when a file is created.
     fn created(&self, src: PathBuf, dest: PathBuf) -> FileResult<()> {
+        if self.overridden(&src) {
+            return Ok(());
+        }
         if matches!(self.options.kind, FileKind::Wrapper) {
-            return Ok(()); // wrapper files are not generated directly
+            // wrapper files are not generated directly, and all of them exist before the pages
+            // are compiled in the initial generation
+            if !self.is_cold_starting() && self.shadowing(&src) {
+                return self.relayer_template(&src);
+            }
+            return Ok(());
+        }
+        // the same file in the layers after is not published any more
+        for file in self.layered(&src).1 {
+            lock!(COMPILER).remove(&file);
+            lock!(FILE_MAP).remove(&file);
+            lock!(FAILURES).remove(&file);
+        }
+        if self.is_sass(&src) {
+            return self.update_sass(&src, &dest, true);
         }
         if self.no_compile(&src) {
-            return file::copy(src, dest);
+            minify::copy(&src, &dest)?;
+            BuildStats::get_instance().record_page("static", &dest);
+            self.update_image(&src);
+            return self.update_fingerprint(&dest);
         }
 
-        lock!(FILE_MAP).insert(src.clone(), dest.clone());
+        if self.skip_invisible(&src, &dest)? {
+            return Ok(());
+        }
 
-        match lock!(COMPILER).compile_file(&src, &self.options.kind) {
-            Ok(output) => Self::write_with_wrapper(&dest, &output.html)?,
-            Err(e) => warn!("Failed to compile {:?}: {}", src, e),
+        Self::move_dest(&src, &dest)?;
+
+        let res = lock!(COMPILER).compile_file(&src, &self.options.kind);
+        self.write_compiled(&src, &dest, res)?;
+        self.update_referring(&src)?;
+        self.refresh()
+    }
+
+    /// Compile the articles referring to an article by internal references again,
+    /// like `[text](@/posts/foo.md)`, since its URL or anchors may change
+    fn update_referring(&self, src: &Path) -> FileResult<()> {
+        if !matches!(self.options.kind, FileKind::Article) || self.is_cold_starting() {
+            return Ok(());
         }
-        Self::refresh()
+        let articles = lock!(COMPILER).referring(src);
+        for article in articles {
+            // the articles not published, like drafts, are skipped
+            let Some(dest) = lock!(FILE_MAP).get(&article).cloned() else {
+                continue;
+            };
+            lock!(COMPILER).modify(&article);
+            let res = lock!(COMPILER).compile_file(&article, &FileKind::Article);
+            self.write_compiled(&article, &dest, res)?;
+        }
+        Ok(())
+    }
+
+    /// Record the destination of a source file, removing the previous one if it is moved,
+    /// like when the permalink of an article changes
+    fn move_dest(src: &Path, dest: &Path) -> FileResult<()> {
+        match lock!(FILE_MAP).insert(src.into(), dest.into()) {
+            Some(old) if old != dest => file::remove(old),
+            _ => Ok(()),
+        }
+    }
+
+    /// Remove an article if it is invisible now, like a draft, returning whether it is skipped.
+    /// Articles with invalid visibility are also skipped and recorded as failures.
+    fn skip_invisible(&self, src: &Path, dest: &Path) -> FileResult<bool> {
+        if !matches!(self.options.kind, FileKind::Article) {
+            return Ok(false);
+        }
+        // leave the errors of the metadata to the compilation
+        let Ok(meta) = match_meta(src) else {
+            return Ok(false);
+        };
+
+        let visible = is_visible(&meta);
+        if visible.as_ref().is_ok_and(|visible| *visible) {
+            return Ok(false);
+        }
+
+        self.removed(src.into(), dest.into())?;
+        if let Err(e) = visible {
+            warn!("Failed to check the visibility of {:?}: {}", src, e);
+            lock!(FAILURES).insert(src.into(), e.to_string());
+        }
+        Ok(true)
     }
 
     /// What to do when a file is removed.
+    /// The same file in the layers after is published instead if any, like the theme one.
     fn removed(&self, src: PathBuf, dest: PathBuf) -> FileResult<()> {
-        if self.no_compile(&src) {
-            return file::remove(dest);
+        if self.overridden(&src) {
+            return Ok(());
         }
+        self.unpublish(&src, &dest)?;
+        if matches!(self.options.kind, FileKind::Wrapper) {
+            return Ok(()); // the templates are looked up in all the layers
+        }
+        match self.layered(&src).1.into_iter().find(|file| file.is_file()) {
+            Some(fallback) => self.created(fallback, dest),
+            None => Ok(()),
+        }
+    }
 
-        lock!(COMPILER).remove(&src);
-        if matches!(&self.options.kind, FileKind::Wrapper) {
-            return Self::refresh();
+    /// Remove the published file of a source file.
+    fn unpublish(&self, src: &Path, dest: &Path) -> FileResult<()> {
+        if self.is_sass(src) {
+            return self.remove_sass(src, dest);
+        }
+        if self.no_compile(&src.to_path_buf()) {
+            file::remove(dest)?;
+            return self.update_fingerprint(dest);
         }
 
-        lock!(FILE_MAP).remove(&src);
-        Self::refresh()?;
+        if matches!(&self.options.kind, FileKind::Wrapper) {
+            if self.shadowing(src) {
+                self.relayer_template(src)?;
+                lock!(COMPILER).remove(src);
+                return Ok(());
+            }
+            lock!(COMPILER).remove(src);
+            return self.refresh();
+        }
+        lock!(COMPILER).remove(src);
+
+        // the destination may not be derived from the removed file, like its permalink
+        let dest = lock!(FILE_MAP).remove(src).unwrap_or(dest.into());
+        lock!(FAILURES).remove(src);
+        self.update_referring(src)?;
+        self.refresh()?;
+        for alternate in lock!(ALTERNATES).remove(src).unwrap_or_default() {
+            file::remove(alternate)?;
+        }
         file::remove(dest)
     }
 
     /// What to do when a file is modified.
     fn modified(&self, src: PathBuf, dest: PathBuf) -> FileResult<()> {
+        if self.overridden(&src) {
+            return Ok(());
+        }
+        if self.is_sass(&src) {
+            return self.update_sass(&src, &dest, false);
+        }
         if self.no_compile(&src) {
-            return file::copy(src, dest);
+            minify::copy(&src, &dest)?;
+            BuildStats::get_instance().record_page("static", &dest);
+            self.update_image(&src);
+            return self.update_fingerprint(&dest);
         }
         lock!(COMPILER).modify(&src);
         if matches!(self.options.kind, FileKind::Wrapper) {
-            return Self::refresh();
+            return self.refresh();
+        }
+        if self.skip_invisible(&src, &dest)? {
+            return Ok(());
+        }
+        // the file may be invisible before, like a draft
+        Self::move_dest(&src, &dest)?;
+
+        let res = lock!(COMPILER).compile_file(&src, &self.options.kind);
+        self.write_compiled(&src, &dest, res)?;
+        self.update_referring(&src)?;
+        self.refresh()
+    }
+
+    /// Publish the variants of an image again if they are requested before
+    fn update_image(&self, src: &Path) {
+        let Ok(path) = src.strip_prefix(file::workspace(self.src_root())) else {
+            return;
+        };
+        if ImageProcessor::is_image(path)
+            && let Err(e) = ImageProcessor::get_instance().refresh(self.path_mapping(path))
+        {
+            warn!("Failed to process image {:?}: {}", src, e);
+        }
+    }
+
+    /// Determine whether the file is a Sass stylesheet to compile.
+    fn is_sass(&self, src: &Path) -> bool {
+        self.options.sass && sass::is_sass(src)
+    }
+
+    /// Compile a Sass stylesheet unless it is a partial, and recompile the stylesheets loading it.
+    /// The compile errors are recorded as failures like the templates.
+    fn update_sass(&self, src: &Path, dest: &Path, created: bool) -> FileResult<()> {
+        if !sass::is_partial(src) {
+            self.compile_sass(src, dest)?;
+        }
+        // all the files exist in the initial generation, so the others need no recompilation
+        if self.is_cold_starting() {
+            return Ok(());
+        }
+        for (src, dest) in sass::affected(src, created) {
+            self.compile_sass(&src, &dest)?;
+        }
+        Ok(())
+    }
+
+    fn compile_sass(&self, src: &Path, dest: &Path) -> FileResult<()> {
+        match sass::compile(src, dest) {
+            Ok(css) => {
+                lock!(FAILURES).remove(src);
+                minify::write_asset(dest, css)?;
+                BuildStats::get_instance().record_page("stylesheet", dest);
+            }
+            Err(e) => {
+                warn!("Failed to compile {:?}: {}", src, e);
+                lock!(FAILURES).insert(src.into(), e);
+            }
+        }
+        self.update_fingerprint(dest)
+    }
+
+    fn remove_sass(&self, src: &Path, dest: &Path) -> FileResult<()> {
+        if !sass::is_partial(src) {
+            sass::forget(src);
+            lock!(FAILURES).remove(src);
+            file::remove(dest)?;
+            self.update_fingerprint(dest)?;
+        }
+        // the stylesheets loading it fail, or load the file of the same name in the theme
+        for (src, dest) in sass::affected(src, false) {
+            self.compile_sass(&src, &dest)?;
+        }
+        Ok(())
+    }
+
+    /// Publish the fingerprinted copy of a static asset or remove the stale one,
+    /// and recompile the pages which may refer to it by `asset()`
+    fn update_fingerprint(&self, dest: &Path) -> FileResult<()> {
+        if !self.options.fingerprint {
+            return Ok(());
         }
-        match lock!(COMPILER).compile_file(&src, &self.options.kind) {
-            Ok(output) => Self::write_with_wrapper(&dest, &output.html)?,
-            Err(e) => warn!("Failed to compile {:?}: {}", src, e),
+        let Ok(path) = dest.strip_prefix(file::workspace(self.dest_root())) else {
+            return Ok(());
+        };
+        if !AssetManifest::should_fingerprint(path) {
+            return Ok(());
         }
-        Self::refresh()
+
+        let manifest = AssetManifest::get_instance();
+        let publish_dir = file::workspace(self.dest_root());
+        let stale = manifest.lookup(path);
+        // the asset may still exist in another static directory, like the theme
+        let current = manifest.fingerprint(path).ok();
+        if let Some(stale) = stale
+            && current.as_ref().is_none_or(|asset| asset.path != stale.path)
+        {
+            file::remove(publish_dir.join(stale.path))?;
+        }
+        // copy the published file if any, which may be minified
+        let published = publish_dir.join(path);
+        match current {
+            Some(asset) if published.is_file() => {
+                file::copy(published, publish_dir.join(asset.path))?
+            }
+            Some(asset) => file::copy(asset.source, publish_dir.join(asset.path))?,
+            None => manifest.remove(path),
+        }
+
+        manifest.dump();
+        self.refresh()
+    }
+
+    fn write_with_wrapper(dest: &PathBuf, output: &CompileOutput) -> FileResult<()> {
+        file::write_file(dest, postprocess(&output.html, &output.lang))
     }
 
-    fn write_with_wrapper(dest: &PathBuf, html: &str) -> FileResult<()> {
-        file::write_file(dest, postprocess(html))
+    /// Write the other output formats next to the HTML, like `foo.json` for `foo.html`,
+    /// and remove the ones no longer in the outputs
+    fn write_alternates(src: &Path, dest: &Path, output: &CompileOutput) -> FileResult<()> {
+        let mut written = HashSet::new();
+        for (format, text) in output.formats.iter() {
+            let path = dest.with_extension(format);
+            file::write_file(&path, text)?;
+            BuildStats::get_instance().record_page(format, &path);
+            written.insert(path);
+        }
+
+        let mut alternates = lock!(ALTERNATES);
+        let last = alternates.remove(src).unwrap_or_default();
+        for path in last.difference(&written) {
+            file::remove(path)?;
+        }
+        if !written.is_empty() {
+            alternates.insert(src.into(), written);
+        }
+        Ok(())
+    }
+
+    /// Write the compiled output, or record the failure of the source file
+    fn write_compiled(
+        &self,
+        src: &Path,
+        dest: &PathBuf,
+        res: CompileResult<CompileOutput>,
+    ) -> FileResult<()> {
+        match res {
+            Ok(output) => {
+                lock!(FAILURES).remove(src);
+                if !self.is_cold_starting() {
+                    feed::forget_content(src);
+                }
+                // the custom pages are compiled from the templates
+                let kind = match TemplateKind::from_filename(src).is_md() {
+                    true => "article",
+                    false => "custom",
+                };
+                BuildStats::get_instance().record_page(kind, src);
+                Self::write_alternates(src, dest, &output)?;
+                Self::write_with_wrapper(dest, &output)
+            }
+            Err(e) => {
+                warn!("Failed to compile {:?}: {}", src, e);
+                lock!(FAILURES).insert(src.into(), e.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    /// Generate the feeds again if the pages in them are compiled again by a change,
+    /// like a shortcode or a wrapper they use, which does not change the site map
+    fn refresh_feeds(&self) {
+        if !self.is_cold_starting() {
+            feed::refresh_feeds();
+        }
     }
 
     /// handling the recompilation of all affected files
-    fn refresh() -> FileResult<()> {
+    fn refresh(&self) -> FileResult<()> {
         let pages = lock!(COMPILER).refresh_pages();
         for (path, res) in pages {
             if let Some(dest) = lock!(FILE_MAP).get(&path) {
-                match res {
-                    Ok(output) => Self::write_with_wrapper(dest, &output.html)?,
-                    Err(e) => warn!("Failed to recompile {:?}: {}", path, e),
-                }
+                self.write_compiled(&path, dest, res)?;
             }
         }
         Ok(())
     }
 }
 
+impl FileGenerator {
+    /// Get the published path of an article from its `url`, like the permalink,
+    /// or `None` if the metadata cannot be read or the URL is external
+    fn url_path(&self, src: &Path) -> Option<PathBuf> {
+        if !TemplateKind::from_filename(src).is_md() {
+            return None;
+        }
+        let meta = match_meta(self.src_workspace(src)).ok()?;
+        let url = meta.get("url")?.as_str().ok()?.to_string();
+        if url.contains("://") {
+            return None;
+        }
+        Some(AllayUrlPath::from_url(&url).file_path())
+    }
+}
+
 impl FileMapper for FileGenerator {
     fn src_root(&self) -> PathBuf {
         self.options.src_root.clone()
@@ -283,15 +677,23 @@ impl FileMapper for FileGenerator {
     }
 
     fn path_mapping(&self, src: &Path) -> PathBuf {
-        if self.options.map_to_html {
-            let mut res = src.to_path_buf();
-            if TemplateKind::from_filename(src).is_md() {
-                res.set_extension(TemplateKind::Html.extension());
-            }
-            res
+        if self.options.follow_url
+            && let Some(path) = self.url_path(src)
+        {
+            return path;
+        }
+        if self.is_sass(src) {
+            return src.with_extension("css");
+        }
+        let mut res = if self.options.localize {
+            get_language_config().localize(src)
         } else {
             src.into()
+        };
+        if self.options.map_to_html && TemplateKind::from_filename(src).is_md() {
+            res.set_extension(TemplateKind::Html.extension());
         }
+        res
     }
 }
 
@@ -300,13 +702,21 @@ impl FileListener for FileGenerator {
         self.src_root()
     }
 
+    fn cold_start(&self) {
+        self.cold_starting.store(true, Ordering::Relaxed);
+        self.create_all();
+        self.cold_starting.store(false, Ordering::Relaxed);
+    }
+
     fn on_create(&self, path: PathBuf) -> FileResult<()> {
         let src = self.src_workspace(&path);
         let dest = self.dest_workspace(&path);
         if src.is_dir() {
             file::create_dir_if_not_exists(dest)
         } else {
-            self.created(src, dest)
+            self.created(src, dest)?;
+            self.refresh_feeds();
+            Ok(())
         }
     }
 
@@ -316,7 +726,9 @@ impl FileListener for FileGenerator {
         if src.is_dir() {
             file::remove_dir_recursively(&dest)
         } else {
-            self.removed(src, dest)
+            self.removed(src, dest)?;
+            self.refresh_feeds();
+            Ok(())
         }
     }
 
@@ -326,6 +738,7 @@ impl FileListener for FileGenerator {
         if src.is_file() {
             let dest = self.dest_workspace(&path);
             self.modified(src, dest)?;
+            self.refresh_feeds();
         }
         Ok(())
     }
@@ -352,6 +765,41 @@ impl FileGeneratorOptions {
         self
     }
 
+    /// Also publish the static assets with content hashes in their names,
+    /// if enabled by the `[assets]` config
+    pub fn fingerprint(mut self, fingerprint: bool) -> Self {
+        self.fingerprint = fingerprint;
+        self
+    }
+
+    /// Compile the Sass stylesheets into CSS instead of copying them
+    pub fn sass(mut self, sass: bool) -> Self {
+        self.sass = sass;
+        self
+    }
+
+    /// Publish the articles at their URLs in the metadata, so that the permalinks and
+    /// the custom `url`s agree with the files
+    pub fn follow_url(mut self, follow_url: bool) -> Self {
+        self.follow_url = follow_url;
+        self
+    }
+
+    /// Publish the files with language suffixes under their language prefixes,
+    /// like `foo.zh.md` -> `zh/foo.html`
+    pub fn localize(mut self, localize: bool) -> Self {
+        self.localize = localize;
+        self
+    }
+
+    /// Share the relative paths with the other source roots, like the static directories of
+    /// the site and the themes, listed from the highest priority together with its own.
+    /// A file is not published if a root before has the same one.
+    pub fn layers(mut self, layers: Vec<PathBuf>) -> Self {
+        self.layers = layers;
+        self
+    }
+
     pub fn build(self) -> FileGenerator {
         FileGenerator::new(self)
     }
@@ -1,15 +1,21 @@
 mod content;
+mod feed;
 mod generator;
+mod minify;
 #[cfg(feature = "plugin")]
 mod plugin;
 mod process;
+mod redirect;
+mod sass;
 mod sitemap;
+mod visibility;
 
 use content::ContentGeneratorWorker;
 use generator::FileListener;
 #[cfg(feature = "plugin")]
 use plugin::PluginListener;
 use sitemap::SiteMapWorker;
+use std::path::PathBuf;
 use std::sync::OnceLock;
 
 /// Start the publishing workers.
@@ -27,9 +33,14 @@ pub fn start() {
 }
 
 /// Generate all files once.
-pub fn generate_once() {
+/// Returns the source files failed to compile together with their errors,
+/// and the conflicts of the aliases and redirects.
+pub fn generate_once() -> Vec<(PathBuf, String)> {
     SiteMapWorker::create().cold_start();
     ContentGeneratorWorker::create().generate_once();
     #[cfg(feature = "plugin")]
     PluginListener.cold_start();
+    let mut failures = generator::failures();
+    failures.extend(redirect::conflicts());
+    failures
 }
@@ -0,0 +1,147 @@
+//! Minification of the published HTML pages and the CSS and JS assets,
+//! which is only active for `allay build` in production, see [`MinifyConfig::is_active`].
+//! Anything failing to minify is published as it is.
+
+use allay_base::config::{MinifyConfig, get_minify_config};
+use allay_base::file::{self, FileResult};
+use lightningcss::stylesheet::{MinifyOptions, ParserOptions, PrinterOptions, StyleSheet};
+use minify_js::{Session, TopLevelMode};
+use std::path::Path;
+
+/// The markers of the conditional comments like `<!--[if IE]>` and `<!--<![endif]-->`,
+/// which are kept by disguising them as SSI comments, the only comments kept by the minifier
+const CONDITIONAL_COMMENTS: [(&str, &str); 2] = [
+    ("<!--[if", "<!--#allay[if"),
+    ("<!--<![endif]", "<!--#allay<![endif]"),
+];
+
+/// Minify an HTML page, together with its inline styles and scripts if enabled.
+/// The whitespace in `<pre>` and `<textarea>` is kept by the minifier.
+pub fn minify_html(html: &str, config: &MinifyConfig) -> String {
+    let mut html = html.to_string();
+    for (marker, disguise) in CONDITIONAL_COMMENTS {
+        html = html.replace(marker, disguise);
+    }
+
+    // keep the output valid HTML, which the minifier does not by default
+    let cfg = minify_html::Cfg {
+        keep_closing_tags: true,
+        keep_html_and_head_opening_tags: true,
+        keep_ssi_comments: true,
+        minify_css: config.css,
+        // inline scripts failing to parse are kept as they are by the minifier
+        minify_js: config.js,
+        ..minify_html::Cfg::spec_compliant()
+    };
+    let mut html = String::from_utf8_lossy(&minify_html::minify(html.as_bytes(), &cfg)).to_string();
+
+    for (marker, disguise) in CONDITIONAL_COMMENTS {
+        html = html.replace(disguise, marker);
+    }
+    html
+}
+
+/// Minify a stylesheet, or `None` if it fails to parse
+pub fn minify_css(css: &str) -> Option<String> {
+    let mut sheet = StyleSheet::parse(css, ParserOptions::default()).ok()?;
+    sheet.minify(MinifyOptions::default()).ok()?;
+    let printer = PrinterOptions {
+        minify: true,
+        ..PrinterOptions::default()
+    };
+    sheet.to_css(printer).ok().map(|output| output.code)
+}
+
+/// Minify a script, or `None` if it fails to parse
+pub fn minify_js(js: &str) -> Option<String> {
+    let session = Session::new();
+    let mut output = Vec::new();
+    minify_js::minify(&session, TopLevelMode::Global, js.as_bytes(), &mut output).ok()?;
+    String::from_utf8(output).ok()
+}
+
+/// Minify the content of an asset by its extension, or `None` if it is not minified,
+/// like the files already minified as `*.min.js`
+fn minify_asset(path: &Path, content: &str, config: &MinifyConfig) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy();
+    if name.ends_with(".min.css") || name.ends_with(".min.js") {
+        return None;
+    }
+    let minified = match path.extension()?.to_str()? {
+        "css" if config.css => minify_css(content)?,
+        "js" if config.js => minify_js(content)?,
+        _ => return None,
+    };
+    (minified.len() < content.len()).then_some(minified)
+}
+
+/// Publish a static file, minifying it if it is a CSS or JS asset
+pub fn copy(src: &Path, dest: &Path) -> FileResult<()> {
+    let config = get_minify_config();
+    if config.is_active()
+        && let Ok(content) = file::read_file_string(src)
+        && let Some(minified) = minify_asset(dest, &content, config)
+    {
+        return file::write_file(dest, minified);
+    }
+    file::copy(src, dest)
+}
+
+/// Publish a generated asset, like a compiled stylesheet, minifying it if possible
+pub fn write_asset(dest: &Path, content: String) -> FileResult<()> {
+    let config = get_minify_config();
+    let content = match config.is_active() {
+        true => minify_asset(dest, &content, config).unwrap_or(content),
+        false => content,
+    };
+    file::write_file(dest, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> MinifyConfig {
+        MinifyConfig::default()
+    }
+
+    #[test]
+    fn test_minify_html() {
+        let html = "<div>\n    <p>Hello,   world</p>\n    <pre>  a\n    b  </pre>\n</div>";
+        assert_eq!(
+            minify_html(html, &config()),
+            "<div><p>Hello, world</p><pre>  a\n    b  </pre></div>"
+        );
+
+        let html = "<p>a</p>\n<!-- comment -->\n<!--[if IE]><p>IE</p><![endif]-->";
+        assert_eq!(
+            minify_html(html, &config()),
+            "<p>a</p><!--[if IE]><p>IE</p><![endif]-->"
+        );
+
+        // scripts failing to parse are kept
+        let html = "<script>let = = 1;</script>";
+        assert_eq!(minify_html(html, &config()), html);
+    }
+
+    #[test]
+    fn test_minify_asset() {
+        assert_eq!(
+            minify_asset(Path::new("a.css"), "a {\n  color: red;\n}\n", &config()),
+            Some("a{color:red}".into())
+        );
+        assert_eq!(
+            minify_asset(Path::new("a.min.css"), "a {\n  color: red;\n}\n", &config()),
+            None
+        );
+        assert_eq!(
+            minify_asset(Path::new("a.js"), "let = = 1;", &config()),
+            None
+        );
+        let config = MinifyConfig {
+            css: false,
+            ..config()
+        };
+        assert_eq!(minify_asset(Path::new("a.css"), "a { }", &config), None);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::generator::FileListener;
+use allay_base::report::BuildStats;
 use allay_base::{config::get_allay_config, file::FileResult};
 use allay_plugin::PluginManager;
 use std::path::PathBuf;
@@ -12,9 +13,11 @@ impl FileListener for PluginListener {
 
     fn on_create(&self, path: PathBuf) -> FileResult<()> {
         let plugin_manager = PluginManager::instance();
-        for plugin in plugin_manager.plugins() {
+        for (name, plugin) in plugin_manager.named_plugins() {
             if let Ok(mut plugin) = plugin.lock() {
-                plugin.on_create(path.to_string_lossy().into());
+                BuildStats::get_instance().time_hook(&name, "on_create", || {
+                    plugin.on_create(path.to_string_lossy().into())
+                });
             }
         }
         Ok(())
@@ -22,9 +25,11 @@ impl FileListener for PluginListener {
 
     fn on_remove(&self, path: PathBuf) -> FileResult<()> {
         let plugin_manager = PluginManager::instance();
-        for plugin in plugin_manager.plugins() {
+        for (name, plugin) in plugin_manager.named_plugins() {
             if let Ok(mut plugin) = plugin.lock() {
-                plugin.on_remove(path.to_string_lossy().into());
+                BuildStats::get_instance().time_hook(&name, "on_remove", || {
+                    plugin.on_remove(path.to_string_lossy().into())
+                });
             }
         }
         Ok(())
@@ -32,9 +37,11 @@ impl FileListener for PluginListener {
 
     fn on_modify(&self, path: PathBuf) -> FileResult<()> {
         let plugin_manager = PluginManager::instance();
-        for plugin in plugin_manager.plugins() {
+        for (name, plugin) in plugin_manager.named_plugins() {
             if let Ok(mut plugin) = plugin.lock() {
-                plugin.on_modify(path.to_string_lossy().into());
+                BuildStats::get_instance().time_hook(&name, "on_modify", || {
+                    plugin.on_modify(path.to_string_lossy().into())
+                });
             }
         }
         Ok(())
@@ -1,13 +1,14 @@
 use std::path::PathBuf;
 
-use allay_base::config::{CLICommand, get_cli_config};
+use crate::minify::minify_html;
+use allay_base::config::{CLICommand, get_cli_config, get_minify_config};
 use allay_base::sitemap::SiteMap;
 use lol_html::{RewriteStrSettings, element, rewrite_str};
 use tracing::warn;
 
 macro_rules! link_handler {
-    ($selector: expr, $attr: expr) => {{
-        let base_url = SiteMap::read().base_url.clone();
+    ($base_url: expr, $selector: expr, $attr: expr) => {{
+        let base_url = $base_url.to_string();
 
         element!($selector, move |el| {
             let link = el.get_attribute($attr).expect("attribute was required");
@@ -20,22 +21,62 @@ macro_rules! link_handler {
     }};
 }
 
-pub fn postprocess(html: &str) -> String {
+macro_rules! srcset_handler {
+    ($base_url: expr, $selector: expr) => {{
+        let base_url = $base_url.to_string();
+
+        element!($selector, move |el| {
+            let srcset = el.get_attribute("srcset").expect("attribute was required");
+            let srcset: Vec<_> = srcset
+                .split(',')
+                .map(|candidate| match candidate.trim().strip_prefix("/") {
+                    Some(stripped) => {
+                        PathBuf::from(&base_url).join(stripped).to_string_lossy().to_string()
+                    }
+                    None => candidate.trim().to_string(),
+                })
+                .collect();
+            el.set_attribute("srcset", &srcset.join(", "))?;
+            Ok(())
+        })
+    }};
+}
+
+pub fn postprocess(html: &str, lang: &str) -> String {
     let hot_reload = matches!(get_cli_config().command, CLICommand::Serve(_))
         .then_some(include_str!("assets/auto-reload.js"))
         .unwrap_or_default();
 
-    let html = format!(include_str!("assets/wrapper.html"), html, hot_reload);
+    let html = format!(
+        include_str!("assets/wrapper.html"),
+        html,
+        hot_reload,
+        // the language comes from the front matter, which may be anything
+        lang = escape_xml(lang)
+    );
+    let base_url = SiteMap::read().base_url.clone();
+    let html = rewrite_links(html, &base_url);
 
+    let config = get_minify_config();
+    match config.is_active() && config.html {
+        true => minify_html(&html, config),
+        false => html,
+    }
+}
+
+/// Prefix all the root-relative links in the HTML with the base URL
+pub fn rewrite_links(html: String, base_url: &str) -> String {
     let settings = RewriteStrSettings {
         element_content_handlers: vec![
-            link_handler!("a[href]", "href"),
-            link_handler!("link[href]", "href"),
-            link_handler!("script[src]", "src"),
-            link_handler!("img[src]", "src"),
-            link_handler!("source[src]", "src"),
-            link_handler!("video[src]", "src"),
-            link_handler!("audio[src]", "src"),
+            link_handler!(base_url, "a[href]", "href"),
+            link_handler!(base_url, "link[href]", "href"),
+            link_handler!(base_url, "script[src]", "src"),
+            link_handler!(base_url, "img[src]", "src"),
+            link_handler!(base_url, "source[src]", "src"),
+            link_handler!(base_url, "video[src]", "src"),
+            link_handler!(base_url, "audio[src]", "src"),
+            srcset_handler!(base_url, "img[srcset]"),
+            srcset_handler!(base_url, "source[srcset]"),
         ],
         ..RewriteStrSettings::new()
     };
@@ -48,3 +89,53 @@ pub fn postprocess(html: &str) -> String {
         }
     }
 }
+
+/// Join a site URL like `posts/foo` to the base URL
+pub fn absolute_url(base_url: &str, url: &str) -> String {
+    format!(
+        "{}/{}",
+        base_url.trim_end_matches('/'),
+        url.trim_start_matches('/')
+    )
+}
+
+/// Escape the special characters in XML text and attributes
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_absolute_url() {
+        assert_eq!(
+            absolute_url("https://example.com/", "posts/foo"),
+            "https://example.com/posts/foo"
+        );
+        assert_eq!(
+            absolute_url("https://example.com", "/"),
+            "https://example.com/"
+        );
+    }
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(
+            escape_xml(r#"<a href="x">Tom & 'Jerry'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &apos;Jerry&apos;&lt;/a&gt;"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Redirects from the old URLs of pages listed in their `aliases` front-matter key,
+//! together with the `[[redirects]]` rules of the site config.
+//! A redirect page is written at every alias for static hosting,
+//! and all the redirects are listed in `_redirects` for the servers understanding it.
+
+use crate::process::{absolute_url, escape_xml};
+use crate::sitemap::custom_page_urls;
+use allay_base::config::{RedirectRule, SITE_CONFIG_FILE, get_allay_config, get_redirects};
+use allay_base::data::AllayData;
+use allay_base::file;
+use allay_base::lock;
+use allay_base::sitemap::SiteMap;
+use allay_base::url::{AllayUrlPath, REDIRECTS_FILE, render_redirects};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use tracing::warn;
+
+/// the old URLs of a page, as a string or a list of strings
+const ALIASES: &str = "aliases";
+
+/// The conflicts found by the last generation of redirects
+static CONFLICTS: LazyLock<Mutex<Vec<(PathBuf, String)>>> =
+    LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// The redirect pages written by the last generation, to remove the stale ones
+static WRITTEN: LazyLock<Mutex<HashSet<PathBuf>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Get the conflicts of aliases and redirects with the pages or each other, sorted by path
+pub fn conflicts() -> Vec<(PathBuf, String)> {
+    lock!(CONFLICTS).clone()
+}
+
+/// A page with its URL and aliases, all normalized
+#[derive(Debug, Clone)]
+struct PageUrls {
+    source: PathBuf,
+    url: String,
+    aliases: Vec<String>,
+}
+
+/// An alias of a page to redirect
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Alias {
+    from: String,
+    to: String,
+}
+
+/// Display a normalized URL as a root-relative one
+fn rooted(url: &str) -> String {
+    match url {
+        "/" => url.into(),
+        url => format!("/{}", url),
+    }
+}
+
+fn aliases_of(data: Option<&AllayData>) -> Vec<String> {
+    let aliases = match data {
+        None | Some(AllayData::Null) => vec![],
+        Some(AllayData::List(list)) => list.iter().map(|alias| alias.to_string()).collect(),
+        Some(data) => vec![data.to_string()],
+    };
+    aliases.iter().map(|alias| AllayUrlPath::normalize(alias)).collect()
+}
+
+/// Match the aliases of the pages and the redirect rules against the page URLs.
+/// An alias or a rule is dropped with a conflict reported if it is the URL of a page,
+/// or if it is already claimed by another page or rule.
+fn resolve(pages: &[PageUrls], rules: &[RedirectRule]) -> (Vec<Alias>, Vec<(PathBuf, String)>) {
+    let urls: HashMap<&str, &PathBuf> =
+        pages.iter().map(|page| (page.url.as_str(), &page.source)).collect();
+    let config = PathBuf::from(SITE_CONFIG_FILE);
+
+    let mut claimed: HashMap<String, PathBuf> = HashMap::new();
+    let mut conflicts = vec![];
+    let mut claim = |from: &str, source: &PathBuf, what: &str| -> bool {
+        let reason = if let Some(page) = urls.get(from) {
+            format!("conflicts with the URL of {}", page.display())
+        } else if let Some(other) = claimed.get(from) {
+            format!("is already redirected by {}", other.display())
+        } else {
+            claimed.insert(from.into(), source.clone());
+            return true;
+        };
+        conflicts.push((
+            source.clone(),
+            format!("{} `{}` {}", what, rooted(from), reason),
+        ));
+        false
+    };
+
+    for rule in rules {
+        claim(&AllayUrlPath::normalize(&rule.from), &config, "Redirect");
+    }
+
+    let mut aliases = vec![];
+    for page in pages {
+        for from in page.aliases.iter() {
+            if claim(from, &page.source, "Alias") {
+                aliases.push(Alias {
+                    from: from.clone(),
+                    to: page.url.clone(),
+                });
+            }
+        }
+    }
+    conflicts.sort();
+    (aliases, conflicts)
+}
+
+/// Render a page redirecting to the target URL, for the hosts without support of `_redirects`
+fn render_redirect_page(target: &str) -> String {
+    let target = escape_xml(target);
+    format!(
+        r#"<!DOCTYPE html><html><head><meta charset="utf-8"><title>Redirecting…</title><link rel="canonical" href="{0}"><meta name="robots" content="noindex"><meta http-equiv="refresh" content="0; url={0}"></head><body><a href="{0}">Click here if you are not redirected.</a></body></html>"#,
+        target
+    )
+}
+
+/// Generate the redirect pages and `_redirects` from the current site map
+pub fn write_redirects() {
+    let content_dir = file::workspace(&get_allay_config().content_dir);
+    let publish_dir = file::workspace(&get_allay_config().publish_dir);
+
+    let mut pages: Vec<_> = SiteMap::read()
+        .urlset
+        .iter()
+        .filter_map(|(path, entry)| {
+            let meta = entry.meta();
+            let url = meta.get("url").and_then(|url| url.as_str().ok().map(String::from))?;
+            Some(PageUrls {
+                source: content_dir.join(path),
+                url: AllayUrlPath::normalize(&url),
+                aliases: aliases_of(meta.get(ALIASES).map(AsRef::as_ref)),
+            })
+        })
+        .collect();
+    pages.sort_by(|a, b| a.source.cmp(&b.source));
+    pages.extend(custom_page_urls().into_iter().map(|(url, _)| {
+        let url = AllayUrlPath::normalize(&url);
+        PageUrls {
+            source: PathBuf::from(rooted(&url)),
+            url,
+            aliases: vec![],
+        }
+    }));
+
+    let (aliases, conflicts) = resolve(&pages, get_redirects());
+    for (path, conflict) in conflicts.iter() {
+        warn!("{}: {}", path.display(), conflict);
+    }
+    *lock!(CONFLICTS) = conflicts;
+
+    let base_url = SiteMap::read().base_url.clone();
+    let mut written = HashSet::new();
+    for alias in aliases.iter() {
+        let path = AllayUrlPath::from_url(&alias.from).file_path();
+        let target = absolute_url(&base_url, &alias.to);
+        if let Err(e) = file::write_file(publish_dir.join(&path), render_redirect_page(&target)) {
+            warn!("Failed to write redirect page {:?}: {}", path, e);
+        }
+        written.insert(path);
+    }
+
+    // remove the redirect pages of the aliases no longer existing
+    let mut last = lock!(WRITTEN);
+    for path in last.difference(&written) {
+        if let Err(e) = file::remove(publish_dir.join(path)) {
+            warn!("Failed to remove redirect page {:?}: {}", path, e);
+        }
+    }
+    *last = written;
+
+    let mut rules: Vec<_> = get_redirects()
+        .iter()
+        .map(|rule| RedirectRule {
+            from: rooted(&AllayUrlPath::normalize(&rule.from)),
+            ..rule.clone()
+        })
+        .collect();
+    rules.extend(aliases.into_iter().map(|alias| RedirectRule {
+        from: rooted(&alias.from),
+        to: absolute_url(&base_url, &alias.to),
+        status: 301,
+    }));
+    if let Err(e) = file::write_file(publish_dir.join(REDIRECTS_FILE), render_redirects(&rules)) {
+        warn!("Failed to write {}: {}", REDIRECTS_FILE, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(source: &str, url: &str, aliases: &[&str]) -> PageUrls {
+        PageUrls {
+            source: source.into(),
+            url: url.into(),
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let pages = [
+            page("a.md", "posts/a", &["old/a", "posts/b"]),
+            page("b.md", "posts/b", &["old/a", "old/b/"]),
+        ];
+        let rules = [RedirectRule {
+            from: "/old/b/".into(),
+            to: "/posts/b".into(),
+            status: 302,
+        }];
+        let (aliases, conflicts) = resolve(&pages, &rules);
+        assert_eq!(
+            aliases,
+            vec![Alias {
+                from: "old/a".into(),
+                to: "posts/a".into(),
+            }]
+        );
+        assert_eq!(
+            conflicts,
+            vec![
+                (
+                    PathBuf::from("a.md"),
+                    "Alias `/posts/b` conflicts with the URL of b.md".to_string()
+                ),
+                (
+                    PathBuf::from("b.md"),
+                    "Alias `/old/a` is already redirected by a.md".to_string()
+                ),
+                (
+                    PathBuf::from("b.md"),
+                    "Alias `/old/b/` is already redirected by allay.toml".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_redirect_page() {
+        let html = render_redirect_page("/posts/a?x=1&y=2");
+        assert!(html.contains(r#"<link rel="canonical" href="/posts/a?x=1&amp;y=2">"#));
+        assert!(html.contains(r#"content="0; url=/posts/a?x=1&amp;y=2""#));
+    }
+}
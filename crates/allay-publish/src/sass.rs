@@ -0,0 +1,206 @@
+//! Compilation of the Sass stylesheets in the static directories of the site and the theme,
+//! like `css/style.scss` -> `css/style.css`. Partials like `css/_vars.scss` are not published,
+//! but only loaded by the other stylesheets.
+
+use allay_base::config::static_dirs;
+use allay_base::file;
+use allay_base::lock;
+use grass_compiler::codemap::SpanLoc;
+use grass_compiler::{Fs, Logger, Options, StdFs};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use tracing::{debug, warn};
+
+const SASS_EXTENSIONS: [&str; 2] = ["scss", "sass"];
+
+/// A compiled stylesheet with its destination and all the files it loads
+#[derive(Debug, Clone)]
+struct Stylesheet {
+    dest: PathBuf,
+    loaded: HashSet<PathBuf>,
+}
+
+/// The stylesheets compiled, keyed by their source paths in the workspace
+static STYLESHEETS: LazyLock<Mutex<HashMap<PathBuf, Stylesheet>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Check if a file is a Sass stylesheet
+pub fn is_sass<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SASS_EXTENSIONS.contains(&ext))
+}
+
+/// Check if a Sass file is a partial, whose name starts with `_`
+pub fn is_partial<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('_'))
+}
+
+/// Resolve `.` and `..` in a path without touching the file system,
+/// so that the removed files can be compared with the loaded ones
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// The file system of the Sass compiler, recording all the files loaded
+#[derive(Debug, Default)]
+struct RecordingFs {
+    loaded: Mutex<HashSet<PathBuf>>,
+}
+
+impl Fs for RecordingFs {
+    fn is_dir(&self, path: &Path) -> bool {
+        StdFs.is_dir(path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        StdFs.is_file(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        lock!(self.loaded).insert(normalize(path));
+        StdFs.read(path)
+    }
+}
+
+/// Send the `@debug` and `@warn` messages of the stylesheets to the logs
+#[derive(Debug)]
+struct TracingLogger;
+
+impl Logger for TracingLogger {
+    fn debug(&self, location: SpanLoc, message: &str) {
+        debug!(
+            "{}:{}: {}",
+            location.file.name(),
+            location.begin.line + 1,
+            message
+        );
+    }
+
+    fn warn(&self, location: SpanLoc, message: &str) {
+        warn!(
+            "{}:{}: {}",
+            location.file.name(),
+            location.begin.line + 1,
+            message
+        );
+    }
+}
+
+/// Compile a stylesheet into CSS, remembering its destination for the recompilation.
+/// The imports are resolved in the directory of the stylesheet first, then in the static
+/// directories of the site and the themes, so that the site can override the theme partials.
+pub fn compile(src: &Path, dest: &Path) -> Result<String, String> {
+    let load_paths = static_dirs().into_iter().map(file::workspace).collect();
+    compile_with(src, dest, load_paths)
+}
+
+/// Compile a stylesheet with the given load paths, see [`compile`]
+fn compile_with(src: &Path, dest: &Path, load_paths: Vec<PathBuf>) -> Result<String, String> {
+    let fs = RecordingFs::default();
+    let options = load_paths.into_iter().fold(
+        Options::default().fs(&fs).logger(&TracingLogger),
+        |options, dir| options.load_path(dir),
+    );
+    let res =
+        grass_compiler::from_path(src, &options).map_err(|e| e.to_string().trim_end().to_string());
+
+    // remember the loaded files even if failed, to recompile after the error is fixed
+    let mut loaded = fs.loaded.into_inner().unwrap_or_default();
+    loaded.insert(normalize(src));
+    lock!(STYLESHEETS).insert(
+        src.into(),
+        Stylesheet {
+            dest: dest.into(),
+            loaded,
+        },
+    );
+    res
+}
+
+/// Forget a removed stylesheet
+pub fn forget(src: &Path) {
+    lock!(STYLESHEETS).remove(src);
+}
+
+/// Get the stylesheets to recompile with their destinations after a Sass file changes,
+/// except itself. A new file may change how the imports are resolved, like a site partial
+/// overriding the theme one, so all the stylesheets are recompiled then.
+pub fn affected(src: &Path, created: bool) -> Vec<(PathBuf, PathBuf)> {
+    let changed = normalize(src);
+    let mut affected: Vec<_> = lock!(STYLESHEETS)
+        .iter()
+        .filter(|(path, sheet)| {
+            path.as_path() != src && (created || sheet.loaded.contains(&changed))
+        })
+        .map(|(path, sheet)| (path.clone(), sheet.dest.clone()))
+        .collect();
+    affected.sort();
+    affected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sass_files() {
+        assert!(is_sass("css/style.scss"));
+        assert!(is_sass("css/style.sass"));
+        assert!(!is_sass("css/style.css"));
+        assert!(is_partial("css/_vars.scss"));
+        assert!(!is_partial("_css/vars.scss"));
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize(Path::new("static/css/./../sass/_vars.scss")),
+            PathBuf::from("static/sass/_vars.scss")
+        );
+    }
+
+    #[test]
+    fn test_partial_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let css = dir.path().join("css");
+        std::fs::create_dir(&css).unwrap();
+        std::fs::write(css.join("_vars.scss"), "$color: red;").unwrap();
+        std::fs::write(css.join("_unused.scss"), "$size: 1px;").unwrap();
+        std::fs::write(
+            css.join("style.scss"),
+            "@use 'vars';\na { color: vars.$color; }",
+        )
+        .unwrap();
+        std::fs::write(css.join("plain.scss"), "b { color: blue; }").unwrap();
+
+        let style = css.join("style.scss");
+        let plain = css.join("plain.scss");
+        let output = compile_with(&style, Path::new("style.css"), vec![]).unwrap();
+        assert!(output.contains("color: red"));
+        compile_with(&plain, Path::new("plain.css"), vec![]).unwrap();
+
+        // only the stylesheets loading the partial are affected by it
+        let vars = affected(&css.join("./_vars.scss"), false);
+        assert_eq!(vars, vec![(style.clone(), PathBuf::from("style.css"))]);
+        assert!(affected(&css.join("_unused.scss"), false).is_empty());
+        // a stylesheet is not affected by itself, but a new file may affect all the others
+        assert!(affected(&style, false).is_empty());
+        assert!(affected(&css.join("_new.scss"), true).contains(&(plain, "plain.css".into())));
+    }
+}
@@ -1,13 +1,28 @@
+use crate::feed;
 use crate::generator::FileListener;
-use allay_base::config::{CLICommand, get_allay_config, get_cli_config, get_site_config};
+use crate::process::{absolute_url, escape_xml};
+use crate::redirect;
+use crate::visibility::is_visible;
+use allay_base::config::{
+    CLICommand, custom_dirs, get_allay_config, get_cli_config, get_site_config, static_dirs,
+};
 use allay_base::file::{self, FileResult};
 use allay_base::log::NoPanicUnwrap;
 use allay_base::sitemap::{SiteMap, UrlEntry};
+use allay_base::template::TemplateKind;
+use allay_base::url::AllayUrlPath;
 use allay_compiler::match_meta;
+use chrono::{DateTime, SecondsFormat};
+use std::collections::HashSet;
 use std::ops::DerefMut;
 use std::path::PathBuf;
 use tracing::{info, warn};
 
+const SITEMAP_FILE: &str = "sitemap.xml";
+const ROBOTS_FILE: &str = "robots.txt";
+/// The maximum number of URLs in a single sitemap file allowed by the protocol
+const MAX_SITEMAP_URLS: usize = 50_000;
+
 /// A worker that manages the site map
 pub struct SiteMapWorker;
 
@@ -49,11 +64,33 @@ impl SiteMapWorker {
         instance
     }
 
+    /// Write the outputs depending on the site map, after the site map changes
+    fn on_changed(&self) {
+        feed::write_feeds();
+        redirect::write_redirects();
+        write_sitemap_xml();
+        write_robots_txt();
+    }
+
     fn create_on(&self, path: PathBuf, map: &mut SiteMap) -> FileResult<()> {
         let real_path = file::workspace(self.root().join(&path));
         let lastmod = file::last_modified(&real_path)?;
-        let meta = match_meta(real_path).unwrap_or_default().into();
-        let entry = UrlEntry { lastmod, meta };
+        let meta = match_meta(real_path).unwrap_or_default();
+
+        // invisible contents like drafts are not in the site map
+        match is_visible(&meta) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) => {
+                warn!("Skip {:?} in site map: {}", path, e);
+                return Ok(());
+            }
+        }
+
+        let entry = UrlEntry {
+            lastmod,
+            meta: meta.into(),
+        };
         map.urlset.insert(path, entry);
         Ok(())
     }
@@ -64,17 +101,198 @@ impl FileListener for SiteMapWorker {
         get_allay_config().content_dir.clone().into()
     }
 
+    /// The site map is already scanned on creation, so only the outputs are written
+    fn cold_start(&self) {
+        SiteMap::read().dump();
+        self.on_changed();
+    }
+
     fn on_create(&self, path: PathBuf) -> FileResult<()> {
-        let mut map = SiteMap::write();
-        self.create_on(path, map.deref_mut())?;
-        map.dump();
+        {
+            let mut map = SiteMap::write();
+            self.create_on(path, map.deref_mut())?;
+            map.dump();
+        }
+        self.on_changed();
         Ok(())
     }
 
     fn on_remove(&self, path: PathBuf) -> FileResult<()> {
-        let mut map = SiteMap::write();
-        map.urlset.remove(&path);
-        map.dump();
+        {
+            let mut map = SiteMap::write();
+            map.urlset.remove(&path);
+            map.dump();
+        }
+        self.on_changed();
         Ok(())
     }
 }
+
+/// Get the base URL in the site config, which the published files like `sitemap.xml` always use
+fn site_base_url() -> String {
+    let base_url = get_site_config().get("base_url").cloned();
+    base_url.and_then(|url| url.as_str().ok().map(String::from)).unwrap_or_default()
+}
+
+/// Collect the URLs of all the visible pages with their last modified time, sorted by URL.
+/// This includes the content pages in the site map and the custom pages of the theme.
+fn sitemap_entries() -> Vec<(String, u64)> {
+    let mut entries: Vec<_> = SiteMap::read()
+        .urlset
+        .values()
+        .filter_map(|entry| {
+            let meta = entry.meta();
+            let hidden = meta.get("hidden").is_some_and(|d| d.as_bool().unwrap_or(false));
+            let url = meta.get("url").and_then(|url| url.as_str().ok().map(String::from));
+            url.filter(|_| !hidden).map(|url| (url, entry.lastmod))
+        })
+        .collect();
+
+    entries.extend(custom_page_urls());
+
+    entries.sort();
+    entries.dedup_by(|(a, _), (b, _)| a == b);
+    entries
+}
+
+/// Collect the URLs of the custom pages of the themes with their last modified time.
+/// A page in more than one theme is only listed once, as published from the nearest theme.
+pub(crate) fn custom_page_urls() -> Vec<(String, u64)> {
+    let mut entries = vec![];
+    let mut listed = HashSet::new();
+    for custom_dir in custom_dirs().into_iter().map(file::workspace) {
+        for path in file::read_dir_all_files(&custom_dir).unwrap_or_default() {
+            if !TemplateKind::from_filename(&path).is_template() {
+                continue;
+            }
+            let Ok(relative) = path.strip_prefix(&custom_dir) else {
+                continue;
+            };
+            if !listed.insert(relative.to_path_buf()) {
+                continue;
+            }
+            let url = AllayUrlPath::from(relative.with_extension(TemplateKind::Html.extension()));
+            let lastmod = file::last_modified(&path).unwrap_or_default();
+            entries.push((url.as_ref().to_string_lossy().to_string(), lastmod));
+        }
+    }
+    entries
+}
+
+/// Format a timestamp in W3C datetime format required by sitemaps
+fn w3c_datetime(timestamp: u64) -> String {
+    DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|date| date.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .unwrap_or_default()
+}
+
+fn render_urlset(base_url: &str, entries: &[(String, u64)]) -> String {
+    let mut urls = String::new();
+    for (url, lastmod) in entries {
+        urls.push_str(&format!(
+            "<url><loc>{}</loc><lastmod>{}</lastmod></url>",
+            escape_xml(&absolute_url(base_url, url)),
+            w3c_datetime(*lastmod),
+        ));
+    }
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">{}</urlset>"#,
+        urls
+    )
+}
+
+fn render_sitemap_index(base_url: &str, files: &[String]) -> String {
+    let mut sitemaps = String::new();
+    for name in files {
+        sitemaps.push_str(&format!(
+            "<sitemap><loc>{}</loc></sitemap>",
+            escape_xml(&absolute_url(base_url, name)),
+        ));
+    }
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">{}</sitemapindex>"#,
+        sitemaps
+    )
+}
+
+/// Render the sitemap files with their names.
+/// If there are too many URLs for a single file, they are split into `sitemap-1.xml`, `sitemap-2.xml`, ...
+/// and `sitemap.xml` becomes the index of them.
+fn render_sitemaps(base_url: &str, entries: &[(String, u64)]) -> Vec<(String, String)> {
+    if entries.len() <= MAX_SITEMAP_URLS {
+        return vec![(SITEMAP_FILE.into(), render_urlset(base_url, entries))];
+    }
+
+    let mut files: Vec<_> = entries
+        .chunks(MAX_SITEMAP_URLS)
+        .enumerate()
+        .map(|(i, chunk)| {
+            (
+                format!("sitemap-{}.xml", i + 1),
+                render_urlset(base_url, chunk),
+            )
+        })
+        .collect();
+    let names: Vec<_> = files.iter().map(|(name, _)| name.clone()).collect();
+    files.push((SITEMAP_FILE.into(), render_sitemap_index(base_url, &names)));
+    files
+}
+
+/// Write `sitemap.xml` of all the visible pages into the publish directory
+fn write_sitemap_xml() {
+    let publish_dir = file::workspace(&get_allay_config().publish_dir);
+    for (name, content) in render_sitemaps(&site_base_url(), &sitemap_entries()) {
+        if let Err(e) = file::write_file(publish_dir.join(&name), content) {
+            warn!("Failed to write {}: {}", name, e);
+        }
+    }
+}
+
+/// Write `robots.txt` pointing to the sitemap into the publish directory,
+/// unless the site or a theme provides its own one as a static file
+fn write_robots_txt() {
+    let statics = static_dirs().into_iter().map(file::workspace);
+    if statics.map(|dir| dir.join(ROBOTS_FILE)).any(file::file_exists) {
+        return;
+    }
+
+    let sitemap = absolute_url(&site_base_url(), SITEMAP_FILE);
+    let content = format!("User-agent: *\nAllow: /\n\nSitemap: {}\n", sitemap);
+    let path = file::workspace(&get_allay_config().publish_dir).join(ROBOTS_FILE);
+    if let Err(e) = file::write_file(path, content) {
+        warn!("Failed to write {}: {}", ROBOTS_FILE, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_urlset() {
+        let entries = [("/".to_string(), 0), ("posts/a&b".to_string(), 1735689600)];
+        let xml = render_urlset("https://example.com/", &entries);
+        assert!(xml.contains(
+            "<url><loc>https://example.com/</loc><lastmod>1970-01-01T00:00:00Z</lastmod></url>"
+        ));
+        assert!(xml.contains("<loc>https://example.com/posts/a&amp;b</loc>"));
+        assert!(xml.contains("<lastmod>2025-01-01T00:00:00Z</lastmod>"));
+    }
+
+    #[test]
+    fn test_split_sitemaps() {
+        let entries: Vec<_> = (0..MAX_SITEMAP_URLS + 1).map(|i| (format!("p{}", i), 0)).collect();
+        let files = render_sitemaps("https://example.com", &entries);
+        let names: Vec<_> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["sitemap-1.xml", "sitemap-2.xml", "sitemap.xml"]);
+        assert!(files[2].1.contains("<loc>https://example.com/sitemap-2.xml</loc>"));
+        assert_eq!(files[1].1.matches("<url>").count(), 1);
+    }
+
+    #[test]
+    fn test_single_sitemap() {
+        let files = render_sitemaps("https://example.com", &[("about".into(), 0)]);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, SITEMAP_FILE);
+    }
+}
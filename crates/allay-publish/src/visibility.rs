@@ -0,0 +1,143 @@
+//! The visibility of contents by their `draft`, `publish_date` and `expiry_date` front-matter keys.
+//! Invisible contents are neither in the site map nor written to the publish directory.
+
+use allay_base::config::{PreviewArgs, get_cli_config};
+use allay_base::data::{AllayData, AllayObject};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+use std::sync::OnceLock;
+use thiserror::Error;
+
+/// if the content is a draft (default `false`)
+const DRAFT: &str = "draft";
+/// the date before which the content is not published
+const PUBLISH_DATE: &str = "publish_date";
+/// the date from which the content is no longer published
+const EXPIRY_DATE: &str = "expiry_date";
+
+#[derive(Error, Debug)]
+pub enum VisibilityError {
+    #[error("Invalid date of `{0}`: {1}")]
+    InvalidDate(&'static str, String),
+}
+
+pub type VisibilityResult<T> = Result<T, VisibilityError>;
+
+fn preview() -> &'static PreviewArgs {
+    static INSTANCE: OnceLock<PreviewArgs> = OnceLock::new();
+    INSTANCE.get_or_init(|| get_cli_config().command.preview())
+}
+
+/// Check if a content is visible now, considering the `--drafts` and `--future` flags
+pub fn is_visible(meta: &AllayObject) -> VisibilityResult<bool> {
+    visible_at(meta, preview(), Local::now().fixed_offset())
+}
+
+fn visible_at(
+    meta: &AllayObject,
+    preview: &PreviewArgs,
+    now: DateTime<FixedOffset>,
+) -> VisibilityResult<bool> {
+    let draft = meta.get(DRAFT).is_some_and(|data| data.as_bool().unwrap_or(false));
+    if draft && !preview.drafts {
+        return Ok(false);
+    }
+
+    if let Some(date) = date_of(meta, PUBLISH_DATE)?
+        && date > now
+        && !preview.future
+    {
+        return Ok(false);
+    }
+
+    if let Some(date) = date_of(meta, EXPIRY_DATE)?
+        && date <= now
+    {
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// Get the date under a front-matter key, which is either a string in YAML
+/// or a TOML datetime (deserialized as an object with a single string field)
+fn date_of(
+    meta: &AllayObject,
+    key: &'static str,
+) -> VisibilityResult<Option<DateTime<FixedOffset>>> {
+    let text = match meta.get(key).map(AsRef::as_ref) {
+        None | Some(AllayData::Null) => return Ok(None),
+        Some(AllayData::String(text)) => text.to_string(),
+        Some(AllayData::Object(obj)) if obj.len() == 1 => obj.values().next().unwrap().to_string(),
+        Some(data) => return Err(VisibilityError::InvalidDate(key, data.to_string())),
+    };
+    parse_date(&text).map(Some).ok_or(VisibilityError::InvalidDate(key, text))
+}
+
+/// Parse a date like `2025-01-01`, `2025-01-01 08:00:00` or `2025-01-01T08:00:00+08:00`.
+/// Dates without an offset are in the local time zone.
+pub(crate) fn parse_date(text: &str) -> Option<DateTime<FixedOffset>> {
+    let text = text.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(text) {
+        return Some(date);
+    }
+
+    let naive = [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+    .or_else(|| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0))?;
+    Local.from_local_datetime(&naive).earliest().map(|date| date.fixed_offset())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn meta(pairs: &[(&str, AllayData)]) -> AllayObject {
+        pairs.iter().map(|(k, v)| (k.to_string(), Arc::new(v.clone()))).collect()
+    }
+
+    #[test]
+    fn test_visibility() {
+        let now = DateTime::parse_from_rfc3339("2025-06-01T00:00:00+00:00").unwrap();
+        let default = PreviewArgs::default();
+        let preview = PreviewArgs {
+            drafts: true,
+            future: true,
+        };
+
+        let draft = meta(&[(DRAFT, true.into())]);
+        assert!(!visible_at(&draft, &default, now).unwrap());
+        assert!(visible_at(&draft, &preview, now).unwrap());
+
+        let future = meta(&[(PUBLISH_DATE, "2025-07-01T00:00:00Z".into())]);
+        assert!(!visible_at(&future, &default, now).unwrap());
+        assert!(visible_at(&future, &preview, now).unwrap());
+
+        let past = meta(&[(PUBLISH_DATE, "2025-05-01T00:00:00Z".into())]);
+        assert!(visible_at(&past, &default, now).unwrap());
+
+        let expired = meta(&[(EXPIRY_DATE, "2025-05-01T00:00:00+08:00".into())]);
+        assert!(!visible_at(&expired, &preview, now).unwrap());
+
+        let invalid = meta(&[(EXPIRY_DATE, "tomorrow".into())]);
+        assert!(visible_at(&invalid, &default, now).is_err());
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert!(parse_date("2025-01-01").is_some());
+        assert!(parse_date("2025-01-01 08:00:00").is_some());
+        assert!(parse_date("2025-01-01T08:00").is_some());
+        assert_eq!(
+            parse_date("2025-01-01T08:00:00+08:00"),
+            parse_date("2025-01-01T00:00:00Z")
+        );
+        assert!(parse_date("01/01/2025").is_none());
+    }
+}
@@ -1,5 +1,7 @@
 mod file;
 mod hot_reload;
+#[cfg(feature = "plugin")]
+mod status;
 mod utils;
 
 #[cfg(feature = "plugin")]
@@ -7,6 +9,8 @@ use crate::plugin_worker::PluginWorker;
 use crate::route::file::{handle_file, handle_index};
 use crate::route::hot_reload::handle_last_modify;
 #[cfg(feature = "plugin")]
+use crate::route::status::handle_plugin_status;
+#[cfg(feature = "plugin")]
 use allay_plugin::PluginManager;
 #[cfg(feature = "plugin")]
 use allay_plugin::manager::Plugin;
@@ -85,6 +89,7 @@ pub fn build_route(path: PathBuf) -> Router {
         .with_state(Arc::new(path));
     cfg_if::cfg_if! {
         if #[cfg(feature = "plugin")] {
+            let route = route.route("/api/plugins", get(handle_plugin_status));
             let plugin_manager = PluginManager::instance();
             plugin_manager.plugins().into_iter().fold(route, register_custom_route)
         } else {
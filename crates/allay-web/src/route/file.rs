@@ -1,14 +1,18 @@
 use crate::route::utils::safe_filename;
 use crate::route::{RouteError, RouteResult};
-use allay_base::config::get_theme_config;
-use allay_base::url::AllayUrlPath;
+use allay_base::asset::AssetManifest;
+use allay_base::config::{RedirectRule, get_theme_config};
+use allay_base::url::{AllayUrlPath, REDIRECTS_FILE, parse_redirects};
+use allay_base::{read, write};
 use axum::body::Body;
 use axum::extract::{Path, Query, State};
-use axum::http::{HeaderValue, header, response::Builder};
+use axum::http::{HeaderValue, StatusCode, header, response::Builder};
 use mime_guess::from_path;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::SystemTime;
 use tokio::fs::File;
 use tokio_util::io::ReaderStream;
 
@@ -70,6 +74,13 @@ pub async fn file_response(
             format!("inline; filename=\"{}\"", safe_filename(file_path))
         };
 
+    // fingerprinted assets never change, while the others may be edited at any time
+    let cache_control = if AssetManifest::get_instance().is_fingerprinted(file_path) {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+
     let response = Builder::new()
         .header(
             header::CONTENT_TYPE,
@@ -82,7 +93,7 @@ pub async fn file_response(
         .header(header::CONTENT_LENGTH, HeaderValue::from(metadata.len()))
         .header(
             header::CACHE_CONTROL,
-            HeaderValue::from_static("public, max-age=3600"),
+            HeaderValue::from_static(cache_control),
         )
         .body(body)
         .unwrap();
@@ -90,11 +101,63 @@ pub async fn file_response(
     Ok(response)
 }
 
+/// The rules in the `_redirects` file by their normalized URL paths,
+/// with the modified time of the file they are parsed from
+type RedirectTable = (SystemTime, HashMap<String, RedirectRule>);
+
+static REDIRECTS: LazyLock<RwLock<Option<RedirectTable>>> = LazyLock::new(|| RwLock::new(None));
+
+/// Find the rule redirecting a URL path in the `_redirects` file of the publish directory.
+/// The rules are parsed again only when the file changes.
+async fn find_redirect(root: &std::path::Path, url: &str) -> Option<RedirectRule> {
+    let path = root.join(REDIRECTS_FILE);
+    let modified = tokio::fs::metadata(&path).await.and_then(|m| m.modified()).ok()?;
+    let url = AllayUrlPath::normalize(url);
+
+    if let Some((time, rules)) = read!(REDIRECTS).as_ref()
+        && *time == modified
+    {
+        return rules.get(&url).cloned();
+    }
+
+    let text = tokio::fs::read_to_string(&path).await.ok()?;
+    let mut rules = HashMap::new();
+    for rule in parse_redirects(&text) {
+        // the first rule of a URL wins
+        rules.entry(AllayUrlPath::normalize(&rule.from)).or_insert(rule);
+    }
+    let rule = rules.get(&url).cloned();
+    *write!(REDIRECTS) = Some((modified, rules));
+    rule
+}
+
+/// Build a redirect response, which is permanent unless the status is another redirection
+fn redirect_response(status: u16, location: &str) -> RouteResult {
+    let status = StatusCode::from_u16(status)
+        .ok()
+        .filter(StatusCode::is_redirection)
+        .unwrap_or(StatusCode::MOVED_PERMANENTLY);
+    let build_error =
+        |e: axum::http::Error| RouteError::Internal(format!("Failed to build redirect: {}", e));
+    Builder::new()
+        .status(status)
+        .header(
+            header::LOCATION,
+            HeaderValue::from_str(location).map_err(|e| build_error(e.into()))?,
+        )
+        .body(Body::empty())
+        .map_err(build_error)
+}
+
 pub async fn handle_file(
     State(root): State<Arc<PathBuf>>,
     Path(file_path): Path<String>,
     Query(params): Query<DownloadParams>,
 ) -> RouteResult {
+    if let Some(rule) = find_redirect(&root, &file_path).await {
+        return redirect_response(rule.status, &rule.to);
+    }
+
     let path = PathBuf::from(&file_path);
 
     // try all possible file paths for this URL path
@@ -116,16 +179,7 @@ pub async fn handle_file(
             if let Err(RouteError::NotFound) = response {
                 continue; // try next possible path
             }
-            return Builder::new()
-                .status(302)
-                .header(
-                    header::LOCATION,
-                    HeaderValue::from_str(&url.as_ref().to_string_lossy()).map_err(|e| {
-                        RouteError::Internal(format!("Failed to build redirect: {}", e))
-                    })?,
-                )
-                .body(Body::empty())
-                .map_err(|e| RouteError::Internal(format!("Failed to build redirect: {}", e)));
+            return redirect_response(302, &url.as_ref().to_string_lossy());
         }
     }
 
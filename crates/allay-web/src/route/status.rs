@@ -0,0 +1,9 @@
+use allay_base::plugin_health::PluginHealth;
+use allay_plugin::PluginManager;
+use axum::Json;
+use std::collections::BTreeMap;
+
+/// The health of the plugins, including the disabled ones
+pub async fn handle_plugin_status() -> Json<BTreeMap<String, PluginHealth>> {
+    Json(PluginManager::instance().health())
+}
@@ -1,5 +1,7 @@
-use allay_plugin_api::{Plugin, RouteComponent, register_plugin};
+use allay_plugin_api::{Capabilities, Plugin, RouteComponent, register_plugin};
 use router::AuthRouter;
+use std::fs;
+use std::path::Path;
 mod conn_pool;
 mod model;
 mod router;
@@ -23,6 +25,31 @@ pub struct AuthPlugin {
     router: AuthRouter,
 }
 
+impl AuthPlugin {
+    const DATA_DIR: &'static str = "data/auth";
+    /// The database of the older versions, at the site root
+    const LEGACY_DB: &'static str = "auth.db";
+
+    /// Copy the database of the older versions into the data directory once,
+    /// so that the existing accounts are kept
+    fn migrate_legacy_db(db_path: &str) {
+        if Path::new(db_path).exists() || !Path::new(Self::LEGACY_DB).is_file() {
+            return;
+        }
+        if let Err(e) =
+            fs::create_dir_all(Self::DATA_DIR).and_then(|_| fs::copy(Self::LEGACY_DB, db_path))
+        {
+            eprintln!("Failed to move {} to {}: {}", Self::LEGACY_DB, db_path, e);
+            return;
+        }
+        eprintln!(
+            "Moved the accounts from {} to {}, the old file can be removed",
+            Self::LEGACY_DB,
+            db_path
+        );
+    }
+}
+
 impl Plugin for AuthPlugin {
     fn name() -> &'static str
     where
@@ -38,13 +65,27 @@ impl Plugin for AuthPlugin {
         "0.1.0"
     }
 
+    fn capabilities() -> Capabilities
+    where
+        Self: Sized,
+    {
+        // the database and its journal are kept in a directory of their own,
+        // while the site is read to move the database of the older versions
+        Capabilities {
+            read_dirs: vec![".".into()],
+            write_dirs: vec![AuthPlugin::DATA_DIR.into()],
+            env: vec![],
+        }
+    }
+
     fn new() -> Self
     where
         Self: Sized,
     {
-        let db_url = "auth.db";
+        let db_url = format!("{}/auth.db", AuthPlugin::DATA_DIR);
+        AuthPlugin::migrate_legacy_db(&db_url);
         AuthPlugin {
-            router: AuthRouter::new(db_url),
+            router: AuthRouter::new(&db_url),
         }
     }
 
@@ -1,4 +1,4 @@
-use allay_plugin_api::{ListenComponent, Plugin, register_plugin};
+use allay_plugin_api::{Capabilities, ListenComponent, Plugin, register_plugin};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
@@ -110,6 +110,14 @@ impl Plugin for ReadingTimePlugin {
         "0.1.0"
     }
 
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            read_dirs: vec![ReadingTimeGenerator::INPUT.into()],
+            write_dirs: vec![ReadingTimeGenerator::OUTPUT.into()],
+            env: vec![],
+        }
+    }
+
     fn new() -> Self {
         Self {
             generator: ReadingTimeGenerator::new(),